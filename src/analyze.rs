@@ -1,9 +1,15 @@
 use crate::ast::nodata;
+use crate::pass::Ir;
+use crate::pass::PassManager;
 
 pub mod compute_lambda_captures;
 pub mod compute_continuations;
 pub mod compute_continuation_captures;
+pub mod anf;
+pub mod closure_conversion;
+pub mod defunctionalize;
 pub mod strip_data;
+pub mod textual;
 
 pub use compute_continuation_captures::Literal;
 pub use compute_continuation_captures::Continuation;
@@ -22,14 +28,17 @@ pub struct AnalysisResult<'i> {
 }
 
 pub fn analyze_program<'i>(program: &nodata::Program<'i>) -> AnalysisResult<'i> {
-    let mut diagnostics = Vec::new();
+    let result = PassManager::analysis().run(Ir::Source(program.clone()))
+        .expect("analysis pipeline failed");
 
-    let program = compute_lambda_captures::transform_program(program, &mut diagnostics);
-    let program = compute_continuations::transform_program(&program);
-    let program = compute_continuation_captures::transform_program(&program);
-
-    AnalysisResult {
-        program,
-        diagnostics
+    match result.ir {
+        Ir::ContinuationCaptures(program) => AnalysisResult {
+            program,
+            diagnostics: result.output.diagnostics
+        },
+        _ => unreachable!("analysis pipeline produced {} IR", result.ir.stage())
     }
 }
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,210 @@
+use std::rc::Rc;
+use std::fmt::{self, Display, Formatter};
+use std::collections::BTreeSet;
+
+use super::compute_continuation_captures as prev;
+use prev::Literal;
+use prev::Continuation;
+
+pub use prev::Identifier;
+
+/// a local variable, either bound by a lambda or by a let
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Name<'i> {
+    Identifier(Identifier<'i>),
+    Temporary(usize)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Atom<'i> {
+    Variable(Name<'i>),
+    Lambda(Rc<Lambda<'i>>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lambda<'i> {
+    pub id: usize,
+    pub argument: Identifier<'i>,
+    /// local variables of the enclosing blocks referenced in the body
+    pub free: BTreeSet<Name<'i>>,
+    pub body: Block<'i>
+}
+
+/// binds the result of a call to a temporary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Let<'i> {
+    pub temporary: usize,
+    pub function: Atom<'i>,
+    pub argument: Atom<'i>,
+    /// local variables still needed after the call, excluding the temporary
+    pub free: BTreeSet<Name<'i>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tail<'i> {
+    Call(Atom<'i>, Atom<'i>),
+    Return(Atom<'i>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block<'i> {
+    pub lets: Vec<Let<'i>>,
+    pub tail: Tail<'i>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition<'i> {
+    pub target: Identifier<'i>,
+    pub body: Block<'i>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Program<'i> {
+    pub definitions: Vec<Definition<'i>>
+}
+
+impl<'i> Program<'i> {
+    pub fn iter(&self) -> impl Iterator<Item = &Definition<'i>> {
+        self.definitions.iter()
+    }
+}
+
+impl Block<'_> {
+    /// a block without calls evaluates to its value immediately
+    pub fn is_value(&self) -> bool {
+        self.lets.is_empty() && matches!(self.tail, Tail::Return(_))
+    }
+}
+
+fn names<'i>(captures: &BTreeSet<Identifier<'i>>, anonymous_captures: &BTreeSet<usize>) -> BTreeSet<Name<'i>> {
+    captures.iter()
+        .map(|&ident| Name::Identifier(ident))
+        .chain(anonymous_captures.iter().map(|&anon| Name::Temporary(anon)))
+        .collect()
+}
+
+fn convert_literal<'i>(lit: &Literal<'i>) -> Atom<'i> {
+    match lit {
+        Literal::Anonymous(id) => Atom::Variable(Name::Temporary(*id)),
+        Literal::Identifier(ident) => Atom::Variable(Name::Identifier(ident)),
+        Literal::Lambda(lambda) => Atom::Lambda(Rc::new(Lambda {
+            id: lambda.data.id,
+            argument: lambda.argument,
+            free: names(&lambda.data.captures, &BTreeSet::new()),
+            body: convert_block(&lambda.data.continuations, &lambda.data.result_literal)
+        }))
+    }
+}
+
+fn convert_block<'i>(continuations: &[Continuation<'i>], result_literal: &Literal<'i>) -> Block<'i> {
+    let (last, rest) = match continuations.split_last() {
+        Some(split) => split,
+        None => return Block {
+            lets: Vec::new(),
+            tail: Tail::Return(convert_literal(result_literal))
+        }
+    };
+
+    debug_assert!(matches!(result_literal, Literal::Anonymous(id) if *id == last.id));
+
+    let lets = rest.iter()
+        .zip(continuations[1..].iter())
+        .map(|(cont, next)| Let {
+            temporary: cont.id,
+            function: convert_literal(&cont.function),
+            argument: convert_literal(&cont.argument),
+            free: names(&next.captures, &next.anonymous_captures)
+        })
+        .collect();
+
+    Block {
+        lets,
+        tail: Tail::Call(convert_literal(&last.function), convert_literal(&last.argument))
+    }
+}
+
+pub fn transform_program<'i>(program: &prev::Program<'i>) -> Program<'i> {
+    Program {
+        definitions: program.iter()
+            .map(|ass| Definition {
+                target: ass.target,
+                body: convert_block(&ass.data.continuations, &ass.data.result_literal)
+            })
+            .collect()
+    }
+}
+
+impl Display for Name<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Name::Identifier(ident) => write!(f, "{}", ident),
+            Name::Temporary(id) => write!(f, "%{}", id)
+        }
+    }
+}
+
+fn write_names(f: &mut Formatter<'_>, names: &BTreeSet<Name<'_>>) -> fmt::Result {
+    let names: Vec<_> = names.iter()
+        .map(|name| name.to_string())
+        .collect();
+
+    write!(f, "[{}]", names.join(", "))
+}
+
+fn write_atom(f: &mut Formatter<'_>, atom: &Atom<'_>, level: usize) -> fmt::Result {
+    match atom {
+        Atom::Variable(name) => write!(f, "{}", name),
+        Atom::Lambda(lambda) => {
+            write!(f, "\\{} {} ", lambda.id, lambda.argument)?;
+            write_names(f, &lambda.free)?;
+            write!(f, " ")?;
+            write_block(f, &lambda.body, level)
+        }
+    }
+}
+
+fn write_block(f: &mut Formatter<'_>, block: &Block<'_>, level: usize) -> fmt::Result {
+    let indent = "    ".repeat(level + 1);
+
+    writeln!(f, "{{")?;
+
+    for binding in block.lets.iter() {
+        write!(f, "{}let %{} = ", indent, binding.temporary)?;
+        write_atom(f, &binding.function, level + 1)?;
+        write!(f, " ")?;
+        write_atom(f, &binding.argument, level + 1)?;
+        write!(f, " ")?;
+        write_names(f, &binding.free)?;
+        writeln!(f)?;
+    }
+
+    match &block.tail {
+        Tail::Call(function, argument) => {
+            write!(f, "{}tail ", indent)?;
+            write_atom(f, function, level + 1)?;
+            write!(f, " ")?;
+            write_atom(f, argument, level + 1)?;
+        }
+        Tail::Return(value) => {
+            write!(f, "{}ret ", indent)?;
+            write_atom(f, value, level + 1)?;
+        }
+    }
+
+    write!(f, "\n{}}}", "    ".repeat(level))
+}
+
+impl Display for Program<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for def in self.iter() {
+            write!(f, "{} = ", def.target)?;
+            write_block(f, &def.body, 0)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,71 @@
+use super::*;
+use crate::parser::LambdaParser;
+use crate::analyze::analyze_program;
+
+fn convert(code: &str) -> Program<'_> {
+    let program = LambdaParser::parse_program(code).unwrap();
+    transform_program(&analyze_program(&program).program)
+}
+
+#[test]
+fn test_values() {
+    let program = convert("k = a -> b -> a\ny = k\n");
+
+    assert!(program.iter().all(|def| def.body.is_value()));
+    assert_eq!(program.to_string(), "\
+k = {
+    ret \\0 a [] {
+        ret \\1 b [a] {
+            ret a
+        }
+    }
+}
+y = {
+    ret k
+}
+");
+}
+
+#[test]
+fn test_lets() {
+    let program = convert("k = a -> b -> a\nx = k k k\n");
+
+    assert!(!program.definitions[1].body.is_value());
+    assert_eq!(program.definitions[1].body, Block {
+        lets: vec![Let {
+            temporary: 0,
+            function: Atom::Variable(Name::Identifier("k")),
+            argument: Atom::Variable(Name::Identifier("k")),
+            free: BTreeSet::new()
+        }],
+        tail: Tail::Call(Atom::Variable(Name::Temporary(0)), Atom::Variable(Name::Identifier("k")))
+    });
+}
+
+#[test]
+fn test_free() {
+    let program = convert("pair = a -> b -> f -> f a b\nfirst = p -> p (a -> b -> a) p\n");
+
+    assert_eq!(program.to_string(), "\
+pair = {
+    ret \\0 a [] {
+        ret \\1 b [a] {
+            ret \\2 f [a, b] {
+                let %0 = f a [b]
+                tail %0 b
+            }
+        }
+    }
+}
+first = {
+    ret \\0 p [] {
+        let %0 = p \\1 a [] {
+            ret \\2 b [a] {
+                ret a
+            }
+        } [p]
+        tail %0 p
+    }
+}
+");
+}
@@ -0,0 +1,397 @@
+use std::fmt::{self, Display, Formatter};
+use std::collections::BTreeSet;
+use std::collections::BTreeMap;
+
+use super::anf;
+use anf::Name;
+use anf::Atom;
+use anf::Tail;
+use anf::Block;
+
+pub use anf::Identifier;
+
+/// the name of a function argument or an environment slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Local<'i> {
+    Unnamed,
+    Temporary(usize),
+    Identifier(Identifier<'i>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variable<'i> {
+    Argument,
+    Capture(usize),
+    Global(Identifier<'i>)
+}
+
+/// allocates an environment record for a function
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Closure<'i> {
+    pub function: usize,
+    pub captures: Vec<Variable<'i>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Value<'i> {
+    Variable(Variable<'i>),
+    Closure(Closure<'i>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Body<'i> {
+    /// calls a function, passing either the current continuation or a new one
+    Call {
+        function: Value<'i>,
+        argument: Value<'i>,
+        continuation: Option<Closure<'i>>
+    },
+    /// passes a value to the current continuation
+    Return(Value<'i>)
+}
+
+/// identifies where a function came from in the source program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionName<'i> {
+    pub assignment: Identifier<'i>,
+    pub lambda: Option<usize>,
+    pub continuation: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function<'i> {
+    pub name: FunctionName<'i>,
+    pub argument: Local<'i>,
+    pub captures: Vec<Local<'i>>,
+    pub body: Body<'i>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Initializer<'i> {
+    Value(Value<'i>),
+    /// calls a function without captures and stores its result
+    Call(usize)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition<'i> {
+    pub target: Identifier<'i>,
+    /// functions introduced by this definition, every function comes after
+    /// the functions it references
+    pub functions: Vec<usize>,
+    pub value: Initializer<'i>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Program<'i> {
+    pub functions: Vec<Function<'i>>,
+    pub definitions: Vec<Definition<'i>>
+}
+
+impl<'i> Program<'i> {
+    pub fn iter(&self) -> impl Iterator<Item = &Definition<'i>> {
+        self.definitions.iter()
+    }
+
+    pub fn function(&self, id: usize) -> &Function<'i> {
+        &self.functions[id]
+    }
+}
+
+/// the variables visible inside the function that is being built
+struct Scope<'i> {
+    argument: Local<'i>,
+    captures: Vec<Local<'i>>
+}
+
+impl<'i> Scope<'i> {
+    /// identifiers that are not in scope are globals, unless an enclosing
+    /// lambda binds them and they were not captured
+    fn resolve(&self, local: Local<'i>, bound: &BTreeMap<Identifier<'i>, usize>) -> Variable<'i> {
+        if local == self.argument {
+            Variable::Argument
+        } else if let Some(i) = self.captures.iter().position(|&cap| cap == local) {
+            Variable::Capture(i)
+        } else if let Local::Identifier(ident) = local {
+            if bound.contains_key(ident) {
+                panic!("uncaptured local '{}' referenced!", ident);
+            }
+
+            Variable::Global(ident)
+        } else {
+            panic!("uncaptured local '{:?}' referenced!", local);
+        }
+    }
+}
+
+impl<'i> From<Name<'i>> for Local<'i> {
+    fn from(name: Name<'i>) -> Self {
+        match name {
+            Name::Identifier(ident) => Local::Identifier(ident),
+            Name::Temporary(id) => Local::Temporary(id)
+        }
+    }
+}
+
+fn locals<'i>(names: &BTreeSet<Name<'i>>) -> Vec<Local<'i>> {
+    names.iter()
+        .map(|&name| Local::from(name))
+        .collect()
+}
+
+/// how a function is entered, a lambda is called with an argument, a
+/// continuation with the result of a call and an initializer once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Lambda,
+    Continuation,
+    Initializer
+}
+
+impl Role {
+    fn of(name: &FunctionName<'_>) -> Self {
+        match name {
+            FunctionName { continuation: 0, lambda: Some(_), .. } => Role::Lambda,
+            FunctionName { continuation: 0, lambda: None, .. } => Role::Initializer,
+            _ => Role::Continuation
+        }
+    }
+}
+
+/// functions are equal up to the names of their argument and captures if
+/// they have the same role, as many captures and the same body, bodies refer
+/// to variables by position and to other functions by id
+type FunctionKey<'i> = (Role, usize, Body<'i>);
+
+struct Context<'i> {
+    assignment: Identifier<'i>,
+    program: Program<'i>,
+    functions: Vec<usize>,
+    shared: BTreeMap<FunctionKey<'i>, usize>,
+    /// arguments of the enclosing lambdas, counted because they can shadow
+    bound: BTreeMap<Identifier<'i>, usize>
+}
+
+impl<'i> Context<'i> {
+    /// returns the id of an equal function if there is one, so every
+    /// implementation exists only once in the program
+    fn add_function(&mut self, function: Function<'i>) -> usize {
+        let key = (Role::of(&function.name), function.captures.len(), function.body.clone());
+
+        if let Some(&id) = self.shared.get(&key) {
+            return id;
+        }
+
+        let id = self.program.functions.len();
+        self.program.functions.push(function);
+        self.functions.push(id);
+        self.shared.insert(key, id);
+        id
+    }
+
+    fn unbind(&mut self, argument: Identifier<'i>) {
+        let count = self.bound.get_mut(argument).expect("unbinding an unbound argument");
+        *count -= 1;
+
+        if *count == 0 {
+            self.bound.remove(argument);
+        }
+    }
+
+    fn convert_atom(&mut self, atom: &Atom<'i>, scope: &Scope<'i>) -> Value<'i> {
+        match atom {
+            Atom::Variable(name) => Value::Variable(scope.resolve(Local::from(*name), &self.bound)),
+            Atom::Lambda(lambda) => {
+                let name = FunctionName {
+                    assignment: self.assignment,
+                    lambda: Some(lambda.id),
+                    continuation: 0
+                };
+                let captures = locals(&lambda.free);
+
+                *self.bound.entry(lambda.argument).or_insert(0) += 1;
+                let entry = self.convert_block(name, Local::Identifier(lambda.argument), captures.clone(), &lambda.body);
+                self.unbind(lambda.argument);
+
+                Value::Closure(convert_closure(entry, &captures, scope, &self.bound))
+            }
+        }
+    }
+
+    /// converts a block into one function per call, the function after a
+    /// let receives its temporary as the argument and captures the variables
+    /// that are still needed, returns the entry function
+    fn convert_block(&mut self, name: FunctionName<'i>, argument: Local<'i>, captures: Vec<Local<'i>>, block: &Block<'i>) -> usize {
+        let mut scopes = vec![Scope { argument, captures }];
+        scopes.extend(block.lets.iter().map(|binding| Scope {
+            argument: Local::Temporary(binding.temporary),
+            captures: locals(&binding.free)
+        }));
+
+        let mut next = None;
+
+        for (i, scope) in scopes.into_iter().enumerate().rev() {
+            let body = match block.lets.get(i) {
+                Some(binding) => {
+                    let continuation = next.map(|(id, captures): (usize, Vec<Local<'i>>)| convert_closure(id, &captures, &scope, &self.bound));
+                    let function = self.convert_atom(&binding.function, &scope);
+                    let argument = self.convert_atom(&binding.argument, &scope);
+
+                    Body::Call { function, argument, continuation }
+                }
+                None => match &block.tail {
+                    Tail::Call(function, argument) => {
+                        let function = self.convert_atom(function, &scope);
+                        let argument = self.convert_atom(argument, &scope);
+
+                        Body::Call { function, argument, continuation: None }
+                    }
+                    Tail::Return(value) => Body::Return(self.convert_atom(value, &scope))
+                }
+            };
+
+            let id = self.add_function(Function {
+                name: FunctionName { continuation: i, ..name },
+                argument: scope.argument,
+                captures: scope.captures.clone(),
+                body
+            });
+
+            next = Some((id, scope.captures));
+        }
+
+        next.expect("block without entry").0
+    }
+}
+
+fn convert_closure<'i>(function: usize, captures: &[Local<'i>], scope: &Scope<'i>, bound: &BTreeMap<Identifier<'i>, usize>) -> Closure<'i> {
+    Closure {
+        function,
+        captures: captures.iter()
+            .map(|&cap| scope.resolve(cap, bound))
+            .collect()
+    }
+}
+
+pub fn transform_program<'i>(program: &anf::Program<'i>) -> Program<'i> {
+    let mut ctx = Context {
+        assignment: "",
+        program: Program::default(),
+        functions: Vec::new(),
+        shared: BTreeMap::new(),
+        bound: BTreeMap::new()
+    };
+
+    for def in program.iter() {
+        ctx.assignment = def.target;
+
+        let globals = Scope { argument: Local::Unnamed, captures: Vec::new() };
+
+        let value = match &def.body.tail {
+            Tail::Return(value) if def.body.is_value() => {
+                Initializer::Value(ctx.convert_atom(value, &globals))
+            }
+            _ => {
+                let name = FunctionName {
+                    assignment: def.target,
+                    lambda: None,
+                    continuation: 0
+                };
+
+                Initializer::Call(ctx.convert_block(name, Local::Unnamed, Vec::new(), &def.body))
+            }
+        };
+
+        let functions = std::mem::take(&mut ctx.functions);
+        ctx.program.definitions.push(Definition {
+            target: def.target,
+            functions,
+            value
+        });
+    }
+
+    ctx.program
+}
+
+impl Display for Local<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Local::Unnamed => write!(f, "_"),
+            Local::Temporary(id) => write!(f, "%{}", id),
+            Local::Identifier(ident) => write!(f, "{}", ident)
+        }
+    }
+}
+
+impl Display for Variable<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Variable::Argument => write!(f, "arg"),
+            Variable::Capture(i) => write!(f, "cap{}", i),
+            Variable::Global(ident) => write!(f, "{}", ident)
+        }
+    }
+}
+
+impl Display for Closure<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let captures: Vec<_> = self.captures.iter()
+            .map(|cap| cap.to_string())
+            .collect();
+
+        write!(f, "@{} [{}]", self.function, captures.join(", "))
+    }
+}
+
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Variable(var) => write!(f, "{}", var),
+            Value::Closure(closure) => write!(f, "({})", closure)
+        }
+    }
+}
+
+impl Display for Body<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Call { function, argument, continuation: None } => write!(f, "{} {}", function, argument),
+            Body::Call { function, argument, continuation: Some(cont) } => write!(f, "{} {} then {}", function, argument, cont),
+            Body::Return(value) => write!(f, "ret {}", value)
+        }
+    }
+}
+
+impl Display for FunctionName<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let lambda = self.lambda.map(|id| id + 1).unwrap_or(0);
+        write!(f, "{}.{}.{}", self.assignment, lambda, self.continuation)
+    }
+}
+
+impl Display for Program<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for def in self.iter() {
+            for &id in def.functions.iter() {
+                let function = self.function(id);
+                let captures: Vec<_> = function.captures.iter()
+                    .map(|cap| cap.to_string())
+                    .collect();
+
+                writeln!(f, "@{} {} {} [{}] = {}",
+                    id, function.name, function.argument, captures.join(", "), function.body
+                )?;
+            }
+
+            match &def.value {
+                Initializer::Value(value) => writeln!(f, "{} = {}", def.target, value)?,
+                Initializer::Call(id) => writeln!(f, "{} = call @{}", def.target, id)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,80 @@
+use super::*;
+use crate::parser::LambdaParser;
+use crate::analyze::analyze_program;
+
+fn convert(code: &str) -> String {
+    let program = LambdaParser::parse_program(code).unwrap();
+    transform_program(&anf::transform_program(&analyze_program(&program).program)).to_string()
+}
+
+#[test]
+fn test_lambdas() {
+    assert_eq!(convert("k = a -> b -> a\n"), "\
+@0 k.2.0 b [a] = ret cap0
+@1 k.1.0 a [] = ret (@0 [arg])
+k = (@1 [])
+");
+}
+
+#[test]
+fn test_definitions() {
+    assert_eq!(convert("k = a -> b -> a\nx = k k k\ny = x\n"), "\
+@0 k.2.0 b [a] = ret cap0
+@1 k.1.0 a [] = ret (@0 [arg])
+k = (@1 [])
+@2 x.0.1 %0 [] = arg k
+@3 x.0.0 _ [] = k k then @2 []
+x = call @3
+y = x
+");
+}
+
+#[test]
+fn test_continuations() {
+    assert_eq!(convert("f = g -> x -> g (g x) x\n"), "\
+@0 f.2.2 %1 [x] = arg cap0
+@1 f.2.1 %0 [g, x] = cap0 arg then @0 [cap1]
+@2 f.2.0 x [g] = cap0 arg then @1 [cap0, arg]
+@3 f.1.0 g [] = ret (@2 [arg])
+f = (@3 [])
+");
+}
+
+#[test]
+fn test_shared_functions() {
+    // l calls f like the continuation of h, but is entered as a lambda
+    assert_eq!(convert("f = a -> a\ng = x -> x\nh = y -> f (f y)\nk = z -> f (f z)\nl = y -> f y\nx = h k\ny = h k\n"), "\
+@0 f.1.0 a [] = ret arg
+f = (@0 [])
+g = (@0 [])
+@1 h.1.1 %0 [] = f arg
+@2 h.1.0 y [] = f arg then @1 []
+h = (@2 [])
+k = (@2 [])
+@3 l.1.0 y [] = f arg
+l = (@3 [])
+@4 x.0.0 _ [] = h k
+x = call @4
+y = call @4
+");
+}
+
+#[test]
+fn test_structure() {
+    let program = LambdaParser::parse_program("f = g -> x -> g (y -> y x)\n").unwrap();
+    let flat = transform_program(&anf::transform_program(&analyze_program(&program).program));
+
+    let def = &flat.definitions[0];
+    assert_eq!(def.functions, vec![0, 1, 2]);
+    assert_eq!(def.value, Initializer::Value(Value::Closure(Closure { function: 2, captures: vec![] })));
+
+    let inner = flat.function(0);
+    assert_eq!(inner.name, FunctionName { assignment: "f", lambda: Some(2), continuation: 0 });
+    assert_eq!(inner.argument, Local::Identifier("y"));
+    assert_eq!(inner.captures, vec![Local::Identifier("x")]);
+    assert_eq!(inner.body, Body::Call {
+        function: Value::Variable(Variable::Argument),
+        argument: Value::Variable(Variable::Capture(0)),
+        continuation: None
+    });
+}
@@ -3,6 +3,8 @@ use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 
 use crate::ast::generic;
+use crate::ast::fold;
+use crate::ast::fold::Fold;
 use super::compute_continuations as prev;
 
 use prev::GenericLiteral;
@@ -38,8 +40,13 @@ pub type Continuation<'i> = GenericContinuation<'i, PassData>;
 pub type AssignmentData<'i> = GenericAssignmentData<'i, PassData>;
 pub type LambdaData<'i> = GenericLambdaData<'i, PassData>;
 
+impl generic::DataDisplay for AssignmentData<'_> {}
+impl generic::DataDisplay for LambdaData<'_> {}
+
 impl<'i> generic::ASTData<'i> for PassData {
+    type ProgramData = ();
     type AssignmentData = AssignmentData<'i>;
+    type ApplicationData = ();
     type LambdaData = LambdaData<'i>;
 }
 
@@ -98,40 +105,58 @@ impl<'i> Context<'i> {
 }
 
 pub fn transform_program<'i>(program: &prev::Program<'i>) -> Program<'i> {
-    Program {
-        assignments: program.iter()
-            .map(transform_assignment)
-            .collect(),
-        data: ()
-    }
+    Context::new().fold_program(program)
 }
 
-fn transform_assignment<'i>(ass: &prev::Assignment<'i>) -> Assignment<'i> {
-    let (value, continuations, lit) = transform_continuations(
-        &ass.value, PrevApplicationData::from_assignment(ass)
-    );
+impl<'i> Fold<'i, prev::PassData, PassData> for Context<'i> {
+    fn fold_program_data(&mut self, _: &prev::Program<'i>, _: &[Assignment<'i>]) {}
+    fn fold_application_data(&mut self, _: &prev::Application<'i>, _: &[Expression<'i>]) {}
+
+    fn fold_assignment_data(&mut self, ass: &prev::Assignment<'i>, _: &Rc<Application<'i>>) -> AssignmentData<'i> {
+        let (continuations, lit) = transform_continuations(
+            PrevApplicationData::from_assignment(ass), self
+        );
 
-    Assignment {
-        target: ass.target,
-        value,
-        data: AssignmentData {
+        AssignmentData {
             continuations,
             result_literal: lit
         }
     }
+
+    fn fold_lambda_data(&mut self, lambda: &prev::Lambda<'i>, _: &Rc<Application<'i>>) -> LambdaData<'i> {
+        let (continuations, lit) = transform_continuations(
+            PrevApplicationData::from_lambda(lambda), self
+        );
+
+        LambdaData {
+            id: lambda.data.id,
+            captures: lambda.data.captures.clone(),
+            continuations,
+            result_literal: lit
+        }
+    }
+
+    fn fold_assignment(&mut self, ass: &prev::Assignment<'i>) -> Assignment<'i> {
+        fold::walk_assignment(&mut Context::new(), ass)
+    }
+
+    fn fold_lambda(&mut self, lambda: &prev::Lambda<'i>) -> Rc<Lambda<'i>> {
+        let lambda = fold::walk_lambda(&mut Context::new(), lambda);
+
+        self.push(lambda.clone());
+
+        lambda
+    }
 }
 
-fn transform_continuations<'i>(app: &prev::Application<'i>, data: PrevApplicationData<'i, '_>)
-    -> (Rc<Application<'i>>, Vec<Continuation<'i>>, Literal<'i>)
+fn transform_continuations<'i>(data: PrevApplicationData<'i, '_>, ctx: &mut Context<'i>)
+    -> (Vec<Continuation<'i>>, Literal<'i>)
 {
-    let mut ctx = Context::new();
-    let app = transform_application(app, &mut ctx);
-
     let mut continuations: Vec<_> = data.continuations.iter()
-        .map(|cont| transform_continuation(cont, &mut ctx))
+        .map(|cont| transform_continuation(cont, ctx))
         .collect();
 
-    let lit = transform_literal(&data.result_literal, &mut ctx);
+    let lit = transform_literal(&data.result_literal, ctx);
 
     let mut next: Option<&Continuation<'i>> = None;
     for cur in continuations.iter_mut().rev() {
@@ -155,7 +180,7 @@ fn transform_continuations<'i>(app: &prev::Application<'i>, data: PrevApplicatio
         next = Some(cur);
     }
 
-    (app, continuations, lit)
+    (continuations, lit)
 }
 
 fn transform_continuation<'i>(cont: &prev::Continuation<'i>, ctx: &mut Context<'i>) -> Continuation<'i> {
@@ -191,41 +216,3 @@ fn compute_literal_captures<'i>(lit: Literal<'i>, cont: &mut Continuation<'i>, d
         }
     }
 }
-
-fn transform_application<'i>(app: &prev::Application<'i>, ctx: &mut Context<'i>) -> Rc<Application<'i>> {
-    Rc::new(Application {
-        head: transform_expression(&app.head, ctx),
-        tail: app.tail.as_ref()
-            .map(|app| transform_application(app, ctx)),
-        data: ()
-    })
-}
-
-fn transform_expression<'i>(expr: &prev::Expression<'i>, ctx: &mut Context<'i>) -> Expression<'i> {
-    match expr {
-        prev::Expression::Parenthesis(app) => Expression::Parenthesis(transform_application(app, ctx)),
-        prev::Expression::Lambda(lambda) => Expression::Lambda(transform_lambda(lambda, ctx)),
-        prev::Expression::Identifier(ident) => Expression::Identifier(ident)
-    }
-}
-
-fn transform_lambda<'i>(lambda: &prev::Lambda<'i>, ctx: &mut Context<'i>) -> Rc<Lambda<'i>> {
-    let (body, continuations, lit) = transform_continuations(
-        &lambda.body, PrevApplicationData::from_lambda(lambda)
-    );
-
-    let lambda = Rc::new(Lambda {
-        argument: lambda.argument,
-        body,
-        data: LambdaData {
-            id: lambda.data.id,
-            captures: lambda.data.captures.clone(),
-            continuations,
-            result_literal: lit
-        }
-    });
-
-    ctx.push(lambda.clone());
-
-    lambda
-}
@@ -5,6 +5,8 @@ use std::rc::Rc;
 use std::collections::BTreeSet;
 
 use crate::ast::generic;
+use crate::ast::fold;
+use crate::ast::fold::Fold;
 use super::compute_lambda_captures as prev;
 
 #[derive(Clone)]
@@ -53,8 +55,13 @@ pub type Continuation<'i> = GenericContinuation<'i, PassData>;
 pub type AssignmentData<'i> = GenericAssignmentData<'i, PassData>;
 pub type LambdaData<'i> = GenericLambdaData<'i, PassData>;
 
+impl generic::DataDisplay for AssignmentData<'_> {}
+impl generic::DataDisplay for LambdaData<'_> {}
+
 impl<'i> generic::ASTData<'i> for PassData {
+    type ProgramData = ();
     type AssignmentData = AssignmentData<'i>;
+    type ApplicationData = ();
     type LambdaData = LambdaData<'i>;
 }
 
@@ -68,14 +75,16 @@ pub type Program<'i> = generic::Program<'i, PassData>;
 #[derive(Debug, Clone)]
 struct Context<'i> {
     current_id: usize,
-    continuations: Vec<Continuation<'i>>
+    continuations: Vec<Continuation<'i>>,
+    result_literal: Option<Literal<'i>>
 }
 
 impl<'i> Context<'i> {
     fn new() -> Self {
         Context {
             current_id: 0,
-            continuations: Vec::new()
+            continuations: Vec::new(),
+            result_literal: None
         }
     }
 
@@ -91,100 +100,72 @@ impl<'i> Context<'i> {
 
         Literal::Anonymous(id)
     }
-}
 
-pub fn transform_program<'i>(program: &prev::Program<'i>) -> Program<'i> {
-    Program {
-        assignments: program.iter()
-            .map(transform_assignment)
-            .collect(),
-        data: ()
+    fn literal(&mut self, expr: &Expression<'i>) -> Literal<'i> {
+        match expr {
+            Expression::Parenthesis(_) => self.take_result_literal(),
+            Expression::Lambda(lambda) => Literal::Lambda(lambda.clone()),
+            Expression::Identifier(ident) => Literal::Identifier(ident)
+        }
     }
-}
-
-fn transform_assignment<'i>(ass: &prev::Assignment<'i>) -> Assignment<'i> {
-    let (value, continuations, lit) = transform_application(&ass.value);
 
-    Assignment {
-        target: ass.target,
-        value,
-        data: AssignmentData {
-            continuations,
-            result_literal: lit
-        }
+    fn take_result_literal(&mut self) -> Literal<'i> {
+        self.result_literal.take().expect("empty application in AST")
     }
 }
 
-fn transform_application<'i>(app: &prev::Application<'i>)
-    -> (Rc<Application<'i>>, Vec<Continuation<'i>>, Literal<'i>)
-{
-    let mut ctx = Context::new();
-
-    let (app, lit) = transform_application_initial(app, &mut ctx, |_, lit| lit);
-
-    (app, ctx.continuations, lit)
+pub fn transform_program<'i>(program: &prev::Program<'i>) -> Program<'i> {
+    Context::new().fold_program(program)
 }
 
-fn transform_application_initial<'i, F>(app: &prev::Application<'i>, ctx: &mut Context<'i>, f: F)
-    -> (Rc<Application<'i>>, Literal<'i>)
-    where F: FnOnce(&mut Context<'i>, Literal<'i>) -> Literal<'i>
-{
-    let next = app.tail.as_deref();
-
-    let (head, lit) = transform_expression(&app.head, ctx);
-    let lit = f(ctx, lit);
-    let (tail, lit) = transform_application_continuation(next, ctx, lit);
-
-    (Rc::new(Application {
-        head,
-        tail,
-        data: ()
-    }), lit)
-}
+impl<'i> Fold<'i, prev::PassData, PassData> for Context<'i> {
+    fn fold_program_data(&mut self, _: &prev::Program<'i>, _: &[Assignment<'i>]) {}
+    fn fold_application_data(&mut self, _: &prev::Application<'i>, _: &[Expression<'i>]) {}
+
+    fn fold_assignment_data(&mut self, _: &prev::Assignment<'i>, _: &Rc<Application<'i>>) -> AssignmentData<'i> {
+        AssignmentData {
+            continuations: std::mem::take(&mut self.continuations),
+            result_literal: self.take_result_literal()
+        }
+    }
 
-fn transform_application_continuation<'i>(app: Option<&prev::Application<'i>>, ctx: &mut Context<'i>, lit1: Literal<'i>)
-    -> (Option<Rc<Application<'i>>>, Literal<'i>)
-{
-    if let Some(app) = app {
-        let (app, lit2) = transform_application_initial(app, ctx, move |ctx, lit2| ctx.push(lit1, lit2));
+    fn fold_lambda_data(&mut self, lambda: &prev::Lambda<'i>, _: &Rc<Application<'i>>) -> LambdaData<'i> {
+        LambdaData {
+            id: lambda.data.id,
+            captures: lambda.data.captures.clone(),
+            continuations: std::mem::take(&mut self.continuations),
+            result_literal: self.take_result_literal()
+        }
+    }
 
-        (Some(app), lit2)
-    } else {
-        (None, lit1)
+    fn fold_assignment(&mut self, ass: &prev::Assignment<'i>) -> Assignment<'i> {
+        fold::walk_assignment(&mut Context::new(), ass)
     }
-}
 
-fn transform_expression<'i>(expr: &prev::Expression<'i>, ctx: &mut Context<'i>)
-    -> (Expression<'i>, Literal<'i>)
-{
-    match expr {
-        prev::Expression::Parenthesis(nonlit) => {
-            let (head_app, anon1) = transform_application_initial(&nonlit, ctx, |_, lit| lit);
+    fn fold_application(&mut self, app: &prev::Application<'i>) -> Rc<Application<'i>> {
+        let mut expressions = Vec::with_capacity(app.expressions.len());
+        let mut lit = None;
 
-            (Expression::Parenthesis(head_app), anon1)
-        },
-        prev::Expression::Lambda(lambda) => {
-            let lambda = transform_lambda(&lambda);
+        for expr in app.iter() {
+            let expr = self.fold_expression(expr);
+            let lit2 = self.literal(&expr);
+            expressions.push(expr);
 
-            (Expression::Lambda(lambda.clone()), Literal::Lambda(lambda))
-        }
-        prev::Expression::Identifier(ident) => {
-            (Expression::Identifier(ident), Literal::Identifier(ident))
+            lit = Some(match lit {
+                Some(lit1) => self.push(lit1, lit2),
+                None => lit2
+            });
         }
-    }
-}
 
-fn transform_lambda<'i>(lambda: &prev::Lambda<'i>) -> Rc<Lambda<'i>> {
-    let (body, continuations, lit) = transform_application(&lambda.body);
+        self.result_literal = lit;
 
-    Rc::new(Lambda {
-        argument: lambda.argument,
-        body,
-        data: LambdaData {
-            id: lambda.data.id,
-            captures: lambda.data.captures.clone(),
-            continuations,
-            result_literal: lit
-        }
-    })
+        Rc::new(Application {
+            expressions,
+            data: ()
+        })
+    }
+
+    fn fold_lambda(&mut self, lambda: &prev::Lambda<'i>) -> Rc<Lambda<'i>> {
+        fold::walk_lambda(&mut Context::new(), lambda)
+    }
 }
@@ -2,6 +2,8 @@ use std::rc::Rc;
 use std::collections::BTreeSet;
 
 use crate::ast::generic;
+use crate::ast::fold;
+use crate::ast::fold::Fold;
 use crate::ast::nodata as prev;
 
 #[derive(Debug, Clone)]
@@ -13,7 +15,12 @@ pub struct LambdaData<'i> {
 #[derive(Debug, Clone, Copy)]
 pub struct PassData;
 
+impl generic::DataDisplay for LambdaData<'_> {}
+
 impl<'i> generic::ASTData<'i> for PassData {
+    type ProgramData = ();
+    type AssignmentData = ();
+    type ApplicationData = ();
     type LambdaData = LambdaData<'i>;
 }
 
@@ -28,6 +35,7 @@ pub type Program<'i> = generic::Program<'i, PassData>;
 struct Context<'i> {
     current_assignment: Identifier<'i>,
     current_id: usize,
+    lambda_id: usize,
     diagnostics: Vec<String>,
     globals: BTreeSet<Identifier<'i>>,
     locals: BTreeSet<Identifier<'i>>,
@@ -39,6 +47,7 @@ impl<'i> Context<'i> {
         Context {
             current_assignment,
             current_id: 0,
+            lambda_id: 0,
             diagnostics: Vec::new(),
             globals: BTreeSet::new(),
             locals: BTreeSet::new(),
@@ -56,6 +65,7 @@ impl<'i> Context<'i> {
         Context {
             current_assignment: self.current_assignment,
             current_id: self.current_id,
+            lambda_id: self.lambda_id,
             diagnostics: Vec::new(),
             globals: self.globals.clone(),
             locals: self.locals.clone(),
@@ -63,9 +73,11 @@ impl<'i> Context<'i> {
         }
     }
 
-    fn split_with_local(&self, local: Identifier<'i>) -> Self {
+    fn split_with_lambda(&mut self, argument: Identifier<'i>) -> Self {
+        let id = self.get_id();
         let mut ctx = self.split();
-        ctx.add_local(local);
+        ctx.lambda_id = id;
+        ctx.add_local(argument);
         ctx
     }
 
@@ -102,92 +114,75 @@ impl<'i> Context<'i> {
     }
 }
 
-pub fn transform_program<'i>(program: &prev::Program<'i>, diagnostics: &mut Vec<String>) -> Program<'i> {
+/// reports undefined names and redefinitions, this runs on the source
+/// program so that optimizations cannot remove the references first
+pub fn check_program<'i>(program: &prev::Program<'i>, diagnostics: &mut Vec<String>) {
     let mut ctx = Context::new("");
 
-    let asss: Vec<_> = program.iter()
-        .map(|ass| {
-            let ass = transform_assignment(ass, &mut ctx);
-            ctx.add_global(ass.target);
-            ass
-        })
-        .collect();
+    ctx.fold_program(program);
 
     diagnostics.extend(ctx.diagnostics);
+}
 
-    Program { assignments: asss, data: () }
+pub fn transform_program<'i>(program: &prev::Program<'i>) -> Program<'i> {
+    Context::new("").fold_program(program)
 }
 
-fn transform_assignment<'i>(ass: &prev::Assignment<'i>, ctx: &mut Context<'i>) -> Assignment<'i> {
-    if ctx.contains(ass.target) {
-        ctx.add_diagnostic("error", format!("redefinition of '{}'", ass.target));
-    }
+impl<'i> Fold<'i, prev::NoData, PassData> for Context<'i> {
+    fn fold_program_data(&mut self, _: &prev::Program<'i>, _: &[Assignment<'i>]) {}
+    fn fold_assignment_data(&mut self, _: &prev::Assignment<'i>, _: &Rc<Application<'i>>) {}
+    fn fold_application_data(&mut self, _: &prev::Application<'i>, _: &[Expression<'i>]) {}
 
-    ctx.set_assignment(ass.target);
+    fn fold_lambda_data(&mut self, lambda: &prev::Lambda<'i>, _: &Rc<Application<'i>>) -> LambdaData<'i> {
+        self.referenced.remove(lambda.argument);
+        let captures: BTreeSet<_> = self.referenced.intersection(&self.locals)
+            .copied().collect();
 
-    Assignment {
-        target: ass.target,
-        value: transform_application(&ass.value, ctx),
-        data: ()
+        LambdaData {
+            id: self.lambda_id,
+            captures
+        }
     }
-}
 
-fn transform_application<'i>(app: &prev::Application<'i>, ctx: &mut Context<'i>) -> Rc<Application<'i>> {
-    let head = transform_expression(&app.head, ctx);
-    let tail = app.tail.as_ref()
-        .map(|tail| transform_application(&tail, ctx));
+    fn fold_assignment(&mut self, ass: &prev::Assignment<'i>) -> Assignment<'i> {
+        if self.contains(ass.target) {
+            self.add_diagnostic("error", format!("redefinition of '{}'", ass.target));
+        }
 
-    Rc::new(Application {
-        head,
-        tail,
-        data: ()
-    })
-}
+        self.set_assignment(ass.target);
 
-fn transform_expression<'i>(expr: &prev::Expression<'i>, ctx: &mut Context<'i>) -> Expression<'i> {
-    match expr {
-        prev::Expression::Identifier(ident) => {
-            ctx.add_referenced(ident);
-
-            if !ctx.contains(ident) {
-                if ident == &ctx.current_assignment {
-                    ctx.add_diagnostic("error", format!(
-                        "name '{}' referenced in its definition",
-                        ident
-                    ));
-                } else {
-                    ctx.add_diagnostic("error", format!(
-                        "undefined name '{}' in definition of '{}'",
-                        ident, ctx.current_assignment
-                    ));
-                }
+        let ass = fold::walk_assignment(self, ass);
+        self.add_global(ass.target);
+        ass
+    }
+
+    fn fold_identifier(&mut self, ident: Identifier<'i>) -> Identifier<'i> {
+        self.add_referenced(ident);
+
+        if !self.contains(ident) {
+            if ident == self.current_assignment {
+                self.add_diagnostic("error", format!(
+                    "name '{}' referenced in its definition",
+                    ident
+                ));
+            } else {
+                self.add_diagnostic("error", format!(
+                    "undefined name '{}' in definition of '{}'",
+                    ident, self.current_assignment
+                ));
             }
+        }
 
-            Expression::Identifier(ident)
-        },
-        prev::Expression::Parenthesis(app) => Expression::Parenthesis(transform_application(app, ctx)),
-        prev::Expression::Lambda(lambda) => Expression::Lambda(transform_lambda(lambda, ctx))
+        ident
     }
-}
 
-fn transform_lambda<'i>(lambda: &prev::Lambda<'i>, ctx: &mut Context<'i>) -> Rc<Lambda<'i>> {
-    let id = ctx.get_id();
-    let mut subctx = ctx.split_with_local(lambda.argument);
+    fn fold_lambda(&mut self, lambda: &prev::Lambda<'i>) -> Rc<Lambda<'i>> {
+        let mut subctx = self.split_with_lambda(lambda.argument);
 
-    let body = transform_application(&lambda.body, &mut subctx);
+        let lambda = fold::walk_lambda(&mut subctx, lambda);
 
-    subctx.referenced.remove(lambda.argument);
-    let captures: BTreeSet<_> = subctx.referenced.intersection(&ctx.locals)
-        .copied().collect();
+        self.merge(subctx);
 
-    ctx.merge(subctx);
-
-    Rc::new(Lambda {
-        argument: lambda.argument,
-        body,
-        data: LambdaData {
-            id,
-            captures
-        }
-    })
+        lambda
+    }
 }
@@ -0,0 +1,251 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::closure_conversion as prev;
+use prev::FunctionName;
+use prev::Local;
+
+pub use prev::Identifier;
+pub use prev::Variable;
+
+/// which dispatch function a record is interpreted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dispatch {
+    /// function values, called with an argument and a continuation
+    Apply,
+    /// continuation frames, called with the result of a call
+    Resume,
+    /// definition initializers, called once with an empty continuation
+    Run
+}
+
+/// a tagged record, the tag selects a case of its dispatch function and the
+/// fields hold the captured variables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<'i> {
+    pub tag: usize,
+    pub fields: Vec<Variable<'i>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value<'i> {
+    Variable(Variable<'i>),
+    /// a function value, interpreted by apply
+    Record(Record<'i>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body<'i> {
+    /// calls apply on a function record, optionally pushing a resume record
+    /// onto the current continuation first
+    Apply {
+        function: Value<'i>,
+        argument: Value<'i>,
+        continuation: Option<Record<'i>>
+    },
+    /// pops the current continuation and calls resume on it
+    Resume(Value<'i>)
+}
+
+/// one branch of a dispatch function, the argument is bound to the value
+/// passed to the dispatch and the fields to those of the record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case<'i> {
+    pub name: FunctionName<'i>,
+    pub argument: Local<'i>,
+    pub fields: Vec<Local<'i>>,
+    pub body: Body<'i>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Initializer<'i> {
+    Value(Value<'i>),
+    /// runs a case of the run dispatch and stores its result
+    Run(usize)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition<'i> {
+    pub target: Identifier<'i>,
+    pub value: Initializer<'i>
+}
+
+/// a first-order program, every tag indexes the cases of its dispatch
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Program<'i> {
+    pub apply: Vec<Case<'i>>,
+    pub resume: Vec<Case<'i>>,
+    pub run: Vec<Case<'i>>,
+    pub definitions: Vec<Definition<'i>>
+}
+
+impl<'i> Program<'i> {
+    pub fn iter(&self) -> impl Iterator<Item = &Definition<'i>> {
+        self.definitions.iter()
+    }
+
+    pub fn cases(&self, dispatch: Dispatch) -> &[Case<'i>] {
+        match dispatch {
+            Dispatch::Apply => &self.apply,
+            Dispatch::Resume => &self.resume,
+            Dispatch::Run => &self.run
+        }
+    }
+}
+
+impl Dispatch {
+    fn of(name: &FunctionName<'_>) -> Self {
+        match name {
+            FunctionName { continuation: 0, lambda: Some(_), .. } => Dispatch::Apply,
+            FunctionName { continuation: 0, lambda: None, .. } => Dispatch::Run,
+            _ => Dispatch::Resume
+        }
+    }
+}
+
+struct Context {
+    /// dispatch and tag of every function of the closure converted program
+    tags: Vec<(Dispatch, usize)>
+}
+
+impl Context {
+    fn convert_record<'i>(&self, closure: &prev::Closure<'i>, dispatch: Dispatch) -> Record<'i> {
+        let (actual, tag) = self.tags[closure.function];
+        assert_eq!(actual, dispatch, "closure of function @{} used as {:?} record", closure.function, dispatch);
+
+        Record {
+            tag,
+            fields: closure.captures.clone()
+        }
+    }
+
+    fn convert_value<'i>(&self, value: &prev::Value<'i>) -> Value<'i> {
+        match value {
+            prev::Value::Variable(var) => Value::Variable(*var),
+            prev::Value::Closure(closure) => Value::Record(self.convert_record(closure, Dispatch::Apply))
+        }
+    }
+
+    fn convert_body<'i>(&self, body: &prev::Body<'i>) -> Body<'i> {
+        match body {
+            prev::Body::Call { function, argument, continuation } => Body::Apply {
+                function: self.convert_value(function),
+                argument: self.convert_value(argument),
+                continuation: continuation.as_ref()
+                    .map(|cont| self.convert_record(cont, Dispatch::Resume))
+            },
+            prev::Body::Return(value) => Body::Resume(self.convert_value(value))
+        }
+    }
+}
+
+pub fn transform_program<'i>(program: &prev::Program<'i>) -> Program<'i> {
+    let mut counts = [0; 3];
+    let tags = program.functions.iter()
+        .map(|function| {
+            let dispatch = Dispatch::of(&function.name);
+            let count = &mut counts[dispatch as usize];
+            *count += 1;
+            (dispatch, *count - 1)
+        })
+        .collect();
+
+    let ctx = Context { tags };
+    let mut res = Program::default();
+
+    // tags count up in function order, so pushing in that order keeps cases at their tag
+    for function in program.functions.iter() {
+        let case = Case {
+            name: function.name,
+            argument: function.argument,
+            fields: function.captures.clone(),
+            body: ctx.convert_body(&function.body)
+        };
+
+        match Dispatch::of(&function.name) {
+            Dispatch::Apply => res.apply.push(case),
+            Dispatch::Resume => res.resume.push(case),
+            Dispatch::Run => res.run.push(case)
+        }
+    }
+
+    res.definitions = program.iter()
+        .map(|def| Definition {
+            target: def.target,
+            value: match &def.value {
+                prev::Initializer::Value(value) => Initializer::Value(ctx.convert_value(value)),
+                &prev::Initializer::Call(id) => Initializer::Run(ctx.tags[id].1)
+            }
+        })
+        .collect();
+
+    res
+}
+
+impl Display for Dispatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Dispatch::Apply => write!(f, "apply"),
+            Dispatch::Resume => write!(f, "resume"),
+            Dispatch::Run => write!(f, "run")
+        }
+    }
+}
+
+impl Display for Record<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{#{}", self.tag)?;
+
+        for field in self.fields.iter() {
+            write!(f, " {}", field)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Variable(var) => write!(f, "{}", var),
+            Value::Record(record) => write!(f, "{}", record)
+        }
+    }
+}
+
+impl Display for Body<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Apply { function, argument, continuation: None } => write!(f, "apply {} {}", function, argument),
+            Body::Apply { function, argument, continuation: Some(cont) } => write!(f, "apply {} {} then {}", function, argument, cont),
+            Body::Resume(value) => write!(f, "resume {}", value)
+        }
+    }
+}
+
+impl Display for Program<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for &dispatch in [Dispatch::Apply, Dispatch::Resume, Dispatch::Run].iter() {
+            for (tag, case) in self.cases(dispatch).iter().enumerate() {
+                let fields: Vec<_> = case.fields.iter()
+                    .map(|field| field.to_string())
+                    .collect();
+
+                writeln!(f, "{} #{} {} {} [{}] = {}",
+                    dispatch, tag, case.name, case.argument, fields.join(", "), case.body
+                )?;
+            }
+        }
+
+        for def in self.iter() {
+            match &def.value {
+                Initializer::Value(value) => writeln!(f, "{} = {}", def.target, value)?,
+                Initializer::Run(tag) => writeln!(f, "{} = run #{}", def.target, tag)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test;
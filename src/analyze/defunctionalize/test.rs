@@ -0,0 +1,269 @@
+use std::rc::Rc;
+use std::collections::HashMap;
+
+use super::*;
+use crate::parser::LambdaParser;
+use crate::ast::nodata;
+use crate::analyze::analyze_program;
+use crate::analyze::anf;
+
+fn convert<'i>(program: &nodata::Program<'i>) -> Program<'i> {
+    let analyzed = analyze_program(program).program;
+    transform_program(&prev::transform_program(&anf::transform_program(&analyzed)))
+}
+
+struct Binding<'a, 'i>(Identifier<'i>, Ref<'a, 'i>, Env<'a, 'i>);
+
+type Env<'a, 'i> = Option<Rc<Binding<'a, 'i>>>;
+type Ref<'a, 'i> = Rc<Object<'a, 'i>>;
+
+/// values of both interpreters, together with the IO primitives passed to start
+enum Object<'a, 'i> {
+    Closure(Identifier<'i>, &'a nodata::Application<'i>, Env<'a, 'i>),
+    Record(usize, Vec<Ref<'a, 'i>>),
+    Int(usize),
+    Succ,
+    Pred,
+    IsZero,
+    GetChar,
+    PutChar,
+    Ident,
+    True,
+    False,
+    Const(Ref<'a, 'i>)
+}
+
+struct Io<'b> {
+    input: &'b [u8],
+    output: Vec<u8>
+}
+
+impl Io<'_> {
+    fn primitive<'a, 'i>(&mut self, function: &Object<'a, 'i>, argument: Ref<'a, 'i>) -> Ref<'a, 'i> {
+        let int = |obj: &Object<'_, '_>| match obj {
+            Object::Int(n) => *n,
+            _ => panic!("IO primitive called with a non-integer")
+        };
+
+        Rc::new(match function {
+            Object::Succ => Object::Int(int(&argument) + 1),
+            Object::Pred => Object::Int(int(&argument) - 1),
+            Object::IsZero => if int(&argument) == 0 { Object::True } else { Object::False },
+            Object::GetChar => match self.input.split_first() {
+                Some((&c, rest)) => {
+                    self.input = rest;
+                    Object::Int(c as usize + 1)
+                }
+                None => Object::Int(0)
+            },
+            Object::PutChar => {
+                self.output.push((int(&argument) - 1) as u8);
+                Object::Ident
+            }
+            Object::Ident => return argument,
+            Object::True => Object::Const(argument),
+            Object::Const(value) => return value.clone(),
+            Object::False => Object::Ident,
+            _ => panic!("called a value that is not a function")
+        })
+    }
+
+    fn start<'a, 'i>(&mut self, start: Ref<'a, 'i>, mut apply: impl FnMut(&mut Self, Ref<'a, 'i>, Ref<'a, 'i>) -> Ref<'a, 'i>) {
+        let primitives = vec![Object::Int(0), Object::Succ, Object::Pred, Object::IsZero, Object::GetChar, Object::PutChar];
+
+        primitives.into_iter()
+            .fold(start, |function, primitive| apply(self, function, Rc::new(primitive)));
+    }
+}
+
+enum Frame<'a, 'i> {
+    Arguments(&'a [nodata::Expression<'i>], Env<'a, 'i>),
+    Call(Ref<'a, 'i>, &'a [nodata::Expression<'i>], Env<'a, 'i>)
+}
+
+/// evaluates the source program directly, as a reference for the output
+fn evaluate<'a, 'i>(program: &'a nodata::Program<'i>, input: &[u8]) -> Vec<u8> {
+    let mut globals = HashMap::new();
+    let mut io = Io { input, output: Vec::new() };
+
+    let lookup = |globals: &HashMap<Identifier<'i>, Ref<'a, 'i>>, mut env: &Env<'a, 'i>, name: Identifier<'i>| {
+        while let Some(binding) = env {
+            if binding.0 == name {
+                return binding.1.clone();
+            }
+
+            env = &binding.2;
+        }
+
+        globals[name].clone()
+    };
+
+    let run = |io: &mut Io, globals: &HashMap<Identifier<'i>, Ref<'a, 'i>>, mut state: Result<(&'a nodata::Expression<'i>, Env<'a, 'i>), Ref<'a, 'i>>, mut stack: Vec<Frame<'a, 'i>>| loop {
+        let value = match state {
+            Ok((nodata::Expression::Identifier(name), env)) => lookup(globals, &env, *name),
+            Ok((nodata::Expression::Lambda(lambda), env)) => Rc::new(Object::Closure(lambda.argument, &lambda.body, env)),
+            Ok((nodata::Expression::Parenthesis(app), env)) => {
+                stack.push(Frame::Arguments(&app.expressions[1..], env.clone()));
+                state = Ok((&app.expressions[0], env));
+                continue;
+            }
+            Err(value) => value
+        };
+
+        state = match stack.pop() {
+            None => return value,
+            Some(Frame::Arguments(args, env)) => match args.split_first() {
+                None => Err(value),
+                Some((arg, rest)) => {
+                    stack.push(Frame::Call(value, rest, env.clone()));
+                    Ok((arg, env))
+                }
+            },
+            Some(Frame::Call(function, rest, env)) => {
+                if !rest.is_empty() {
+                    stack.push(Frame::Arguments(rest, env));
+                }
+
+                match function.as_ref() {
+                    Object::Closure(argument, body, closure_env) => {
+                        let env = Some(Rc::new(Binding(argument, value, closure_env.clone())));
+                        stack.push(Frame::Arguments(&body.expressions[1..], env.clone()));
+                        Ok((&body.expressions[0], env))
+                    }
+                    primitive => Err(io.primitive(primitive, value))
+                }
+            }
+        };
+    };
+
+    for ass in program.iter() {
+        let stack = vec![Frame::Arguments(&ass.value.expressions[1..], None)];
+        let value = run(&mut io, &globals, Ok((&ass.value.expressions[0], None)), stack);
+        globals.insert(ass.target, value);
+    }
+
+    let start = globals["start"].clone();
+    io.start(start, |io, function, argument| {
+        run(io, &globals, Err(argument), vec![Frame::Call(function, &[], None)])
+    });
+
+    io.output
+}
+
+enum State<'a, 'i> {
+    Apply(Ref<'a, 'i>, Ref<'a, 'i>),
+    Resume(Ref<'a, 'i>),
+    Enter(&'a Case<'i>, Ref<'a, 'i>, Vec<Ref<'a, 'i>>)
+}
+
+/// runs the defunctionalized program with an explicit stack of resume records
+fn execute<'a, 'i>(program: &'a Program<'i>, input: &[u8]) -> Vec<u8> {
+    let mut globals: HashMap<Identifier<'i>, Ref<'a, 'i>> = HashMap::new();
+    let mut io = Io { input, output: Vec::new() };
+
+    let run = |io: &mut Io, globals: &HashMap<Identifier<'i>, Ref<'a, 'i>>, mut state| {
+        let mut stack: Vec<(usize, Vec<Ref<'a, 'i>>)> = Vec::new();
+
+        loop {
+            state = match state {
+                State::Apply(function, argument) => match function.as_ref() {
+                    Object::Record(tag, fields) => State::Enter(&program.apply[*tag], argument, fields.clone()),
+                    primitive => State::Resume(io.primitive(primitive, argument))
+                },
+                State::Resume(value) => match stack.pop() {
+                    None => return value,
+                    Some((tag, fields)) => State::Enter(&program.resume[tag], value, fields)
+                },
+                State::Enter(case, argument, fields) => {
+                    let variable = |var: &Variable<'i>| match var {
+                        Variable::Argument => argument.clone(),
+                        Variable::Capture(i) => fields[*i].clone(),
+                        Variable::Global(name) => globals[name].clone()
+                    };
+
+                    let record = |record: &Record<'i>| (record.tag, record.fields.iter().map(variable).collect::<Vec<_>>());
+
+                    let value = |value: &Value<'i>| match value {
+                        Value::Variable(var) => variable(var),
+                        Value::Record(rec) => {
+                            let (tag, fields) = record(rec);
+                            Rc::new(Object::Record(tag, fields))
+                        }
+                    };
+
+                    match &case.body {
+                        Body::Apply { function, argument, continuation } => {
+                            stack.extend(continuation.as_ref().map(record));
+                            State::Apply(value(function), value(argument))
+                        }
+                        Body::Resume(result) => State::Resume(value(result))
+                    }
+                }
+            };
+        }
+    };
+
+    for def in program.iter() {
+        let value = match &def.value {
+            Initializer::Value(Value::Variable(Variable::Global(name))) => globals[name].clone(),
+            Initializer::Value(Value::Record(record)) => {
+                let fields = record.fields.iter()
+                    .map(|var| match var {
+                        Variable::Global(name) => globals[name].clone(),
+                        _ => panic!("definition captures a local")
+                    })
+                    .collect();
+
+                Rc::new(Object::Record(record.tag, fields))
+            }
+            Initializer::Value(_) => panic!("definition references a local"),
+            &Initializer::Run(tag) => run(&mut io, &globals, State::Enter(&program.run[tag], Rc::new(Object::Ident), Vec::new()))
+        };
+
+        globals.insert(def.target, value);
+    }
+
+    let start = globals["start"].clone();
+    io.start(start, |io, function, argument| run(io, &globals, State::Apply(function, argument)));
+
+    io.output
+}
+
+#[test]
+fn test_dispatch() {
+    let program = LambdaParser::parse_program("k = a -> b -> a\nx = k k k\n").unwrap();
+
+    assert_eq!(convert(&program).to_string(), "\
+apply #0 k.2.0 b [a] = resume cap0
+apply #1 k.1.0 a [] = resume {#0 arg}
+resume #0 x.0.1 %0 [] = apply arg k
+run #0 x.0.0 _ [] = apply k k then {#0}
+k = {#1}
+x = run #0
+");
+}
+
+#[test]
+fn test_echo() {
+    let code = "\
+y = g -> (f -> f f) f -> g x -> f f x
+start = zero -> succ -> pred -> iszero -> getchar -> putchar ->
+    y (loop -> x -> (c -> iszero c (x -> x) (x -> loop (putchar c)) zero) (getchar x)) zero
+";
+    let program = LambdaParser::parse_program(code).unwrap();
+    let input = b"echo\n";
+
+    assert_eq!(evaluate(&program, input), input);
+    assert_eq!(execute(&convert(&program), input), input);
+}
+
+#[test]
+fn test_examples() {
+    for &code in [include_str!("../../../examples/test.lambda"), include_str!("../../../examples/hello.lambda")].iter() {
+        let program = LambdaParser::parse_program(code).unwrap();
+        let expected = evaluate(&program, b"");
+
+        assert_eq!(expected, b"Hello\nWorld\n");
+        assert_eq!(execute(&convert(&program), b""), expected);
+    }
+}
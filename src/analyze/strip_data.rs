@@ -1,46 +1,18 @@
 use std::rc::Rc;
 
 use crate::ast::generic;
+use crate::ast::fold::Fold;
 pub use crate::ast::nodata::*;
 
-pub fn transform_program<'i, D: generic::ASTData<'i>>(program: &generic::Program<'i, D>) -> Program<'i> {
-    Program {
-        assignments: program.iter()
-            .map(transform_assignment)
-            .collect(),
-        data: ()
-    }
-}
-
-fn transform_assignment<'i, D: generic::ASTData<'i>>(ass: &generic::Assignment<'i, D>) -> Assignment<'i> {
-    Assignment {
-        target: ass.target,
-        value: transform_application(&ass.value),
-        data: ()
-    }
-}
+struct StripData;
 
-fn transform_application<'i, D: generic::ASTData<'i>>(app: &generic::Application<'i, D>) -> Rc<Application<'i>> {
-    Rc::new(Application {
-        head: transform_expression(&app.head),
-        tail: app.tail.as_ref()
-            .map(|tail| transform_application(tail)),
-        data: ()
-    })
+impl<'i, D: generic::ASTData<'i>> Fold<'i, D, NoData> for StripData {
+    fn fold_program_data(&mut self, _: &generic::Program<'i, D>, _: &[Assignment<'i>]) {}
+    fn fold_assignment_data(&mut self, _: &generic::Assignment<'i, D>, _: &Rc<Application<'i>>) {}
+    fn fold_application_data(&mut self, _: &generic::Application<'i, D>, _: &[Expression<'i>]) {}
+    fn fold_lambda_data(&mut self, _: &generic::Lambda<'i, D>, _: &Rc<Application<'i>>) {}
 }
 
-fn transform_expression<'i, D: generic::ASTData<'i>>(expr: &generic::Expression<'i, D>) -> Expression<'i> {
-    match expr {
-        generic::Expression::Identifier(ident) => Expression::Identifier(ident),
-        generic::Expression::Parenthesis(app) => Expression::Parenthesis(transform_application(app)),
-        generic::Expression::Lambda(lambda) => Expression::Lambda(transform_lambda(lambda))
-    }
-}
-
-fn transform_lambda<'i, D: generic::ASTData<'i>>(lambda: &generic::Lambda<'i, D>) -> Rc<Lambda<'i>> {
-    Rc::new(Lambda {
-        argument: lambda.argument,
-        body: transform_application(&lambda.body),
-        data: ()
-    })
+pub fn transform_program<'i, D: generic::ASTData<'i>>(program: &generic::Program<'i, D>) -> Program<'i> {
+    StripData.fold_program(program)
 }
@@ -0,0 +1,48 @@
+use super::*;
+use crate::parser::LambdaParser;
+use crate::codegen::*;
+use crate::error::Error;
+
+#[test]
+fn test_long_application() {
+    let code = format!("f = a -> a\nx = f{}\n", " f".repeat(100_000));
+    let parsed = LambdaParser::parse_program(&code).unwrap();
+
+    let result = analyze_program(&parsed);
+    assert!(result.diagnostics.is_empty());
+
+    let ass = &result.program.assignments[1];
+    assert_eq!(ass.data.continuations.len(), 100_000);
+    assert_eq!(strip_data::transform_program(&result.program).to_string(), code);
+
+    let targets: [&dyn CodegenTarget; 4] = [&CPlusPlus::default(), &JavaScript::default(), &Lua::default(), &Python::default()];
+    for target in targets.iter() {
+        assert!(!target.generate(&result.program).is_empty());
+    }
+
+    let flat = closure_conversion::transform_program(&anf::transform_program(&result.program));
+    let targets: [&dyn FlatCodegenTarget; 6] = [&C::default(), &CPlusPlusCPS::default(), &Llvm::default(), &PythonCPS::default(), &Rust::default(), &Wat::default()];
+    for target in targets.iter() {
+        assert!(!target.generate(&flat).is_empty());
+    }
+}
+
+#[test]
+fn test_deep_lambda() {
+    // the limit is chosen for the 8 MiB stack of the main thread, test threads get less
+    let deepest = std::thread::Builder::new().stack_size(8 << 20).spawn(|| {
+        let code = format!("x = {}a\n", "a -> ".repeat(crate::parser::MAX_NESTING));
+        let result = analyze_program(&LambdaParser::parse_program(&code).unwrap());
+
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(strip_data::transform_program(&result.program).to_string(), code);
+    });
+    deepest.unwrap().join().unwrap();
+
+    // deeper nesting is rejected instead of overflowing the stack
+    let code = format!("x = {}a\n", "a -> ".repeat(100_000));
+    match LambdaParser::parse_program(&code) {
+        Err(Error::ParseError(e)) => assert!(e.to_string().contains("nested deeper than 500 levels")),
+        res => panic!("unexpected {:?}", res.map(|program| program.to_string()))
+    }
+}
@@ -0,0 +1,493 @@
+use std::rc::Rc;
+use std::collections::BTreeSet;
+
+use pest::Parser;
+use pest::Span;
+use pest::iterators::Pair as PestPair;
+use pest::error::Error as PestError;
+use pest::error::ErrorVariant;
+use pest_derive::Parser;
+
+use crate::error::Error;
+use crate::parser::MAX_NESTING;
+use crate::ast::generic::push_expression;
+use super::compute_continuation_captures::*;
+
+#[derive(Parser)]
+#[grammar = "textual.pest"]
+pub struct IrParser;
+
+pub type Pair<'i> = PestPair<'i, Rule>;
+pub type ParseError = PestError<Rule>;
+
+fn indent(res: &mut String, level: usize) {
+    for _ in 0..level {
+        res.push_str("    ");
+    }
+}
+
+fn print_captures(captures: &BTreeSet<Identifier<'_>>, anonymous_captures: &BTreeSet<usize>, res: &mut String) {
+    let cap = captures.iter()
+        .map(|ident| ident.to_string());
+    let anon_cap = anonymous_captures.iter()
+        .map(|anon| format!("%{}", anon));
+
+    res.push('[');
+    res.push_str(&cap.chain(anon_cap).collect::<Vec<_>>().join(", "));
+    res.push(']');
+}
+
+fn print_literal(lit: &Literal<'_>, level: usize, res: &mut String) {
+    match lit {
+        Literal::Anonymous(id) => res.push_str(&format!("%{}", id)),
+        Literal::Identifier(ident) => res.push_str(ident),
+        Literal::Lambda(lambda) => {
+            res.push_str(&format!("\\{} {} ", lambda.data.id, lambda.argument));
+            print_captures(&lambda.data.captures, &BTreeSet::new(), res);
+            res.push(' ');
+            print_block(&lambda.data.continuations, &lambda.data.result_literal, level, res);
+        }
+    }
+}
+
+fn print_block(continuations: &[Continuation<'_>], result_literal: &Literal<'_>, level: usize, res: &mut String) {
+    res.push_str("{\n");
+
+    for cont in continuations.iter() {
+        indent(res, level + 1);
+        res.push_str(&format!("%{} = ", cont.id));
+        print_literal(&cont.function, level + 1, res);
+        res.push(' ');
+        print_literal(&cont.argument, level + 1, res);
+        res.push(' ');
+        print_captures(&cont.captures, &cont.anonymous_captures, res);
+        res.push('\n');
+    }
+
+    indent(res, level + 1);
+    res.push_str("ret ");
+    print_literal(result_literal, level + 1, res);
+    res.push('\n');
+
+    indent(res, level);
+    res.push('}');
+}
+
+pub fn print_program(program: &Program<'_>) -> String {
+    let mut res = String::new();
+
+    for ass in program.iter() {
+        res.push_str(&format!("{} = ", ass.target));
+        print_block(&ass.data.continuations, &ass.data.result_literal, 0, &mut res);
+        res.push('\n');
+    }
+
+    res
+}
+
+fn ir_error(span: Span<'_>, message: String) -> Error {
+    Error::IrParseError(Box::new(ParseError::new_from_span(
+        ErrorVariant::CustomError { message },
+        span
+    )))
+}
+
+/// every assignment and every lambda opens a block, so the depth of blocks is
+/// the nesting of lambdas plus one
+fn check_nesting(code: &str) -> Result<(), Error> {
+    let mut depth: usize = 0;
+    let mut chars = code.char_indices();
+
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '#' => { chars.find(|&(_, c)| c == '\n'); },
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => ()
+        }
+
+        if depth > MAX_NESTING + 1 {
+            let span = Span::new(code, pos, pos + 1).expect("span of a char is valid");
+            return Err(ir_error(span, format!("lambdas are nested deeper than {} levels", MAX_NESTING)));
+        }
+    }
+
+    Ok(())
+}
+
+struct Context<'i> {
+    /// the assignment that is being parsed
+    target: Identifier<'i>,
+    /// lambda ids are unique per assignment
+    lambda_ids: BTreeSet<usize>,
+    /// globals defined by the assignments before the current one
+    globals: BTreeSet<Identifier<'i>>
+}
+
+/// the variables a function of a block receives, identifiers bound by an
+/// enclosing lambda are locals and would resolve to globals outside of it
+#[derive(Default)]
+struct Scope<'i> {
+    identifiers: BTreeSet<Identifier<'i>>,
+    anonymous: BTreeSet<usize>
+}
+
+/// checks that the captures of a new closure are available where it is built
+fn check_captures<'i>(
+    span: Span<'_>,
+    captures: &BTreeSet<Identifier<'i>>,
+    anonymous_captures: &BTreeSet<usize>,
+    locals: &BTreeSet<Identifier<'i>>,
+    scope: &Scope<'i>
+) -> Result<(), Error> {
+    if let Some(ident) = captures.iter().find(|ident| !locals.contains(*ident)) {
+        return Err(ir_error(span, format!("'{}' is not a local and cannot be captured", ident)));
+    }
+
+    if let Some(ident) = captures.iter().find(|ident| !scope.identifiers.contains(*ident)) {
+        return Err(ir_error(span, format!("local '{}' is captured but not in scope", ident)));
+    }
+
+    if let Some(anon) = anonymous_captures.iter().find(|anon| !scope.anonymous.contains(*anon)) {
+        return Err(ir_error(span, format!("anonymous value %{} is captured but not in scope", anon)));
+    }
+
+    Ok(())
+}
+
+struct Block<'i> {
+    continuations: Vec<Continuation<'i>>,
+    result_literal: Literal<'i>,
+    application: Rc<Application<'i>>
+}
+
+fn make_number(pair: Pair<'_>) -> Result<usize, Error> {
+    let span = pair.as_span();
+    pair.as_str().parse()
+        .map_err(|_| ir_error(span, String::from("number out of range")))
+}
+
+fn make_anonymous(pair: Pair<'_>) -> Result<usize, Error> {
+    make_number(pair.into_inner().next().expect("anonymous without number"))
+}
+
+fn make_captures(pair: Pair<'_>) -> Result<(BTreeSet<Identifier<'_>>, BTreeSet<usize>), Error> {
+    let mut captures = BTreeSet::new();
+    let mut anonymous_captures = BTreeSet::new();
+
+    for capture in pair.into_inner() {
+        let capture = capture.into_inner().next().expect("empty capture");
+
+        match capture.as_rule() {
+            Rule::anonymous => { anonymous_captures.insert(make_anonymous(capture)?); }
+            Rule::identifier => { captures.insert(capture.as_str()); }
+            _ => unreachable!("unexpected capture rule")
+        }
+    }
+
+    Ok((captures, anonymous_captures))
+}
+
+fn make_lambda<'i>(pair: Pair<'i>, ctx: &mut Context<'i>, locals: &BTreeSet<Identifier<'i>>, scope: &Scope<'i>) -> Result<Rc<Lambda<'i>>, Error> {
+    let mut inner = pair.into_inner();
+    let id_pair = inner.next().expect("lambda without id");
+    let argument = inner.next().expect("lambda without argument").as_str();
+    let captures_pair = inner.next().expect("lambda without captures");
+    let block = inner.next().expect("lambda without body");
+
+    let id_span = id_pair.as_span();
+    let id = make_number(id_pair)?;
+    if !ctx.lambda_ids.insert(id) {
+        return Err(ir_error(id_span, format!("duplicate lambda id {}", id)));
+    }
+
+    let captures_span = captures_pair.as_span();
+    let (captures, anonymous_captures) = make_captures(captures_pair)?;
+    if !anonymous_captures.is_empty() {
+        return Err(ir_error(captures_span, String::from("lambdas cannot capture anonymous values")));
+    }
+    check_captures(captures_span, &captures, &anonymous_captures, locals, scope)?;
+
+    let mut inner_locals = locals.clone();
+    inner_locals.insert(argument);
+
+    let mut entry = Scope {
+        identifiers: captures.clone(),
+        anonymous: BTreeSet::new()
+    };
+    entry.identifiers.insert(argument);
+
+    let block = make_block(block, ctx, &inner_locals, entry)?;
+
+    Ok(Rc::new(Lambda {
+        argument,
+        body: block.application,
+        data: LambdaData {
+            id,
+            captures,
+            continuations: block.continuations,
+            result_literal: block.result_literal
+        }
+    }))
+}
+
+fn make_literal<'i>(pair: Pair<'i>, ctx: &mut Context<'i>, next_id: usize, locals: &BTreeSet<Identifier<'i>>, scope: &Scope<'i>) -> Result<Literal<'i>, Error> {
+    let lit = pair.into_inner().next().expect("empty literal");
+    let span = lit.as_span();
+
+    match lit.as_rule() {
+        Rule::lambda => make_lambda(lit, ctx, locals, scope).map(Literal::Lambda),
+        Rule::identifier => {
+            let ident = lit.as_str();
+
+            if locals.contains(ident) && !scope.identifiers.contains(ident) {
+                return Err(ir_error(span, format!("local '{}' is referenced but not in scope", ident)));
+            }
+
+            if !locals.contains(ident) && !ctx.globals.contains(ident) {
+                return Err(ir_error(span, if ident == ctx.target {
+                    format!("name '{}' referenced in its definition", ident)
+                } else {
+                    format!("undefined name '{}'", ident)
+                }));
+            }
+
+            Ok(Literal::Identifier(ident))
+        }
+        Rule::anonymous => {
+            let id = make_anonymous(lit)?;
+
+            if id >= next_id {
+                return Err(ir_error(span, format!("anonymous value %{} used before its definition", id)));
+            }
+
+            if !scope.anonymous.contains(&id) {
+                return Err(ir_error(span, format!("anonymous value %{} is referenced but not in scope", id)));
+            }
+
+            Ok(Literal::Anonymous(id))
+        }
+        _ => unreachable!("unexpected literal rule")
+    }
+}
+
+/// the source expressions of the anonymous values of a block, a value is
+/// moved into its last use and shared by the uses before it, so rebuilding
+/// a block takes linear time
+struct Values<'i> {
+    applications: Vec<Option<Rc<Application<'i>>>>,
+    uses: Vec<usize>
+}
+
+impl<'i> Values<'i> {
+    fn new(continuations: &[Continuation<'i>], result_literal: &Literal<'i>) -> Self {
+        let mut uses = vec![0; continuations.len()];
+
+        let literals = continuations.iter()
+            .flat_map(|cont| [&cont.function, &cont.argument])
+            .chain(std::iter::once(result_literal));
+
+        for lit in literals {
+            if let Literal::Anonymous(id) = lit {
+                uses[*id] += 1;
+            }
+        }
+
+        Values {
+            applications: Vec::with_capacity(continuations.len()),
+            uses
+        }
+    }
+
+    fn push(&mut self, expressions: Vec<Expression<'i>>) {
+        self.applications.push(Some(Rc::new(Application {
+            expressions,
+            data: ()
+        })));
+    }
+
+    fn expressions(&mut self, lit: &Literal<'i>) -> Vec<Expression<'i>> {
+        match lit {
+            Literal::Anonymous(id) => {
+                self.uses[*id] -= 1;
+
+                let application = if self.uses[*id] == 0 {
+                    self.applications[*id].take()
+                } else {
+                    self.applications[*id].clone()
+                };
+
+                match Rc::try_unwrap(application.expect("anonymous value used after its last use")) {
+                    Ok(application) => application.expressions,
+                    Err(shared) if shared.expressions.len() == 1 => shared.expressions.clone(),
+                    Err(shared) => vec![Expression::Parenthesis(shared)]
+                }
+            }
+            Literal::Identifier(ident) => vec![Expression::Identifier(ident)],
+            Literal::Lambda(lambda) => vec![Expression::Lambda(lambda.clone())]
+        }
+    }
+
+    fn expression(&mut self, lit: &Literal<'i>) -> Expression<'i> {
+        let mut expressions = self.expressions(lit);
+
+        if expressions.len() == 1 {
+            expressions.pop().expect("empty expression list")
+        } else {
+            Expression::Parenthesis(Rc::new(Application {
+                expressions,
+                data: ()
+            }))
+        }
+    }
+}
+
+/// every continuation of a block after the first becomes a function that
+/// receives the result of the previous call and its captures, the first one
+/// runs in the entry scope
+fn make_block<'i>(pair: Pair<'i>, ctx: &mut Context<'i>, locals: &BTreeSet<Identifier<'i>>, entry: Scope<'i>) -> Result<Block<'i>, Error> {
+    let mut continuations: Vec<Continuation<'i>> = Vec::new();
+    let mut result_literal = None;
+    let mut scope = entry;
+
+    for item in pair.into_inner() {
+        match item.as_rule() {
+            Rule::continuation => {
+                let next_id = continuations.len();
+
+                let mut inner = item.into_inner();
+                let id_pair = inner.next().expect("continuation without id");
+                let function = inner.next().expect("continuation without function");
+                let argument = inner.next().expect("continuation without argument");
+                let captures_pair = inner.next().expect("continuation without captures");
+
+                let id_span = id_pair.as_span();
+                let id = make_anonymous(id_pair)?;
+                if id != next_id {
+                    return Err(ir_error(id_span, format!("expected continuation %{}, found %{}", next_id, id)));
+                }
+
+                let captures_span = captures_pair.as_span();
+                let (captures, anonymous_captures) = make_captures(captures_pair)?;
+                if let Some(anon) = anonymous_captures.iter().find(|&&anon| anon >= id) {
+                    return Err(ir_error(captures_span, format!("anonymous value %{} captured before its definition", anon)));
+                }
+                check_captures(captures_span, &captures, &anonymous_captures, locals, &scope)?;
+
+                if id > 0 {
+                    scope = Scope {
+                        identifiers: captures.clone(),
+                        anonymous: anonymous_captures.clone()
+                    };
+                    scope.anonymous.insert(id - 1);
+                }
+
+                let function = make_literal(function, ctx, id, locals, &scope)?;
+                let argument = make_literal(argument, ctx, id, locals, &scope)?;
+
+                continuations.push(Continuation {
+                    id,
+                    function,
+                    argument,
+                    captures,
+                    anonymous_captures
+                });
+            }
+            Rule::literal => {
+                let span = item.as_span();
+
+                // the last call is a tail call, its result is returned directly
+                if let Some(last) = continuations.last() {
+                    scope = Scope::default();
+                    scope.anonymous.insert(last.id);
+                }
+
+                let lit = make_literal(item, ctx, continuations.len(), locals, &scope)?;
+
+                // a block returns the result of its last call
+                if let Some(last) = continuations.last() {
+                    if !matches!(lit, Literal::Anonymous(id) if id == last.id) {
+                        return Err(ir_error(span, format!("block must return its last continuation %{}", last.id)));
+                    }
+                }
+
+                result_literal = Some(lit);
+            }
+            _ => unreachable!("unexpected block rule")
+        }
+    }
+
+    let result_literal = result_literal.expect("block without result");
+
+    let mut values = Values::new(&continuations, &result_literal);
+    for cont in continuations.iter() {
+        let mut expressions = values.expressions(&cont.function);
+        push_expression(&mut expressions, values.expression(&cont.argument));
+        values.push(expressions);
+    }
+
+    let application = Rc::new(Application {
+        expressions: values.expressions(&result_literal),
+        data: ()
+    });
+
+    Ok(Block {
+        continuations,
+        result_literal,
+        application
+    })
+}
+
+fn make_assignment<'i>(pair: Pair<'i>, ctx: &mut Context<'i>) -> Result<Assignment<'i>, Error> {
+    let mut inner = pair.into_inner();
+    let target = inner.next().expect("assignment without target");
+    let block = inner.next().expect("assignment without value");
+
+    if ctx.globals.contains(target.as_str()) {
+        return Err(ir_error(target.as_span(), format!("redefinition of '{}'", target.as_str())));
+    }
+
+    ctx.target = target.as_str();
+    ctx.lambda_ids.clear();
+
+    let block = make_block(block, ctx, &BTreeSet::new(), Scope::default())?;
+
+    ctx.globals.insert(target.as_str());
+
+    Ok(Assignment {
+        target: target.as_str(),
+        value: block.application,
+        data: AssignmentData {
+            continuations: block.continuations,
+            result_literal: block.result_literal
+        }
+    })
+}
+
+pub fn parse_program(code: &str) -> Result<Program<'_>, Error> {
+    check_nesting(code)?;
+
+    let mut pairs = IrParser::parse(Rule::program, code)
+        .map_err(|e| Error::IrParseError(Box::new(e)))?;
+
+    let program = pairs.next().expect("no program in parse result");
+
+    let mut ctx = Context {
+        target: "",
+        lambda_ids: BTreeSet::new(),
+        globals: BTreeSet::new()
+    };
+
+    let assignments = program.into_inner()
+        .filter(|pair| pair.as_rule() == Rule::assignment)
+        .map(|pair| make_assignment(pair, &mut ctx))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Program {
+        assignments,
+        data: ()
+    })
+}
+
+#[cfg(test)]
+mod test;
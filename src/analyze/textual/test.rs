@@ -0,0 +1,158 @@
+use super::*;
+use crate::parser::LambdaParser;
+use crate::analyze::analyze_program;
+use crate::analyze::strip_data;
+
+const CODE: &str = r"
+ident = a -> a
+y = g -> (f -> f f) f -> g x -> f f x
+true = a -> b -> a
+tuple = a -> b -> sel -> sel a b
+nil = tuple (tuple ident true) ident
+";
+
+/// globals for the blocks in the tests below
+const GLOBALS: &str = "f = { ret \\0 x [] { ret x } }\ng = { ret f }\na = { ret f }\n";
+
+fn with_globals(code: &str) -> String {
+    format!("{}{}", GLOBALS, code)
+}
+
+fn error_message(code: &str) -> String {
+    match parse_program(&with_globals(code)) {
+        Err(Error::IrParseError(e)) => e.to_string(),
+        _ => panic!("expected an IR parse error for {}", code)
+    }
+}
+
+#[test]
+fn test_print() {
+    let parsed = LambdaParser::parse_program("k = a -> b -> a\nx = k k (k k)").unwrap();
+    let program = analyze_program(&parsed).program;
+
+    assert_eq!(print_program(&program), concat!(
+        "k = {\n",
+        "    ret \\0 a [] {\n",
+        "        ret \\1 b [a] {\n",
+        "            ret a\n",
+        "        }\n",
+        "    }\n",
+        "}\n",
+        "x = {\n",
+        "    %0 = k k []\n",
+        "    %1 = k k []\n",
+        "    %2 = %0 %1 [%0]\n",
+        "    ret %2\n",
+        "}\n"
+    ));
+}
+
+#[test]
+fn test_round_trip() {
+    let parsed = LambdaParser::parse_program(CODE).unwrap();
+    let program = analyze_program(&parsed).program;
+
+    let text = print_program(&program);
+    let reparsed = parse_program(&text).unwrap();
+
+    assert_eq!(print_program(&reparsed), text);
+
+    let reconstructed = strip_data::transform_program(&reparsed).to_string();
+    let reanalyzed = analyze_program(&LambdaParser::parse_program(&reconstructed).unwrap()).program;
+
+    assert_eq!(print_program(&reanalyzed), text);
+}
+
+#[test]
+fn test_reconstruct() {
+    let code = with_globals(r"
+        # comments are allowed
+        x = {
+            %0 = f a []
+            %1 = \0 b [] { ret b } %0 []
+            %2 = g %1 []
+            ret %2
+        }
+    ");
+    let program = parse_program(&code).unwrap();
+
+    assert!(strip_data::transform_program(&program).to_string().ends_with("\nx = g ((b -> b) (f a))\n"));
+}
+
+#[test]
+fn test_errors() {
+    assert!(parse_program(&with_globals("x = { %1 = f a [] ret %1 }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f %0 [] ret %0 }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f a [] ret %1 }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f a [%0] ret %0 }")).is_err());
+    assert!(parse_program(&with_globals(r"x = { %0 = \0 a [] { ret a } \0 b [] { ret b } [] ret %0 }")).is_err());
+    assert!(parse_program(&with_globals(r"x = { ret \0 a [%0] { ret a } }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f a [] }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f f [] ret f }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f a [] %1 = %0 a [] ret %0 }")).is_err());
+}
+
+#[test]
+fn test_capture_errors() {
+    // continuations after the first only see their argument and captures
+    assert!(parse_program(&with_globals(r"x = { ret \0 a [] { %0 = a a [] %1 = %0 a [] ret %1 } }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f a [] %1 = g a [] %2 = %0 %1 [] ret %2 }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f a [] %1 = g a [%0] %2 = %0 %1 [%0] ret %2 }")).is_err());
+    assert!(parse_program(&with_globals("x = { %0 = f a [] %1 = g a [] %2 = %0 %1 [%0] ret %2 }")).is_ok());
+
+    // lambdas only see their argument and captures
+    assert!(parse_program(&with_globals(r"x = { ret \0 a [] { ret \1 b [] { ret a } } }")).is_err());
+    assert!(parse_program(&with_globals(r"x = { ret \0 a [] { ret \1 b [c] { ret c } } }")).is_err());
+    assert!(parse_program(&with_globals(r"x = { ret \0 a [] { %0 = f f [] %1 = \1 b [a] { ret a } %0 [] ret %1 } }")).is_err());
+    assert!(parse_program(&with_globals(r"x = { ret \0 a [] { %0 = f f [] %1 = \1 b [a] { ret a } %0 [a] ret %1 } }")).is_ok());
+}
+
+#[test]
+fn test_global_errors() {
+    assert!(error_message("x = { %0 = f b [] ret %0 }").contains("undefined name 'b'"));
+    assert!(error_message("x = { ret y }\ny = { ret f }").contains("undefined name 'y'"));
+    assert!(error_message("x = { %0 = f x [] ret %0 }").contains("name 'x' referenced in its definition"));
+
+    // the span points at the second definition
+    let message = error_message("x = { ret f }\nx = { ret g }");
+    assert!(message.contains("redefinition of 'x'"));
+    assert!(message.contains(" --> 5:1"));
+}
+
+#[test]
+fn test_reconstruct_shared() {
+    // a value used more than once is shared instead of copied into each use
+    let code = with_globals("x = { %0 = f a [] %1 = %0 %0 [] ret %1 }");
+    let program = parse_program(&code).unwrap();
+
+    assert!(strip_data::transform_program(&program).to_string().ends_with("\nx = (f a) (f a)\n"));
+}
+
+#[test]
+fn test_long_block() {
+    let code = format!("f = a -> a\nx = f{}\n", " f".repeat(100_000));
+    let program = analyze_program(&LambdaParser::parse_program(&code).unwrap()).program;
+
+    let text = print_program(&program);
+    let reparsed = parse_program(&text).unwrap();
+
+    assert_eq!(reparsed.assignments[1].data.continuations.len(), 100_000);
+    assert_eq!(strip_data::transform_program(&reparsed).to_string(), code);
+}
+
+#[test]
+fn test_deep_block() {
+    // the limit is chosen for the 8 MiB stack of the main thread, test threads get less
+    let deepest = std::thread::Builder::new().stack_size(8 << 20).spawn(|| {
+        let code = format!("x = {}a\n", "a -> ".repeat(crate::parser::MAX_NESTING));
+        let program = analyze_program(&LambdaParser::parse_program(&code).unwrap()).program;
+        let text = print_program(&program);
+        let reparsed = parse_program(&text).unwrap();
+
+        assert_eq!(strip_data::transform_program(&reparsed).to_string(), code);
+    });
+    deepest.unwrap().join().unwrap();
+
+    let code = format!("x = {}{}\n", "{ ret \\0 a [] ".repeat(100_000), "}".repeat(100_000));
+    assert!(error_message(&code).contains("lambdas are nested deeper than 500 levels"));
+}
@@ -1,4 +1,10 @@
 pub mod generic;
 pub mod nodata;
 pub mod maker;
+pub mod visit;
+pub mod visit_mut;
+pub mod fold;
 mod fmt;
+
+#[cfg(test)]
+mod test;
@@ -5,16 +5,6 @@ use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
 
-impl<T: Debug> DataDisplay for T {
-    default fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        if f.alternate() {
-            write!(f, "[data = {:#?}] ", self)
-        } else {
-            write!(f, "[data = {:?}] ", self)
-        }
-    }
-}
-
 impl DataDisplay for () {
     fn fmt(&self, _: &mut Formatter<'_>) -> FmtResult {
         Ok(())
@@ -47,14 +37,16 @@ impl<'i, D: ASTData<'i>> Debug for Expression<'i, D> {
 impl<'i, D: ASTData<'i>> Debug for Application<'i, D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         DataDisplay::fmt(&self.data, f)?;
-        Debug::fmt(&self.head, f)?;
 
-        if let Some(tail) = &self.tail {
-            f.write_str(" ")?;
-            Debug::fmt(tail, f)
-        } else {
-            Ok(())
+        for (i, expr) in self.expressions.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+
+            Debug::fmt(expr, f)?;
         }
+
+        Ok(())
     }
 }
 
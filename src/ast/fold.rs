@@ -0,0 +1,100 @@
+use std::rc::Rc;
+
+use super::generic::*;
+
+pub trait Fold<'i, D1: ASTData<'i>, D2: ASTData<'i>> {
+    fn fold_program_data(&mut self, program: &Program<'i, D1>, assignments: &[Assignment<'i, D2>]) -> D2::ProgramData;
+    fn fold_assignment_data(&mut self, ass: &Assignment<'i, D1>, value: &Rc<Application<'i, D2>>) -> D2::AssignmentData;
+    fn fold_application_data(&mut self, app: &Application<'i, D1>, expressions: &[Expression<'i, D2>]) -> D2::ApplicationData;
+    fn fold_lambda_data(&mut self, lambda: &Lambda<'i, D1>, body: &Rc<Application<'i, D2>>) -> D2::LambdaData;
+
+    fn fold_program(&mut self, program: &Program<'i, D1>) -> Program<'i, D2> {
+        walk_program(self, program)
+    }
+
+    fn fold_assignment(&mut self, ass: &Assignment<'i, D1>) -> Assignment<'i, D2> {
+        walk_assignment(self, ass)
+    }
+
+    fn fold_application(&mut self, app: &Application<'i, D1>) -> Rc<Application<'i, D2>> {
+        walk_application(self, app)
+    }
+
+    fn fold_expression(&mut self, expr: &Expression<'i, D1>) -> Expression<'i, D2> {
+        walk_expression(self, expr)
+    }
+
+    fn fold_lambda(&mut self, lambda: &Lambda<'i, D1>) -> Rc<Lambda<'i, D2>> {
+        walk_lambda(self, lambda)
+    }
+
+    fn fold_identifier(&mut self, ident: Identifier<'i>) -> Identifier<'i> {
+        ident
+    }
+}
+
+pub fn walk_program<'i, D1, D2, F>(folder: &mut F, program: &Program<'i, D1>) -> Program<'i, D2>
+    where D1: ASTData<'i>, D2: ASTData<'i>, F: Fold<'i, D1, D2> + ?Sized
+{
+    let assignments: Vec<_> = program.iter()
+        .map(|ass| folder.fold_assignment(ass))
+        .collect();
+
+    let data = folder.fold_program_data(program, &assignments);
+
+    Program {
+        assignments,
+        data
+    }
+}
+
+pub fn walk_assignment<'i, D1, D2, F>(folder: &mut F, ass: &Assignment<'i, D1>) -> Assignment<'i, D2>
+    where D1: ASTData<'i>, D2: ASTData<'i>, F: Fold<'i, D1, D2> + ?Sized
+{
+    let value = folder.fold_application(&ass.value);
+    let data = folder.fold_assignment_data(ass, &value);
+
+    Assignment {
+        target: ass.target,
+        value,
+        data
+    }
+}
+
+pub fn walk_application<'i, D1, D2, F>(folder: &mut F, app: &Application<'i, D1>) -> Rc<Application<'i, D2>>
+    where D1: ASTData<'i>, D2: ASTData<'i>, F: Fold<'i, D1, D2> + ?Sized
+{
+    let expressions: Vec<_> = app.iter()
+        .map(|expr| folder.fold_expression(expr))
+        .collect();
+
+    let data = folder.fold_application_data(app, &expressions);
+
+    Rc::new(Application {
+        expressions,
+        data
+    })
+}
+
+pub fn walk_expression<'i, D1, D2, F>(folder: &mut F, expr: &Expression<'i, D1>) -> Expression<'i, D2>
+    where D1: ASTData<'i>, D2: ASTData<'i>, F: Fold<'i, D1, D2> + ?Sized
+{
+    match expr {
+        Expression::Lambda(lambda) => Expression::Lambda(folder.fold_lambda(lambda)),
+        Expression::Parenthesis(app) => Expression::Parenthesis(folder.fold_application(app)),
+        Expression::Identifier(ident) => Expression::Identifier(folder.fold_identifier(ident))
+    }
+}
+
+pub fn walk_lambda<'i, D1, D2, F>(folder: &mut F, lambda: &Lambda<'i, D1>) -> Rc<Lambda<'i, D2>>
+    where D1: ASTData<'i>, D2: ASTData<'i>, F: Fold<'i, D1, D2> + ?Sized
+{
+    let body = folder.fold_application(&lambda.body);
+    let data = folder.fold_lambda_data(lambda, &body);
+
+    Rc::new(Lambda {
+        argument: lambda.argument,
+        body,
+        data
+    })
+}
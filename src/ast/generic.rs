@@ -1,16 +1,23 @@
 use std::rc::Rc;
+use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
 
 pub trait ASTData<'i> {
-    type ProgramData: Clone + DataDisplay = ();
-    type AssignmentData: Clone + DataDisplay = ();
-    type ApplicationData: Clone + DataDisplay = ();
-    type LambdaData: Clone + DataDisplay = ();
+    type ProgramData: Clone + DataDisplay;
+    type AssignmentData: Clone + DataDisplay;
+    type ApplicationData: Clone + DataDisplay;
+    type LambdaData: Clone + DataDisplay;
 }
 
-pub trait DataDisplay {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult;
+pub trait DataDisplay: Debug {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if f.alternate() {
+            write!(f, "[data = {:#?}] ", self)
+        } else {
+            write!(f, "[data = {:?}] ", self)
+        }
+    }
 }
 
 pub type Identifier<'i> = &'i str;
@@ -31,8 +38,7 @@ pub enum Expression<'i, D: ASTData<'i>> {
 
 #[derive(Clone)]
 pub struct Application<'i, D: ASTData<'i>> {
-    pub head: Expression<'i, D>,
-    pub tail: Option<Rc<Application<'i, D>>>,
+    pub expressions: Vec<Expression<'i, D>>,
     pub data: D::ApplicationData
 }
 
@@ -49,35 +55,77 @@ pub struct Program<'i, D: ASTData<'i>> {
     pub data: D::ProgramData
 }
 
-pub struct ApplicationIter<'a, 'i, D: ASTData<'i>> (
-    Option<&'a Application<'i, D>>
-);
+impl<'i, D: ASTData<'i>> Application<'i, D> {
+    pub fn iter(&self) -> impl Iterator<Item = &Expression<'i, D>> {
+        self.expressions.iter()
+    }
+}
 
-impl<'a, 'i, D: ASTData<'i>> Iterator for ApplicationIter<'a, 'i, D> {
-    type Item = &'a Expression<'i, D>;
+/// appends an expression to an application, wrapping a preceding lambda in
+/// parentheses so that it does not swallow the new expression
+pub fn push_expression<'i, D>(expressions: &mut Vec<Expression<'i, D>>, expr: Expression<'i, D>)
+    where D: ASTData<'i>, D::ApplicationData: Default
+{
+    if let Some(last) = expressions.last_mut() {
+        if let Expression::Lambda(lambda) = last {
+            *last = Expression::Parenthesis(Rc::new(Application {
+                expressions: vec![Expression::Lambda(lambda.clone())],
+                data: Default::default()
+            }));
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(app) = self.0 {
-            let expr = &app.head;
+    expressions.push(expr);
+}
 
-            self.0 = app.tail.as_ref()
-                .map(|app| app.as_ref());
+impl<'i, D: ASTData<'i>> Program<'i, D> {
+    pub fn iter(&self) -> impl Iterator<Item = &Assignment<'i, D>> {
+        self.assignments.iter()
+    }
+}
 
-            Some(expr)
-        } else {
-            None
+impl<'i, D: ASTData<'i>> PartialEq for Lambda<'i, D>
+    where D::ApplicationData: PartialEq, D::LambdaData: PartialEq
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.argument == other.argument && self.body == other.body && self.data == other.data
+    }
+}
+
+impl<'i, D: ASTData<'i>> PartialEq for Expression<'i, D>
+    where D::ApplicationData: PartialEq, D::LambdaData: PartialEq
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Expression::Lambda(a), Expression::Lambda(b)) => a == b,
+            (Expression::Parenthesis(a), Expression::Parenthesis(b)) => a == b,
+            (Expression::Identifier(a), Expression::Identifier(b)) => a == b,
+            _ => false
         }
     }
 }
 
-impl<'i, D: ASTData<'i>> Application<'i, D> {
-    pub fn iter(&self) -> impl Iterator<Item = &Expression<'i, D>> {
-        ApplicationIter(Some(self))
+impl<'i, D: ASTData<'i>> PartialEq for Application<'i, D>
+    where D::ApplicationData: PartialEq, D::LambdaData: PartialEq
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.expressions == other.expressions && self.data == other.data
     }
 }
 
-impl<'i, D: ASTData<'i>> Program<'i, D> {
-    pub fn iter(&self) -> impl Iterator<Item = &Assignment<'i, D>> {
-        self.assignments.iter()
+impl<'i, D: ASTData<'i>> PartialEq for Assignment<'i, D>
+    where D::ApplicationData: PartialEq, D::LambdaData: PartialEq, D::AssignmentData: PartialEq
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.target == other.target && self.value == other.value && self.data == other.data
+    }
+}
+
+impl<'i, D: ASTData<'i>> PartialEq for Program<'i, D>
+    where D::ApplicationData: PartialEq, D::LambdaData: PartialEq,
+          D::AssignmentData: PartialEq, D::ProgramData: PartialEq
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.assignments == other.assignments && self.data == other.data
     }
 }
@@ -32,7 +32,7 @@ pub fn make_identifier(pair: Pair<'_>) -> Result<Identifier<'_>, Error> {
     let ident = pair.as_str();
 
     let mut inner = pair.into_inner();
-    if inner.next() != None { ast_error_result()? }
+    if inner.next().is_some() { ast_error_result()? }
 
     Ok(ident)
 }
@@ -43,7 +43,7 @@ pub fn make_rc_lambda(pair: Pair<'_>) -> Result<Rc<Lambda<'_>>, Error> {
     let mut inner = pair.into_inner();
     let ident = inner.next().ok_or_else(ast_error)?;
     let expr = inner.next().ok_or_else(ast_error)?;
-    if inner.next() != None { ast_error_result()? }
+    if inner.next().is_some() { ast_error_result()? }
 
     Ok(Rc::new(Lambda {
         argument: make_identifier(ident)?,
@@ -61,7 +61,7 @@ pub fn make_rc_parenthesis(pair: Pair<'_>) -> Result<Rc<Application<'_>>, Error>
 
     let mut inner = pair.into_inner();
     let app = inner.next().ok_or_else(ast_error)?;
-    if inner.next() != None { ast_error_result()? }
+    if inner.next().is_some() { ast_error_result()? }
 
     make_rc_application(app)
 }
@@ -75,7 +75,7 @@ pub fn make_expression(pair: Pair<'_>) -> Result<Expression<'_>, Error> {
 
     let mut inner = pair.into_inner();
     let expr = inner.next().ok_or_else(ast_error)?;
-    if inner.next() != None { ast_error_result()? }
+    if inner.next().is_some() { ast_error_result()? }
 
     match expr.as_rule() {
         Rule::lambda => make_rc_lambda(expr).map(Expression::Lambda),
@@ -92,15 +92,12 @@ pub fn make_rc_application(pair: Pair<'_>) -> Result<Rc<Application<'_>>, Error>
         .map(make_expression)
         .collect::<Result<Vec<_>, _>>()?;
 
-    let app = exprs.into_iter()
-        .rev()
-        .fold(None, |tail, head| Some(Rc::new(Application {
-            head,
-            tail,
-            data: ()
-        })));
+    if exprs.is_empty() { ast_error_result()? }
 
-    app.ok_or_else(ast_error)
+    Ok(Rc::new(Application {
+        expressions: exprs,
+        data: ()
+    }))
 }
 
 pub fn make_application(pair: Pair<'_>) -> Result<Application<'_>, Error> {
@@ -113,7 +110,7 @@ pub fn make_assignment(pair: Pair<'_>) -> Result<Assignment<'_>, Error> {
     let mut inner = pair.into_inner();
     let ident = inner.next().ok_or_else(ast_error)?;
     let app = inner.next().ok_or_else(ast_error)?;
-    if inner.next() != None { ast_error_result()? }
+    if inner.next().is_some() { ast_error_result()? }
 
     Ok(Assignment {
         target: make_identifier(ident)?,
@@ -144,7 +141,7 @@ pub fn from_pairs<'i, T, M>(mut pairs: Pairs<'i>, maker: M) -> Result<T, Error>
     where T: 'i, M: Maker<'i, T>
 {
     let pair = pairs.next().ok_or_else(ast_error)?;
-    if pairs.next() != None { ast_error_result()? }
+    if pairs.next().is_some() { ast_error_result()? }
 
     maker(pair)
 }
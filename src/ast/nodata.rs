@@ -2,7 +2,12 @@ use super::generic;
 
 #[derive(Debug, Clone, Copy)]
 pub struct NoData;
-impl generic::ASTData<'_> for NoData {}
+impl generic::ASTData<'_> for NoData {
+    type ProgramData = ();
+    type AssignmentData = ();
+    type ApplicationData = ();
+    type LambdaData = ();
+}
 
 pub use generic::Identifier;
 pub type Lambda<'i> = generic::Lambda<'i, NoData>;
@@ -0,0 +1,54 @@
+use super::generic::Identifier;
+use super::nodata::*;
+use super::visit::*;
+use super::visit_mut::*;
+use crate::parser::LambdaParser;
+
+#[derive(Default)]
+struct CountVisitor<'i> {
+    lambdas: usize,
+    identifiers: Vec<Identifier<'i>>
+}
+
+impl<'a, 'i: 'a> Visitor<'a, 'i, NoData> for CountVisitor<'i> {
+    fn visit_lambda(&mut self, lambda: &'a Lambda<'i>) {
+        self.lambdas += 1;
+        walk_lambda(self, lambda);
+    }
+
+    fn visit_identifier(&mut self, ident: Identifier<'i>) {
+        self.identifiers.push(ident);
+    }
+}
+
+struct RenameVisitor<'i>(Identifier<'i>, Identifier<'i>);
+
+impl<'i> VisitorMut<'i, NoData> for RenameVisitor<'i> {
+    fn visit_identifier_mut(&mut self, ident: &mut Identifier<'i>) {
+        if *ident == self.0 {
+            *ident = self.1;
+        }
+    }
+}
+
+#[test]
+fn test_visitor() {
+    let program = LambdaParser::parse_program("x = a -> b -> a (c -> c) b\ny = x x").unwrap();
+
+    let mut visitor = CountVisitor::default();
+    visitor.visit_program(&program);
+
+    assert_eq!(visitor.lambdas, 3);
+    assert_eq!(visitor.identifiers, vec!["a", "c", "b", "x", "x"]);
+}
+
+#[test]
+fn test_visitor_mut() {
+    let program = LambdaParser::parse_program("x = a -> b -> a (c -> c) b\ny = x x").unwrap();
+
+    let mut renamed = program.clone();
+    RenameVisitor("x", "z").visit_program_mut(&mut renamed);
+
+    assert_eq!(renamed.to_string(), "x = a -> b -> a (c -> c) b\ny = z z\n");
+    assert_eq!(program.to_string(), "x = a -> b -> a (c -> c) b\ny = x x\n");
+}
@@ -0,0 +1,63 @@
+use super::generic::*;
+
+pub trait Visitor<'a, 'i: 'a, D: ASTData<'i> + 'a> {
+    fn visit_program(&mut self, program: &'a Program<'i, D>) {
+        walk_program(self, program)
+    }
+
+    fn visit_assignment(&mut self, ass: &'a Assignment<'i, D>) {
+        walk_assignment(self, ass)
+    }
+
+    fn visit_application(&mut self, app: &'a Application<'i, D>) {
+        walk_application(self, app)
+    }
+
+    fn visit_expression(&mut self, expr: &'a Expression<'i, D>) {
+        walk_expression(self, expr)
+    }
+
+    fn visit_lambda(&mut self, lambda: &'a Lambda<'i, D>) {
+        walk_lambda(self, lambda)
+    }
+
+    fn visit_identifier(&mut self, _ident: Identifier<'i>) {}
+}
+
+pub fn walk_program<'a, 'i: 'a, D, V>(visitor: &mut V, program: &'a Program<'i, D>)
+    where D: ASTData<'i> + 'a, V: Visitor<'a, 'i, D> + ?Sized
+{
+    for ass in program.iter() {
+        visitor.visit_assignment(ass);
+    }
+}
+
+pub fn walk_assignment<'a, 'i: 'a, D, V>(visitor: &mut V, ass: &'a Assignment<'i, D>)
+    where D: ASTData<'i> + 'a, V: Visitor<'a, 'i, D> + ?Sized
+{
+    visitor.visit_application(&ass.value);
+}
+
+pub fn walk_application<'a, 'i: 'a, D, V>(visitor: &mut V, app: &'a Application<'i, D>)
+    where D: ASTData<'i> + 'a, V: Visitor<'a, 'i, D> + ?Sized
+{
+    for expr in app.iter() {
+        visitor.visit_expression(expr);
+    }
+}
+
+pub fn walk_expression<'a, 'i: 'a, D, V>(visitor: &mut V, expr: &'a Expression<'i, D>)
+    where D: ASTData<'i> + 'a, V: Visitor<'a, 'i, D> + ?Sized
+{
+    match expr {
+        Expression::Lambda(lambda) => visitor.visit_lambda(lambda),
+        Expression::Parenthesis(app) => visitor.visit_application(app),
+        Expression::Identifier(ident) => visitor.visit_identifier(ident)
+    }
+}
+
+pub fn walk_lambda<'a, 'i: 'a, D, V>(visitor: &mut V, lambda: &'a Lambda<'i, D>)
+    where D: ASTData<'i> + 'a, V: Visitor<'a, 'i, D> + ?Sized
+{
+    visitor.visit_application(&lambda.body);
+}
@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use super::generic::*;
+
+pub trait VisitorMut<'i, D: ASTData<'i> + Clone> {
+    fn visit_program_mut(&mut self, program: &mut Program<'i, D>) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_assignment_mut(&mut self, ass: &mut Assignment<'i, D>) {
+        walk_assignment_mut(self, ass)
+    }
+
+    fn visit_application_mut(&mut self, app: &mut Application<'i, D>) {
+        walk_application_mut(self, app)
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression<'i, D>) {
+        walk_expression_mut(self, expr)
+    }
+
+    fn visit_lambda_mut(&mut self, lambda: &mut Lambda<'i, D>) {
+        walk_lambda_mut(self, lambda)
+    }
+
+    fn visit_identifier_mut(&mut self, _ident: &mut Identifier<'i>) {}
+}
+
+pub fn walk_program_mut<'i, D, V>(visitor: &mut V, program: &mut Program<'i, D>)
+    where D: ASTData<'i> + Clone, V: VisitorMut<'i, D> + ?Sized
+{
+    for ass in program.assignments.iter_mut() {
+        visitor.visit_assignment_mut(ass);
+    }
+}
+
+pub fn walk_assignment_mut<'i, D, V>(visitor: &mut V, ass: &mut Assignment<'i, D>)
+    where D: ASTData<'i> + Clone, V: VisitorMut<'i, D> + ?Sized
+{
+    visitor.visit_application_mut(Rc::make_mut(&mut ass.value));
+}
+
+pub fn walk_application_mut<'i, D, V>(visitor: &mut V, app: &mut Application<'i, D>)
+    where D: ASTData<'i> + Clone, V: VisitorMut<'i, D> + ?Sized
+{
+    for expr in app.expressions.iter_mut() {
+        visitor.visit_expression_mut(expr);
+    }
+}
+
+pub fn walk_expression_mut<'i, D, V>(visitor: &mut V, expr: &mut Expression<'i, D>)
+    where D: ASTData<'i> + Clone, V: VisitorMut<'i, D> + ?Sized
+{
+    match expr {
+        Expression::Lambda(lambda) => visitor.visit_lambda_mut(Rc::make_mut(lambda)),
+        Expression::Parenthesis(app) => visitor.visit_application_mut(Rc::make_mut(app)),
+        Expression::Identifier(ident) => visitor.visit_identifier_mut(ident)
+    }
+}
+
+pub fn walk_lambda_mut<'i, D, V>(visitor: &mut V, lambda: &mut Lambda<'i, D>)
+    where D: ASTData<'i> + Clone, V: VisitorMut<'i, D> + ?Sized
+{
+    visitor.visit_application_mut(Rc::make_mut(&mut lambda.body));
+}
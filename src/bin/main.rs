@@ -1,5 +1,4 @@
 use std::str::FromStr;
-use std::ops::Deref;
 use std::path::PathBuf;
 use std::fs::read_to_string;
 
@@ -7,8 +6,10 @@ use structopt::StructOpt;
 
 use lambda::error::Error;
 use lambda::parser::LambdaParser;
-use lambda::analyze::analyze_program;
 use lambda::analyze::strip_data;
+use lambda::analyze::textual;
+use lambda::pass::*;
+use lambda::optimize::OptimizationOptions;
 use lambda::codegen::*;
 
 #[derive(StructOpt)]
@@ -16,83 +17,369 @@ use lambda::codegen::*;
 #[structopt(rename_all = "kebab-case")]
 enum Options {
     Check {
-        #[structopt(parse(from_os_str))]
-        file: PathBuf
+        #[structopt(flatten)]
+        input: Input,
+
+        #[structopt(flatten)]
+        pass_options: PassOptions
     },
     Pretty {
-        #[structopt(parse(from_os_str))]
-        file: PathBuf,
+        #[structopt(flatten)]
+        input: Input,
+
+        #[structopt(flatten)]
+        pass_options: PassOptions
     },
     Debug {
-        #[structopt(parse(from_os_str))]
-        file: PathBuf,
+        #[structopt(flatten)]
+        input: Input,
+
+        /// print the closure converted program instead
+        #[structopt(long)]
+        flat: bool,
+
+        #[structopt(flatten)]
+        pass_options: PassOptions
     },
     Codegen {
-        #[structopt(parse(from_os_str))]
-        file: PathBuf,
+        #[structopt(flatten)]
+        input: Input,
 
         #[structopt(long)]
-        target: Target
+        target: Target,
+
+        #[structopt(flatten)]
+        codegen_options: CodegenOptions,
+
+        #[structopt(flatten)]
+        pass_options: PassOptions
+    }
+}
+
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct Input {
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+
+    /// read the file as textual continuation IR instead of source code
+    #[structopt(long)]
+    ir: bool
+}
+
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct PassOptions {
+    /// comma-separated list of passes to run instead of the default pipeline
+    #[structopt(long, require_delimiter = true)]
+    passes: Option<Vec<String>>,
+
+    /// report code that can be simplified on stderr
+    #[structopt(long)]
+    lint: bool,
+
+    /// optimization level
+    #[structopt(short = "O", default_value = "0")]
+    opt_level: u32,
+
+    /// maximum size of definitions that get inlined
+    #[structopt(long, default_value = "16")]
+    inline_threshold: usize,
+
+    /// maximum number of reduction steps spent on folding each definition
+    #[structopt(long, default_value = "100000")]
+    eval_budget: usize,
+
+    /// maximum size of the normal form of a folded term
+    #[structopt(long, default_value = "512")]
+    fold_threshold: usize,
+
+    /// print the IR after the given pass to stderr
+    #[structopt(long, number_of_values = 1)]
+    dump_after: Vec<String>,
+
+    /// print the IR after every pass to stderr
+    #[structopt(long)]
+    dump_all: bool,
+
+    /// print the time taken by each pass to stderr
+    #[structopt(long)]
+    time_passes: bool
+}
+
+impl PassOptions {
+    /// builds the pipeline, flat lowers the default pipeline to flat IR
+    fn pass_manager(&self, input: &Input, flat: bool) -> Result<PassManager, Error> {
+        let mut pm = PassManager::new();
+
+        let options = OptimizationOptions {
+            level: self.opt_level,
+            inline_threshold: self.inline_threshold,
+            eval_budget: self.eval_budget,
+            fold_threshold: self.fold_threshold
+        };
+
+        if let Some(passes) = &self.passes {
+            for name in passes.iter() {
+                pm.add_pass(pass_from_name(name, &options)?);
+            }
+        } else {
+            if !input.ir {
+                pm.add_check_passes();
+
+                if self.lint {
+                    pm.add_lint_passes();
+                }
+
+                pm.add_optimization_passes(&options);
+                pm.add_analysis_passes();
+            }
+
+            if flat {
+                pm.add_flat_passes();
+            }
+        }
+
+        for name in self.dump_after.iter() {
+            pm.dump_after(name);
+        }
+
+        pm.dump_all(self.dump_all);
+        pm.time_passes(self.time_passes);
+
+        Ok(pm)
     }
 }
 
-struct Target(Box<dyn CodegenTarget>);
+enum Target {
+    C,
+    CPlusPlus,
+    CPlusPlusCPS,
+    JavaScript,
+    Llvm,
+    Lua,
+    Python,
+    PythonCPS,
+    Rust,
+    Wat
+}
 
 impl FromStr for Target {
     type Err = &'static str;
     fn from_str(target: &str) -> Result<Target, Self::Err> {
-        Ok(Target(match target {
-            "c++" | "cplusplus" | "cxx" | "cpp" => Box::new(CPlusPlus),
-            "c++-cps" | "cplusplus-cps" | "cxx-cps" | "cpp-cps" => Box::new(CPlusPlusCPS),
-            "javascript" | "js" => Box::new(JavaScript),
-            "lua" => Box::new(Lua),
-            "python" | "py" => Box::new(Python),
+        Ok(match target {
+            "c" => Target::C,
+            "c++" | "cplusplus" | "cxx" | "cpp" => Target::CPlusPlus,
+            "c++-cps" | "cplusplus-cps" | "cxx-cps" | "cpp-cps" => Target::CPlusPlusCPS,
+            "javascript" | "js" => Target::JavaScript,
+            "llvm" | "ll" => Target::Llvm,
+            "lua" => Target::Lua,
+            "python" | "py" => Target::Python,
+            "python-cps" | "py-cps" => Target::PythonCPS,
+            "rust" | "rs" => Target::Rust,
+            "wat" => Target::Wat,
             _ => return Err("unsupported target")
-        }))
+        })
     }
 }
 
-impl Deref for Target {
-    type Target = dyn CodegenTarget;
-    fn deref(&self) -> &Self::Target {
-        &*self.0
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct CodegenOptions {
+    /// how the cpp-cps target allocates continuations and lambdas (heap or pool)
+    #[structopt(long, default_value = "pool")]
+    allocator: Allocator,
+
+    /// how the cpp-cps target frees lambdas (refcount or mark-sweep), mark-sweep implies --trampoline
+    #[structopt(long, default_value = "refcount")]
+    gc: Collector,
+
+    /// make the cpp-cps target run calls from a driver loop, keeping the stack flat
+    #[structopt(long)]
+    trampoline: bool,
+
+    /// make the cpp-cps target report objects that are still alive at exit
+    #[structopt(long)]
+    leak_check: bool,
+
+    /// emit a main function that runs the entry definition with the IO primitives
+    #[structopt(long)]
+    executable: bool,
+
+    /// definition that the main function of an executable runs
+    #[structopt(long, default_value = "start")]
+    entry: String,
+
+    /// module format of the javascript target (script, esm or commonjs)
+    #[structopt(long, default_value = "script")]
+    module: ModuleFormat,
+
+    /// definition exported by a javascript, lua or rust module, all of them if not given
+    #[structopt(long, number_of_values = 1)]
+    export: Vec<String>
+}
+
+enum Backend {
+    Continuations(Box<dyn CodegenTarget>),
+    Flat(Box<dyn FlatCodegenTarget>)
+}
+
+impl Backend {
+    /// the IR stage the target generates code from
+    fn stage(&self) -> &'static str {
+        match self {
+            Backend::Continuations(_) => "continuation-captures",
+            Backend::Flat(_) => "flat"
+        }
+    }
+}
+
+impl CodegenOptions {
+    fn target(&self, target: &Target) -> Backend {
+        let executable = if self.executable {
+            Some(self.entry.clone())
+        } else {
+            None
+        };
+
+        match target {
+            Target::C => Backend::Flat(Box::new(C { executable })),
+            Target::CPlusPlus => Backend::Continuations(Box::new(CPlusPlus { executable })),
+            Target::CPlusPlusCPS => Backend::Flat(Box::new(CPlusPlusCPS {
+                allocator: self.allocator,
+                collector: self.gc,
+                trampoline: self.trampoline,
+                leak_check: self.leak_check,
+                executable
+            })),
+            Target::JavaScript => Backend::Continuations(Box::new(JavaScript {
+                module: self.module,
+                exports: self.export.clone(),
+                executable
+            })),
+            Target::Llvm => Backend::Flat(Box::new(Llvm { executable })),
+            Target::Lua => Backend::Continuations(Box::new(Lua {
+                exports: self.export.clone(),
+                executable
+            })),
+            Target::Python => Backend::Continuations(Box::new(Python { executable })),
+            Target::PythonCPS => Backend::Flat(Box::new(PythonCPS { executable })),
+            Target::Rust => Backend::Flat(Box::new(Rust {
+                exports: self.export.clone(),
+                executable
+            })),
+            Target::Wat => Backend::Flat(Box::new(Wat { executable }))
+        }
+    }
+}
+
+/// everything the passes report goes to stderr, stdout only gets the program
+/// so that it can be redirected to a file
+fn print_pass_output(output: &PassOutput) {
+    for diagnostic in output.diagnostics.iter() {
+        eprintln!("{}", diagnostic);
+    }
+
+    for (name, dump) in output.dumps.iter() {
+        eprintln!("*** IR dump after {} ***\n{}", name, dump);
+    }
+
+    for (name, duration) in output.timings.iter() {
+        eprintln!("{:>12.3} ms  {}", duration.as_secs_f64() * 1000.0, name);
     }
 }
 
 fn main() -> Result<(), String> {
     let opt = Options::from_args();
 
-    let file = match &opt {
-        Options::Check { file, .. } => file,
-        Options::Pretty { file, .. } => file,
-        Options::Debug { file, .. } => file,
-        Options::Codegen { file, .. } => file
+    let (input, pass_options) = match &opt {
+        Options::Check { input, pass_options, .. } => (input, pass_options),
+        Options::Pretty { input, pass_options, .. } => (input, pass_options),
+        Options::Debug { input, pass_options, .. } => (input, pass_options),
+        Options::Codegen { input, pass_options, .. } => (input, pass_options)
     };
 
-    let code = read_to_string(file)
+    let code = read_to_string(&input.file)
         .map_err(|e| format!("failed to read file: {}", e))?;
 
-    let parsed = match LambdaParser::parse_program(&code) {
-        Ok(program) => program,
+    let parsed = if input.ir {
+        textual::parse_program(&code).map(Ir::ContinuationCaptures)
+    } else {
+        LambdaParser::parse_program(&code).map(Ir::Source)
+    };
+
+    let parsed = match parsed {
+        Ok(ir) => ir,
         Err(Error::ParseError(e)) => {
             eprintln!("\n{}", e);
             return Err(String::from("failed to parse program"));
         }
-        Err(Error::AstMakeError(_)) => return Err(String::from("failed to build AST"))
+        Err(Error::IrParseError(e)) => {
+            eprintln!("\n{}", e);
+            return Err(String::from("failed to parse IR"));
+        }
+        Err(_) => return Err(String::from("failed to build AST"))
     };
 
-    let analyze_result = analyze_program(&parsed);
-    for diagnostic in analyze_result.diagnostics {
-        println!("{}", diagnostic);
+    let backend = match &opt {
+        Options::Codegen { target, codegen_options, .. } => Some(codegen_options.target(target)),
+        _ => None
+    };
+
+    let flat = matches!(opt, Options::Debug { flat: true, .. }) || matches!(backend, Some(Backend::Flat(_)));
+
+    let pass_result = pass_options.pass_manager(input, flat)
+        .and_then(|pm| pm.run(parsed))
+        .map_err(|e| match e {
+            Error::PassError(e) => {
+                // the output of the passes before a failing one explains the failure
+                if let PassError::Failed { output, .. } = &e {
+                    print_pass_output(output);
+                }
+
+                e.to_string()
+            }
+            _ => String::from("failed to run passes")
+        })?;
+
+    print_pass_output(&pass_result.output);
+
+    // a custom pipeline may stop at any IR, debug prints whatever it produced
+    if let Options::Debug { .. } = opt {
+        print!("{}", pass_result.ir);
+        return Ok(());
     }
 
-    let stripped = strip_data::transform_program(&analyze_result.program);
+    let expected = backend.as_ref().map_or("continuation-captures", Backend::stage);
+
+    if pass_result.ir.stage() != expected {
+        return Err(format!("pipeline ends in {} IR, expected {} IR", pass_result.ir.stage(), expected));
+    }
+
+    if let Options::Codegen { codegen_options, .. } = &opt {
+        let definitions: Vec<&str> = match &pass_result.ir {
+            Ir::ContinuationCaptures(program) => program.iter().map(|ass| ass.target).collect(),
+            Ir::Flat(program) => program.iter().map(|def| def.target).collect(),
+            ir => unreachable!("codegen from {} IR", ir.stage())
+        };
+
+        let defined = |name: &str| definitions.contains(&name);
+
+        if codegen_options.executable && !defined(&codegen_options.entry) {
+            return Err(format!("entry definition '{}' not found", codegen_options.entry));
+        }
+
+        if let Some(name) = codegen_options.export.iter().find(|name| !defined(name)) {
+            return Err(format!("exported definition '{}' not found", name));
+        }
+    }
 
-    match opt {
-        Options::Check { .. } => {}
-        Options::Pretty { .. } => print!("{}", stripped),
-        Options::Debug { .. } => println!("{:#?}", analyze_result.program),
-        Options::Codegen { target, .. } => print!("{}", target.generate(&analyze_result.program))
+    match (opt, backend, pass_result.ir) {
+        (Options::Pretty { .. }, _, Ir::ContinuationCaptures(program)) => print!("{}", strip_data::transform_program(&program)),
+        (_, Some(Backend::Continuations(target)), Ir::ContinuationCaptures(program)) => print!("{}", target.generate(&program)),
+        (_, Some(Backend::Flat(target)), Ir::Flat(program)) => print!("{}", target.generate(&program)),
+        (Options::Check { .. }, ..) => {}
+        (_, _, ir) => unreachable!("nothing to do with {} IR", ir.stage())
     }
 
     Ok(())
@@ -1,19 +1,43 @@
 use crate::analyze::*;
+use crate::analyze::closure_conversion as flat;
 
+/// a target that generates code from the continuation IR
 pub trait CodegenTarget {
     fn generate(&self, program: &Program<'_>) -> String;
 }
 
+/// a target that generates code from the closure converted IR, the program
+/// comes from the anf and closure-conversion passes
+pub trait FlatCodegenTarget {
+    fn generate(&self, program: &flat::Program<'_>) -> String;
+}
+
 mod util;
 
+mod c;
 mod cplusplus;
 mod cplusplus_cps;
 mod javascript;
+mod llvm;
 mod lua;
 mod python;
+mod python_cps;
+mod rust;
+mod wat;
 
+pub use c::C;
 pub use cplusplus::CPlusPlus;
 pub use cplusplus_cps::CPlusPlusCPS;
+pub use cplusplus_cps::Allocator;
+pub use cplusplus_cps::Collector;
 pub use javascript::JavaScript;
+pub use javascript::ModuleFormat;
+pub use llvm::Llvm;
 pub use lua::Lua;
 pub use python::Python;
+pub use python_cps::PythonCPS;
+pub use rust::Rust;
+pub use wat::Wat;
+
+#[cfg(test)]
+mod test;
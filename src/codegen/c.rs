@@ -0,0 +1,431 @@
+use super::*;
+use crate::analyze::closure_conversion as flat;
+use flat::Local;
+use flat::Variable;
+use flat::Value;
+use flat::Body;
+use flat::Initializer;
+
+/// C keywords up to C23, the names of the runtime and of the standard
+/// library headers it includes
+static RESERVED_WORDS: [&str; 199] = [
+    "abort",
+    "abs",
+    "alignas",
+    "alignof",
+    "atexit",
+    "atof",
+    "atoi",
+    "atol",
+    "atoll",
+    "auto",
+    "bool",
+    "break",
+    "bsearch",
+    "BUFSIZ",
+    "calloc",
+    "case",
+    "char",
+    "clearerr",
+    "const",
+    "constexpr",
+    "Cont",
+    "cont",
+    "cont_call",
+    "cont_mk",
+    "continue",
+    "default",
+    "div",
+    "div_t",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "EOF",
+    "exit",
+    "EXIT_FAILURE",
+    "EXIT_SUCCESS",
+    "extern",
+    "false",
+    "fclose",
+    "feof",
+    "ferror",
+    "fflush",
+    "fgetc",
+    "fgetpos",
+    "fgets",
+    "FILE",
+    "FILENAME_MAX",
+    "float",
+    "fopen",
+    "FOPEN_MAX",
+    "for",
+    "fpos_t",
+    "fprintf",
+    "fputc",
+    "fputs",
+    "fread",
+    "free",
+    "freopen",
+    "fscanf",
+    "fseek",
+    "fsetpos",
+    "ftell",
+    "fwrite",
+    "getc",
+    "getchar",
+    "getenv",
+    "gets",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "io_false",
+    "io_false_0",
+    "io_false_1",
+    "io_false_1_c",
+    "io_getchar",
+    "io_getchar_fn",
+    "io_ident",
+    "io_identity",
+    "io_integer",
+    "io_iszero",
+    "io_iszero_fn",
+    "io_pred",
+    "io_pred_fn",
+    "io_putchar",
+    "io_putchar_fn",
+    "io_run",
+    "io_succ",
+    "io_succ_fn",
+    "io_true",
+    "io_true_0",
+    "io_true_1",
+    "L_tmpnam",
+    "labs",
+    "Lambda",
+    "lambda_alloc",
+    "lambda_call",
+    "LAMBDA_CONSTANT",
+    "lambda_drop",
+    "LAMBDA_IMMORTAL",
+    "lambda_immortal",
+    "lambda_init",
+    "lambda_jump",
+    "lambda_mk",
+    "lambda_next_call",
+    "lambda_nop",
+    "lambda_nop_fn",
+    "lambda_ref",
+    "lambda_ret",
+    "lambda_return",
+    "lambda_return_fn",
+    "lambda_unref",
+    "LambdaCall",
+    "LambdaFn",
+    "ldiv",
+    "ldiv_t",
+    "llabs",
+    "lldiv",
+    "lldiv_t",
+    "long",
+    "main",
+    "malloc",
+    "MB_CUR_MAX",
+    "mblen",
+    "mbstowcs",
+    "mbtowc",
+    "NULL",
+    "nullptr",
+    "offsetof",
+    "perror",
+    "printf",
+    "ptrdiff_t",
+    "putc",
+    "putchar",
+    "puts",
+    "qsort",
+    "rand",
+    "RAND_MAX",
+    "realloc",
+    "register",
+    "remove",
+    "rename",
+    "restrict",
+    "return",
+    "rewind",
+    "scanf",
+    "SEEK_CUR",
+    "SEEK_END",
+    "SEEK_SET",
+    "self",
+    "setbuf",
+    "setvbuf",
+    "short",
+    "signed",
+    "size_t",
+    "sizeof",
+    "snprintf",
+    "sprintf",
+    "srand",
+    "sscanf",
+    "static",
+    "static_assert",
+    "stderr",
+    "stdin",
+    "stdout",
+    "strtod",
+    "strtof",
+    "strtol",
+    "strtold",
+    "strtoll",
+    "strtoul",
+    "strtoull",
+    "struct",
+    "switch",
+    "system",
+    "thread_local",
+    "TMP_MAX",
+    "tmpfile",
+    "tmpnam",
+    "true",
+    "typedef",
+    "typeof",
+    "typeof_unqual",
+    "ungetc",
+    "union",
+    "unsigned",
+    "vfprintf",
+    "vfscanf",
+    "void",
+    "volatile",
+    "vprintf",
+    "vscanf",
+    "vsnprintf",
+    "vsprintf",
+    "vsscanf",
+    "wchar_t",
+    "wcstombs",
+    "wctomb",
+    "while"
+];
+
+static CODEGEN_PRELUDE: &str = include_str!("prelude.c");
+static CODEGEN_HARNESS: &str = include_str!("harness.c");
+
+/// emits C99 from the closure converted program, calls always go through
+/// the trampoline in the runtime since C compilers need not eliminate tail calls
+#[derive(Debug, Clone, Default)]
+pub struct C {
+    /// emit a main() that passes the IO primitives to this definition
+    pub executable: Option<String>
+}
+
+/// counts the references an implementation passes on, globals and
+/// constants are immortal and need no references
+#[derive(Debug, Clone, Default)]
+struct ImplementationContext {
+    arg_references: usize,
+    capture_references: Vec<usize>
+}
+
+impl ImplementationContext {
+    fn new(function: &flat::Function<'_>) -> Self {
+        ImplementationContext {
+            arg_references: 0,
+            capture_references: vec![0; function.captures.len()]
+        }
+    }
+
+    fn reference(&mut self, var: Variable<'_>) {
+        match var {
+            Variable::Argument => self.arg_references += 1,
+            Variable::Capture(i) => self.capture_references[i] += 1,
+            Variable::Global(_) => {}
+        }
+    }
+}
+
+fn generate_identifier(ident: Identifier<'_>) -> String {
+    util::generate_suffix_identifier(ident, &RESERVED_WORDS, None)
+}
+
+fn generate_function_name(name: flat::FunctionName<'_>) -> String {
+    let lambda_id = name.lambda.map(|i| i + 1).unwrap_or(0);
+    util::generate_suffix_identifier(
+        name.assignment, &RESERVED_WORDS,
+        Some(format!("_{}_{}", lambda_id, name.continuation))
+    )
+}
+
+/// the statically allocated closure of a function without captures
+fn generate_constant_name(name: flat::FunctionName<'_>) -> String {
+    let lambda_id = name.lambda.map(|i| i + 1).unwrap_or(0);
+    util::generate_suffix_identifier(
+        name.assignment, &RESERVED_WORDS,
+        Some(format!("_{}_{}_c", lambda_id, name.continuation))
+    )
+}
+
+fn generate_local(local: Local<'_>) -> String {
+    match local {
+        Local::Unnamed => util::generate_suffix_identifier(
+            "arg", &RESERVED_WORDS, Some(String::new())
+        ),
+        Local::Temporary(id) => util::generate_suffix_identifier(
+            "ret", &RESERVED_WORDS,
+            Some(format!("_{}", id))
+        ),
+        Local::Identifier(ident) => generate_identifier(ident)
+    }
+}
+
+fn generate_variable<'i>(var: Variable<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    ictx.reference(var);
+
+    match var {
+        Variable::Argument => generate_local(function.argument),
+        Variable::Capture(i) => generate_local(function.captures[i]),
+        Variable::Global(ident) => generate_identifier(ident)
+    }
+}
+
+fn generate_closure<'i>(closure: &flat::Closure<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    let name = program.function(closure.function).name;
+
+    if closure.captures.is_empty() {
+        format!("&{}", generate_constant_name(name))
+    } else {
+        let captures: Vec<_> = closure.captures.iter()
+            .map(|&var| generate_variable(var, function, ictx))
+            .collect();
+
+        format!("lambda_mk({}, {}, (Lambda*[]) {{ {} }})",
+            generate_function_name(name), captures.len(), captures.join(", ")
+        )
+    }
+}
+
+fn generate_value<'i>(value: &Value<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    match value {
+        Value::Variable(var) => generate_variable(*var, function, ictx),
+        Value::Closure(closure) => generate_closure(closure, program, function, ictx)
+    }
+}
+
+fn generate_implementation(program: &flat::Program<'_>, id: usize) -> String {
+    let function = program.function(id);
+    let name = generate_function_name(function.name);
+    let arg_name = generate_local(function.argument);
+
+    let mut res = format!("Lambda* {}(Lambda* {}, Lambda* self, Cont* cont) {{\n", name, arg_name);
+    let mut ictx = ImplementationContext::new(function);
+
+    // constants have nothing to take from self
+    if function.captures.is_empty() {
+        res += "    (void) self;\n";
+    }
+
+    let ret = match &function.body {
+        Body::Call { function: func, argument, continuation } => {
+            let cont = match continuation {
+                Some(cont) => format!("cont_mk({}, cont)", generate_closure(cont, program, function, &mut ictx)),
+                None => String::from("cont")
+            };
+            let func = generate_value(func, program, function, &mut ictx);
+            let arg = generate_value(argument, program, function, &mut ictx);
+
+            format!("    return lambda_call({}, {}, {});\n", func, arg, cont)
+        }
+        Body::Return(value) => {
+            let arg = generate_value(value, program, function, &mut ictx);
+
+            format!("    return cont_call(cont, {});\n", arg)
+        }
+    };
+
+    for (i, refcount) in ictx.capture_references.iter().enumerate() {
+        if *refcount > 0 {
+            res += &format!("    Lambda* {} = self->captures[{}];\n",
+                generate_local(function.captures[i]), i
+            );
+        }
+    }
+
+    if ictx.arg_references == 0 {
+        res += &format!("    lambda_unref({});\n", arg_name);
+    } else if ictx.arg_references > 1 {
+        res += &format!("    lambda_ref({}, {});\n", arg_name, ictx.arg_references - 1);
+    }
+
+    // closures without captures are immortal constants, others hand their
+    // references to the captures over when they are dropped
+    if !function.captures.is_empty() {
+        let uses: Vec<_> = ictx.capture_references.iter()
+            .map(|refcount| refcount.to_string())
+            .collect();
+
+        res += &format!("    lambda_drop(self, (size_t[]) {{ {} }});\n", uses.join(", "));
+    }
+
+    res += &ret;
+    res += "}\n";
+
+    if function.captures.is_empty() {
+        res += &format!("static Lambda {} = LAMBDA_CONSTANT({});\n", generate_constant_name(function.name), name);
+    }
+
+    res
+}
+
+/// returns the declaration of a definition and the statement in
+/// lambda_init() that initializes it
+fn generate_definition<'i>(program: &flat::Program<'i>, def: &flat::Definition<'i>) -> (String, String) {
+    let mut res = String::from("\n");
+
+    for &id in def.functions.iter() {
+        res += &format!("{}\n", generate_implementation(program, id));
+    }
+
+    let value = match &def.value {
+        Initializer::Value(Value::Variable(var)) => match var {
+            Variable::Global(ident) => generate_identifier(ident),
+            _ => unreachable!("local variable in global scope")
+        },
+        Initializer::Value(Value::Closure(closure)) => format!("&{}",
+            generate_constant_name(program.function(closure.function).name)
+        ),
+        Initializer::Call(id) => format!("lambda_immortal(lambda_ret(&{}, NULL))",
+            generate_constant_name(program.function(*id).name)
+        )
+    };
+
+    let target = generate_identifier(def.target);
+
+    (
+        res + &format!("Lambda* {};\n", target),
+        format!("    {} = {};\n", target, value)
+    )
+}
+
+impl FlatCodegenTarget for C {
+    fn generate(&self, program: &flat::Program<'_>) -> String {
+        let mut res = String::from(CODEGEN_PRELUDE);
+        let mut init = String::new();
+
+        for def in program.iter() {
+            let (declaration, statement) = generate_definition(program, def);
+            res += &declaration;
+            init += &statement;
+        }
+
+        res += &format!("\nvoid lambda_init(void) {{\n{}}}\n", init);
+
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\nint main(void) {{\n    lambda_init();\n    io_run({});\n}}\n", generate_identifier(entry));
+        }
+
+        res
+    }
+}
@@ -1,6 +1,6 @@
 use super::*;
 
-static RESERVED_WORDS: [&str; 96] = [
+static RESERVED_WORDS: [&str; 101] = [
     "alignas",
     "alignof",
     "and",
@@ -96,13 +96,22 @@ static RESERVED_WORDS: [&str; 96] = [
     "while",
     "xor",
     "xor_eq ",
-    "lambda"
+    "lambda",
+    "main",
+    "puts",
+    "getchar",
+    "putchar",
+    "io"
 ];
 
 static CODEGEN_PRELUDE: &str = include_str!("prelude.cpp");
+static CODEGEN_HARNESS: &str = include_str!("harness.cpp");
 
-#[derive(Debug, Clone, Copy)]
-pub struct CPlusPlus;
+#[derive(Debug, Clone, Default)]
+pub struct CPlusPlus {
+    /// emit a main() that passes the IO primitives to this definition
+    pub executable: Option<String>
+}
 
 fn generate_identifier(ident: Identifier<'_>) -> String {
     util::generate_identifier(ident, &RESERVED_WORDS)
@@ -155,6 +164,11 @@ impl CodegenTarget for CPlusPlus {
             res += &format!("{}\n", generate_assignment(ass));
         }
 
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\nint main() {{\n    io::run({});\n}}\n", generate_identifier(entry));
+        }
+
         res
     }
 }
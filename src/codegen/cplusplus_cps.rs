@@ -1,10 +1,15 @@
-use std::collections::BTreeSet;
+use std::str::FromStr;
 use std::collections::BTreeMap;
-use std::collections::btree_map::Entry::Occupied;
 
 use super::*;
-
-static RESERVED_WORDS: [&str; 102] = [
+use crate::analyze::closure_conversion as flat;
+use flat::Local;
+use flat::Variable;
+use flat::Value;
+use flat::Body;
+use flat::Initializer;
+
+static RESERVED_WORDS: [&str; 105] = [
     "alignas",
     "alignof",
     "and",
@@ -106,103 +111,106 @@ static RESERVED_WORDS: [&str; 102] = [
     "Cont",
     "LambdaFn",
     "self",
-    "cont"
+    "cont",
+    "getchar",
+    "putchar",
+    "io"
 ];
 
 static CODEGEN_PRELUDE: &str = include_str!("prelude_cps.cpp");
-
-#[derive(Debug, Clone, Copy)]
-pub struct CPlusPlusCPS;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ArgName<'i> {
-    Unnamed,
-    Anonymous(usize),
-    Identifier(Identifier<'i>)
-}
-
-#[derive(Debug)]
-struct Implementation<'i, 'a> {
-    id: usize,
-    arg_name: ArgName<'i>,
-    function: Option<&'a Literal<'i>>,
-    argument: &'a Literal<'i>,
-    captures: &'a BTreeSet<Identifier<'i>>,
-    anonymous_captures: &'a BTreeSet<usize>,
-    next: Option<&'a Continuation<'i>>
+static CODEGEN_HARNESS: &str = include_str!("harness_cps.cpp");
+
+/// the largest number of captures of a lambda that is allocated from a pool
+const MAX_POOL_CAPTURES: usize = 8;
+
+/// how the generated program allocates continuations and lambdas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Allocator {
+    /// every object is allocated with new and freed with delete
+    Heap,
+    /// continuations and small lambdas are recycled through free lists
+    #[default]
+    Pool
 }
 
-#[derive(Debug)]
-struct ImplementationChain<'i, 'a> {
-    arg_name: Option<Identifier<'i>>,
-    continuations: &'a [Continuation<'i>],
-    result_literal: &'a Literal<'i>,
-    captures: &'a BTreeSet<Identifier<'i>>
+impl FromStr for Allocator {
+    type Err = &'static str;
+    fn from_str(allocator: &str) -> Result<Allocator, Self::Err> {
+        match allocator {
+            "heap" => Ok(Allocator::Heap),
+            "pool" => Ok(Allocator::Pool),
+            _ => Err("unsupported allocator")
+        }
+    }
 }
 
-struct AssignmentContext<'i> {
-    cur_assignment: Identifier<'i>,
-    cur_lambda_id: Option<usize>,
-    impls: Vec<String>,
+/// how the generated program frees lambdas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collector {
+    /// lambdas are freed when their last reference is dropped
+    #[default]
+    Refcount,
+    /// lambdas are traced from the globals and the pending call whenever the
+    /// driver loop has allocated enough, this implies the trampoline
+    MarkSweep
 }
 
-impl<'i> AssignmentContext<'i> {
-    fn new(cur_assignment: Identifier<'i>, cur_lambda_id: Option<usize>) -> Self {
-        AssignmentContext {
-            cur_assignment,
-            cur_lambda_id,
-            impls: Vec::new()
+impl FromStr for Collector {
+    type Err = &'static str;
+    fn from_str(collector: &str) -> Result<Collector, Self::Err> {
+        match collector {
+            "refcount" => Ok(Collector::Refcount),
+            "mark-sweep" => Ok(Collector::MarkSweep),
+            _ => Err("unsupported collector")
         }
     }
+}
 
-    fn add_impl(&mut self, s: String) {
-        self.impls.push(s)
+#[derive(Debug, Clone, Default)]
+pub struct CPlusPlusCPS {
+    pub allocator: Allocator,
+    pub collector: Collector,
+    /// return every call to a driver loop instead of relying on the C++
+    /// compiler to turn it into a tail call
+    pub trampoline: bool,
+    /// report objects that are still alive when the program exits
+    pub leak_check: bool,
+    /// emit a main() that passes the IO primitives to this definition
+    pub executable: Option<String>
+}
+
+impl CPlusPlusCPS {
+    /// the mark-sweep collector only runs between calls in the driver loop
+    fn uses_trampoline(&self) -> bool {
+        self.trampoline || self.collector == Collector::MarkSweep
     }
 }
 
+/// globals whose value is a closure of a known implementation, these are
+/// called directly instead of through the function pointer
+type KnownFunctions<'i> = BTreeMap<Identifier<'i>, usize>;
+
+/// counts the references an implementation passes on, globals are immortal
+/// and need no references
 #[derive(Debug, Clone, Default)]
-struct ImplementationContext<'i> {
-    arg_name: Option<ArgName<'i>>,
+struct ImplementationContext {
     arg_references: usize,
-    global_references: BTreeMap<Identifier<'i>, usize>,
-    capture_references: BTreeMap<Identifier<'i>, usize>,
-    anonymous_references: BTreeMap<usize, usize>,
+    capture_references: Vec<usize>
 }
 
-impl<'i> ImplementationContext<'i> {
-    fn new(arg_name: ArgName<'i>, captures: &BTreeSet<Identifier<'i>>, anonymous_captures: &BTreeSet<usize>) -> Self {
+impl ImplementationContext {
+    fn new(function: &flat::Function<'_>) -> Self {
         ImplementationContext {
-            arg_name: Some(arg_name),
             arg_references: 0,
-            global_references: BTreeMap::new(),
-            capture_references: captures.iter()
-                .cloned()
-                .map(|c| (c, 0))
-                .collect(),
-            anonymous_references: anonymous_captures.iter()
-                .cloned()
-                .map(|c| (c, 0))
-                .collect()
-        }
-    }
-
-    fn reference_identifier(&mut self, ident: Identifier<'i>) {
-        if Some(ArgName::Identifier(ident)) == self.arg_name {
-            self.arg_references += 1;
-        } else if let Occupied(mut entry) = self.capture_references.entry(ident) {
-            *entry.get_mut() += 1;
-        } else {
-            *self.global_references.entry(ident).or_insert(0) += 1;
+            capture_references: vec![0; function.captures.len()]
         }
     }
 
-    fn reference_anonymous(&mut self, anon: usize) {
-        if Some(ArgName::Anonymous(anon)) == self.arg_name {
-            self.arg_references += 1;
-        } else if let Occupied(mut entry) = self.anonymous_references.entry(anon) {
-            *entry.get_mut() += 1;
-        } else {
-            panic!("uncaptured anonymous literal '{}' referenced!", anon);
+    fn reference(&mut self, var: Variable<'_>) {
+        match var {
+            Variable::Argument => self.arg_references += 1,
+            Variable::Capture(i) => self.capture_references[i] += 1,
+            Variable::Global(_) => {}
         }
     }
 }
@@ -218,94 +226,122 @@ fn generate_anonymous_identifier(id: usize) -> String {
     )
 }
 
-fn generate_cont_identifier(ident: Identifier<'_>, lambda_id: Option<usize>, cont_id: usize) -> String {
-    let lambda_id = lambda_id.map(|i| i + 1).unwrap_or(0);
+fn generate_function_name(name: flat::FunctionName<'_>) -> String {
+    let lambda_id = name.lambda.map(|i| i + 1).unwrap_or(0);
     util::generate_suffix_identifier(
-        ident, &RESERVED_WORDS,
-        Some(format!("_{}_{}", lambda_id, cont_id))
+        name.assignment, &RESERVED_WORDS,
+        Some(format!("_{}_{}", lambda_id, name.continuation))
     )
 }
 
-fn generate_arg_name_identifier(arg_name: ArgName<'_>) -> String {
-    match arg_name {
-        ArgName::Unnamed => util::generate_suffix_identifier(
+fn generate_local(local: Local<'_>) -> String {
+    match local {
+        Local::Unnamed => util::generate_suffix_identifier(
             "arg", &RESERVED_WORDS, None
         ),
-        ArgName::Anonymous(id) => generate_anonymous_identifier(id),
-        ArgName::Identifier(ident) => generate_identifier(ident)
+        Local::Temporary(id) => generate_anonymous_identifier(id),
+        Local::Identifier(ident) => generate_identifier(ident)
     }
 }
 
-fn generate_literal<'i>(lit: &Literal<'i>, actx: &mut AssignmentContext<'i>, ictx: &mut ImplementationContext<'i>) -> String {
-    match lit {
-        Literal::Anonymous(id) => {
-            ictx.reference_anonymous(*id);
-            generate_anonymous_identifier(*id)
-        }
-        Literal::Identifier(ident) => {
-            ictx.reference_identifier(ident);
-            generate_identifier(ident)
-        },
-        Literal::Lambda(lambda) => generate_lambda(&lambda, actx, ictx)
+fn generate_variable<'i>(var: Variable<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    ictx.reference(var);
+
+    match var {
+        Variable::Argument => generate_local(function.argument),
+        Variable::Capture(i) => generate_local(function.captures[i]),
+        Variable::Global(ident) => generate_identifier(ident)
     }
 }
 
-fn generate_captures<'i>(captures: &BTreeSet<Identifier<'i>>, anonymous_captures: &BTreeSet<usize>, ictx: &mut ImplementationContext<'i>) -> String {
+fn generate_captures<'i>(captures: &[Variable<'i>], function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
     let cap: Vec<_> = captures.iter()
-        .map(|ident| {
-            ictx.reference_identifier(ident);
-            generate_identifier(ident)
-        }).collect();
-    let anon_cap = anonymous_captures.iter()
-        .map(|anon| {
-            ictx.reference_anonymous(*anon);
-            generate_anonymous_identifier(*anon)
-        });
-
-    format!("{{{}}}", cap.into_iter().chain(anon_cap).collect::<Vec<_>>().join(", "))
+        .map(|&var| generate_variable(var, function, ictx))
+        .collect();
+
+    format!("{{{}}}", cap.join(", "))
 }
 
-fn generate_continuation<'i>(cont: &Continuation<'i>, actx: &mut AssignmentContext<'_>, ictx: &mut ImplementationContext<'i>) -> String {
-    let cont_name = generate_cont_identifier(actx.cur_assignment, actx.cur_lambda_id, cont.id);
-    let n = cont.captures.len() + cont.anonymous_captures.len();
+/// closures without captures use a shared lambda instead of allocating
+fn generate_closure<'i>(closure: &flat::Closure<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    let name = generate_function_name(program.function(closure.function).name);
 
-    format!("Cont::mk<{}>({}, {}, cont)", n, cont_name, generate_captures(&cont.captures, &cont.anonymous_captures, ictx))
+    if closure.captures.is_empty() {
+        format!("Lambda::constant<{}>()", name)
+    } else {
+        format!("Lambda::mk<{}>({}, {})",
+            closure.captures.len(), name,
+            generate_captures(&closure.captures, function, ictx)
+        )
+    }
 }
 
-fn generate_implementation<'i>(imp: Implementation<'i, '_>, actx: &mut AssignmentContext<'i>) {
-    let cont_name = generate_cont_identifier(actx.cur_assignment, actx.cur_lambda_id, imp.id);
-    let arg_name = generate_arg_name_identifier(imp.arg_name);
+fn generate_value<'i>(value: &Value<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    match value {
+        Value::Variable(var) => generate_variable(*var, function, ictx),
+        Value::Closure(closure) => generate_closure(closure, program, function, ictx)
+    }
+}
 
-    let mut res = format!("Lambda* {}(Lambda* {}, Lambda* self, Cont* cont) {{\n",
-        cont_name, arg_name
-    );
+/// the implementation a value will call, if it is known at compile time
+fn known_function(value: &Value<'_>, known: &KnownFunctions<'_>) -> Option<usize> {
+    match value {
+        Value::Closure(closure) => Some(closure.function),
+        Value::Variable(Variable::Global(ident)) => known.get(ident).copied(),
+        Value::Variable(_) => None
+    }
+}
 
-    let mut ictx = ImplementationContext::new(imp.arg_name, imp.captures, imp.anonymous_captures);
+fn generate_implementation(target: &CPlusPlusCPS, program: &flat::Program<'_>, known: &KnownFunctions<'_>, id: usize) -> String {
+    let function = program.function(id);
+    let name = generate_function_name(function.name);
+    let arg_name = generate_local(function.argument);
 
-    let next = imp.next.map(|next| generate_continuation(next, actx, &mut ictx));
-    let func = imp.function.map(|lit| generate_literal(lit, actx, &mut ictx));
-    let arg = generate_literal(imp.argument, actx, &mut ictx);
+    let mut res = format!("Lambda* {}(Lambda* {}, Lambda* self, Cont* cont) {{\n",
+        name, arg_name
+    );
 
-    let mut i = 0;
+    let mut ictx = ImplementationContext::new(function);
 
-    let cap = ictx.capture_references.into_iter()
-        .map(|(cap, refcount)|
-             (generate_identifier(cap), refcount)
-        );
+    let ret = match &function.body {
+        Body::Call { function: func, argument, continuation } => {
+            let next = continuation.as_ref().map(|cont| if cont.captures.is_empty() {
+                format!("Cont::mk({}, cont)", generate_closure(cont, program, function, &mut ictx))
+            } else {
+                format!("Cont::mk<{}>({}, {}, cont)",
+                    cont.captures.len(),
+                    generate_function_name(program.function(cont.function).name),
+                    generate_captures(&cont.captures, function, &mut ictx)
+                )
+            });
+            let callee = known_function(func, known);
+            let func = generate_value(func, program, function, &mut ictx);
+            let arg = generate_value(argument, program, function, &mut ictx);
+            let cont = next.unwrap_or_else(|| String::from("cont"));
+
+            match callee {
+                Some(id) if target.uses_trampoline() => format!("    return Lambda::jump({}, {}, {}, {});\n",
+                    generate_function_name(program.function(id).name), arg, func, cont
+                ),
+                Some(id) => format!("    return {}({}, {}, {});\n",
+                    generate_function_name(program.function(id).name), arg, func, cont
+                ),
+                None => format!("    return {}->call({}, {});\n", func, arg, cont)
+            }
+        }
+        Body::Return(value) => {
+            let arg = generate_value(value, program, function, &mut ictx);
 
-    let anon_cap = ictx.anonymous_references.into_iter()
-        .map(|(cap, refcount)|
-            (generate_anonymous_identifier(cap), refcount)
-        );
+            format!("    return cont->call({});\n", arg)
+        }
+    };
 
-    for (capture, refcount) in cap.chain(anon_cap) {
-        if refcount > 0 {
-            res += &format!("    Lambda* {} = self->captures[{}]->ref({});\n",
-                capture, i, refcount
+    for (i, refcount) in ictx.capture_references.iter().enumerate() {
+        if *refcount > 0 {
+            res += &format!("    Lambda* {} = self->captures[{}];\n",
+                generate_local(function.captures[i]), i
             );
         }
-
-        i += 1;
     }
 
     if ictx.arg_references == 0 {
@@ -314,131 +350,95 @@ fn generate_implementation<'i>(imp: Implementation<'i, '_>, actx: &mut Assignmen
         res += &format!("    {}->ref({});\n", arg_name, ictx.arg_references - 1);
     }
 
-    for (capture, refcount) in ictx.global_references.into_iter() {
-        if refcount > 0 {
-            res += &format!("    {}->ref({});\n",
-                generate_identifier(capture), refcount
-            );
-        }
+    // closures without captures are immortal constants, others hand their
+    // references to the captures over when they are dropped
+    if !function.captures.is_empty() {
+        let uses: Vec<_> = ictx.capture_references.iter()
+            .map(|refcount| refcount.to_string())
+            .collect();
 
-        i += 1;
+        res += &format!("    self->drop<{}>({{{}}});\n", uses.len(), uses.join(", "));
     }
 
-    res += "    self->unref();\n";
+    res += &ret;
+    res + "}\n"
+}
 
-    if let Some(func) = func {
-        let cont = next.unwrap_or_else(|| String::from("cont"));
+fn generate_definition<'i>(target: &CPlusPlusCPS, program: &flat::Program<'i>, def: &flat::Definition<'i>, known: &mut KnownFunctions<'i>) -> String {
+    let mut res = String::from("\n");
 
-        res += &format!("    return {}->call({}, {});\n",
-            func, arg, cont
-        );
-    } else {
-        res += &format!("    return cont->call({});\n", arg);
+    for &id in def.functions.iter() {
+        res += &format!("{}\n", generate_implementation(target, program, known, id));
     }
 
-    res += "}\n";
-
-    actx.add_impl(res);
-}
+    let value = match &def.value {
+        Initializer::Value(Value::Variable(var)) => match var {
+            Variable::Global(ident) => generate_identifier(ident),
+            _ => unreachable!("local variable in global scope")
+        },
+        Initializer::Value(Value::Closure(closure)) => format!("Lambda::constant<{}>()",
+            generate_function_name(program.function(closure.function).name)
+        ),
+        Initializer::Call(id) => format!("Lambda::constant<{}>()->ret()->immortal()",
+            generate_function_name(program.function(*id).name)
+        )
+    };
 
-fn generate_implementation_chain<'i>(chain: ImplementationChain<'i, '_>, actx: &mut AssignmentContext<'i>, ictx: &mut ImplementationContext<'i>) -> String {
-    let first_arg_name = match chain.arg_name {
-        None => ArgName::Unnamed,
-        Some(ident) => ArgName::Identifier(ident)
+    let callee = match &def.value {
+        Initializer::Value(value) => known_function(value, known),
+        Initializer::Call(_) => None
     };
-    let mut next = None;
-
-    if chain.continuations.is_empty() {
-        generate_implementation(Implementation {
-            id: 0,
-            arg_name: first_arg_name,
-            function: None,
-            argument: &chain.result_literal,
-            captures: chain.captures,
-            anonymous_captures: &BTreeSet::new(),
-            next
-        }, actx);
-    } else {
-        for cont in chain.continuations.iter().rev() {
-            let arg_name = if cont.id == 0 {
-                first_arg_name
-            } else {
-                ArgName::Anonymous(cont.id - 1)
-            };
-
-            generate_implementation(Implementation {
-                id: cont.id,
-                arg_name,
-                function: Some(&cont.function),
-                argument: &cont.argument,
-                captures: &cont.captures,
-                anonymous_captures: &cont.anonymous_captures,
-                next
-            }, actx);
-
-            next = Some(cont);
-        }
-    }
 
-    let lambda_name = generate_cont_identifier(actx.cur_assignment, actx.cur_lambda_id, 0);
-    let n = chain.captures.len();
+    match callee {
+        Some(id) => known.insert(def.target, id),
+        None => known.remove(def.target)
+    };
 
-    format!("Lambda::mk<{}>({}, {})", n, lambda_name, generate_captures(chain.captures, &BTreeSet::new(), ictx))
+    res + &format!("Lambda* {} = {};\n", generate_identifier(def.target), value)
 }
 
-
-fn generate_lambda<'i>(lambda: &Lambda<'i>, actx: &mut AssignmentContext<'i>, ictx: &mut ImplementationContext<'i>) -> String {
-    let mut sub_actx = AssignmentContext::new(actx.cur_assignment, Some(lambda.data.id));
-
-    let res = generate_implementation_chain(ImplementationChain {
-        arg_name: Some(lambda.argument),
-        continuations: &lambda.data.continuations,
-        result_literal: &lambda.data.result_literal,
-        captures: &lambda.data.captures
-    }, &mut sub_actx, ictx);
-
-    actx.impls.extend(sub_actx.impls);
-
-    res
+/// pools are sized for the closures of the program, each closure is
+/// allocated with as many captures as its function has
+fn pool_captures(program: &flat::Program<'_>) -> usize {
+    program.functions.iter()
+        .map(|function| function.captures.len())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_POOL_CAPTURES)
 }
 
+impl FlatCodegenTarget for CPlusPlusCPS {
+    fn generate(&self, program: &flat::Program<'_>) -> String {
+        let mut res = String::new();
 
-fn generate_assignment(ass: &Assignment<'_>) -> String {
-    let mut actx = AssignmentContext::new(ass.target, None);
-    let mut ictx = Default::default();
-
-    let target = generate_identifier(ass.target);
+        if self.uses_trampoline() {
+            res += "#define LAMBDA_TRAMPOLINE\n";
+        }
 
-    let value = if ass.data.continuations.is_empty() {
-        generate_literal(&ass.data.result_literal, &mut actx, &mut ictx)
-    } else {
-        let lambda = generate_implementation_chain(ImplementationChain {
-            arg_name: None,
-            continuations: &ass.data.continuations,
-            result_literal: &ass.data.result_literal,
-            captures: &BTreeSet::new()
-        }, &mut actx, &mut ictx);
-
-        lambda + "->ret()"
-    };
+        if self.collector == Collector::MarkSweep {
+            res += "#define LAMBDA_MARK_SWEEP\n";
+        }
 
-    let mut res = String::from("\n");
+        if self.leak_check {
+            res += "#define LAMBDA_LEAK_CHECK\n";
+        }
 
-    for imp in actx.impls {
-        res += &format!("{}\n", imp);
-    }
+        if self.allocator == Allocator::Pool {
+            res += "#define LAMBDA_POOL_ALLOCATOR\n";
+            res += &format!("#define LAMBDA_POOL_CAPTURES {}\n", pool_captures(program));
+        }
 
-    res + &format!("Lambda* {} = {};\n", target, value)
-}
+        res += CODEGEN_PRELUDE;
 
-impl CodegenTarget for CPlusPlusCPS {
-    fn generate(&self, program: &Program<'_>) -> String {
-        let mut res = String::new();
+        let mut known = KnownFunctions::new();
 
-        res += CODEGEN_PRELUDE;
+        for def in program.iter() {
+            res += &generate_definition(self, program, def, &mut known);
+        }
 
-        for ass in program.iter() {
-            res += &generate_assignment(ass);
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\nint main() {{\n    io::run({});\n}}\n", generate_identifier(entry));
         }
 
         res
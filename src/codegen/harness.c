@@ -0,0 +1,97 @@
+
+#include <stdio.h>
+
+// IO integers are identity lambdas that carry their value in data
+static Lambda* io_ident(Lambda* arg, Lambda* self, Cont* cont) {
+    lambda_unref(self);
+    return cont_call(cont, arg);
+}
+
+static Lambda* io_integer(size_t value) {
+    Lambda* l = lambda_mk(io_ident, 0, NULL);
+    l->data = value;
+    return l;
+}
+
+static Lambda io_identity = LAMBDA_CONSTANT(io_ident);
+
+static Lambda* io_succ_fn(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    size_t value = arg->data;
+    lambda_unref(arg);
+    return cont_call(cont, io_integer(value + 1));
+}
+
+static Lambda* io_pred_fn(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    size_t value = arg->data;
+    lambda_unref(arg);
+    return cont_call(cont, io_integer(value - 1));
+}
+
+static Lambda* io_true_1(Lambda* arg, Lambda* self, Cont* cont) {
+    Lambda* a = self->captures[0];
+    lambda_drop(self, (size_t[]) { 1 });
+    lambda_unref(arg);
+    return cont_call(cont, a);
+}
+
+static Lambda* io_true_0(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    return cont_call(cont, lambda_mk(io_true_1, 1, (Lambda*[]) { arg }));
+}
+
+static Lambda* io_false_1(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    return cont_call(cont, arg);
+}
+
+static Lambda io_false_1_c = LAMBDA_CONSTANT(io_false_1);
+
+static Lambda* io_false_0(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    lambda_unref(arg);
+    return cont_call(cont, &io_false_1_c);
+}
+
+static Lambda io_true = LAMBDA_CONSTANT(io_true_0);
+static Lambda io_false = LAMBDA_CONSTANT(io_false_0);
+
+static Lambda* io_iszero_fn(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    size_t value = arg->data;
+    lambda_unref(arg);
+    return cont_call(cont, value == 0 ? &io_true : &io_false);
+}
+
+static Lambda* io_getchar_fn(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    lambda_unref(arg);
+    fflush(stdout);
+    int c = getchar();
+    return cont_call(cont, io_integer(c == EOF ? 0 : (size_t) c + 1));
+}
+
+static Lambda* io_putchar_fn(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    putchar((int) arg->data - 1);
+    lambda_unref(arg);
+    return cont_call(cont, &io_identity);
+}
+
+static Lambda io_succ = LAMBDA_CONSTANT(io_succ_fn);
+static Lambda io_pred = LAMBDA_CONSTANT(io_pred_fn);
+static Lambda io_iszero = LAMBDA_CONSTANT(io_iszero_fn);
+static Lambda io_getchar = LAMBDA_CONSTANT(io_getchar_fn);
+static Lambda io_putchar = LAMBDA_CONSTANT(io_putchar_fn);
+
+void io_run(Lambda* entry) {
+    Lambda* l = lambda_ret(entry, io_integer(0));
+    l = lambda_ret(l, &io_succ);
+    l = lambda_ret(l, &io_pred);
+    l = lambda_ret(l, &io_iszero);
+    l = lambda_ret(l, &io_getchar);
+    l = lambda_ret(l, &io_putchar);
+    lambda_unref(l);
+    fflush(stdout);
+}
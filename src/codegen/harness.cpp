@@ -0,0 +1,31 @@
+
+#include <cstdio>
+
+// IO integers are identity lambdas that carry their value in data
+namespace io {
+    lambda integer(size_t value) {
+        return lambda([](lambda arg) { return arg; }, value);
+    }
+
+    void run(lambda entry) {
+        lambda succ([](lambda n) { return integer(n.data + 1); });
+        lambda pred([](lambda n) { return integer(n.data - 1); });
+        lambda iszero([](lambda n) {
+            if (n.data == 0) {
+                return lambda([](lambda a) { return lambda([=](lambda) { return a; }); });
+            }
+
+            return lambda([](lambda) { return lambda([](lambda b) { return b; }); });
+        });
+        lambda getchar([](lambda) {
+            int c = std::getchar();
+            return integer(c == EOF ? 0 : c + 1);
+        });
+        lambda putchar([](lambda n) {
+            std::putchar((int) n.data - 1);
+            return lambda([](lambda arg) { return arg; });
+        });
+
+        entry(integer(0))(succ)(pred)(iszero)(getchar)(putchar);
+    }
+}
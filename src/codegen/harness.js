@@ -0,0 +1,25 @@
+((entry, fs) => {
+    const buffer = Buffer.alloc(1);
+
+    // IO integers are native numbers, getchar returns the character + 1 or 0 on EOF
+    const getchar = () => {
+        for (;;) {
+            try {
+                return fs.readSync(0, buffer, 0, 1, null) === 0 ? 0 : buffer[0] + 1;
+            } catch (e) {
+                if (e.code === "EOF") {
+                    return 0;
+                } else if (e.code !== "EAGAIN") {
+                    throw e;
+                }
+            }
+        }
+    };
+
+    const putchar = n => {
+        fs.writeSync(1, Buffer.from([n - 1]));
+        return x => x;
+    };
+
+    entry(0)(n => n + 1)(n => n - 1)(n => n === 0 ? (a => b => a) : (a => b => b))(x => getchar())(putchar);
+})
@@ -0,0 +1,124 @@
+
+declare i32 @getchar()
+declare i32 @putchar(i32)
+declare i32 @fflush(ptr)
+
+; IO integers are identity lambdas that carry their value in data
+define internal ptr @io_ident(ptr %arg, ptr %self, ptr %cont) {
+  call void @lambda_unref(ptr %self)
+  %res = musttail call ptr @cont_call(ptr %arg, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_integer(i64 %value) {
+  %l = call ptr @lambda_mk(ptr @io_ident, i64 0)
+  %data = getelementptr %Lambda, ptr %l, i32 0, i32 2
+  store i64 %value, ptr %data
+  ret ptr %l
+}
+
+define internal i64 @io_value(ptr %l) {
+  %data = getelementptr %Lambda, ptr %l, i32 0, i32 2
+  %value = load i64, ptr %data
+  call void @lambda_unref(ptr %l)
+  ret i64 %value
+}
+
+define internal ptr @io_succ_fn(ptr %arg, ptr %self, ptr %cont) {
+  %value = call i64 @io_value(ptr %arg)
+  %succ = add i64 %value, 1
+  %res.value = call ptr @io_integer(i64 %succ)
+  %res = musttail call ptr @cont_call(ptr %res.value, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_pred_fn(ptr %arg, ptr %self, ptr %cont) {
+  %value = call i64 @io_value(ptr %arg)
+  %pred = sub i64 %value, 1
+  %res.value = call ptr @io_integer(i64 %pred)
+  %res = musttail call ptr @cont_call(ptr %res.value, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_true_1(ptr %arg, ptr %self, ptr %cont) {
+  %ap = getelementptr %Lambda, ptr %self, i32 0, i32 4, i64 0
+  %a = load ptr, ptr %ap
+  call void @lambda_drop(ptr %self, ptr @io_true_1.uses)
+  call void @lambda_unref(ptr %arg)
+  %res = musttail call ptr @cont_call(ptr %a, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+@io_true_1.uses = private constant [1 x i64] [i64 1]
+
+define internal ptr @io_true_0(ptr %arg, ptr %self, ptr %cont) {
+  %l = call ptr @lambda_mk(ptr @io_true_1, i64 1)
+  %ap = getelementptr %Lambda, ptr %l, i32 0, i32 4, i64 0
+  store ptr %arg, ptr %ap
+  %res = musttail call ptr @cont_call(ptr %l, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_false_1(ptr %arg, ptr %self, ptr %cont) {
+  %res = musttail call ptr @cont_call(ptr %arg, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_false_0(ptr %arg, ptr %self, ptr %cont) {
+  call void @lambda_unref(ptr %arg)
+  %res = musttail call ptr @cont_call(ptr @io_false_1.c, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_iszero_fn(ptr %arg, ptr %self, ptr %cont) {
+  %value = call i64 @io_value(ptr %arg)
+  %zero = icmp eq i64 %value, 0
+  %bool = select i1 %zero, ptr @io_true, ptr @io_false
+  %res = musttail call ptr @cont_call(ptr %bool, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_getchar_fn(ptr %arg, ptr %self, ptr %cont) {
+  call void @lambda_unref(ptr %arg)
+  call i32 @fflush(ptr null)
+  %c = call i32 @getchar()
+  %eof = icmp slt i32 %c, 0
+  %succ = add i32 %c, 1
+  %char = select i1 %eof, i32 0, i32 %succ
+  %value = zext i32 %char to i64
+  %res.value = call ptr @io_integer(i64 %value)
+  %res = musttail call ptr @cont_call(ptr %res.value, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @io_putchar_fn(ptr %arg, ptr %self, ptr %cont) {
+  %value = call i64 @io_value(ptr %arg)
+  %pred = sub i64 %value, 1
+  %char = trunc i64 %pred to i32
+  call i32 @putchar(i32 %char)
+  %res = musttail call ptr @cont_call(ptr @io_identity, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+@io_identity = internal global %Lambda { i64 9223372036854775807, ptr @io_ident, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_false_1.c = internal global %Lambda { i64 9223372036854775807, ptr @io_false_1, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_true = internal global %Lambda { i64 9223372036854775807, ptr @io_true_0, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_false = internal global %Lambda { i64 9223372036854775807, ptr @io_false_0, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_succ = internal global %Lambda { i64 9223372036854775807, ptr @io_succ_fn, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_pred = internal global %Lambda { i64 9223372036854775807, ptr @io_pred_fn, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_iszero = internal global %Lambda { i64 9223372036854775807, ptr @io_iszero_fn, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_getchar = internal global %Lambda { i64 9223372036854775807, ptr @io_getchar_fn, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@io_putchar = internal global %Lambda { i64 9223372036854775807, ptr @io_putchar_fn, i64 0, i64 0, [0 x ptr] zeroinitializer }
+
+define void @io_run(ptr %entry) {
+  %zero = call ptr @io_integer(i64 0)
+  %l0 = call ptr @lambda_ret(ptr %entry, ptr %zero)
+  %l1 = call ptr @lambda_ret(ptr %l0, ptr @io_succ)
+  %l2 = call ptr @lambda_ret(ptr %l1, ptr @io_pred)
+  %l3 = call ptr @lambda_ret(ptr %l2, ptr @io_iszero)
+  %l4 = call ptr @lambda_ret(ptr %l3, ptr @io_getchar)
+  %l5 = call ptr @lambda_ret(ptr %l4, ptr @io_putchar)
+  call void @lambda_unref(ptr %l5)
+  call i32 @fflush(ptr null)
+  ret void
+}
@@ -0,0 +1,24 @@
+-- IO integers are native numbers, getchar returns the character + 1 or 0 on EOF
+local function _main(entry)
+    local function getchar(x)
+        io.stdout:flush()
+        local c = io.read(1)
+        return c and string.byte(c) + 1 or 0
+    end
+
+    local function putchar(n)
+        io.write(string.char(n - 1))
+        return function (x) return x end
+    end
+
+    local function iszero(n)
+        if n == 0 then
+            return function (a) return function (b) return a end end
+        else
+            return function (a) return function (b) return b end end
+        end
+    end
+
+    entry(0)(function (n) return n + 1 end)(function (n) return n - 1 end)(iszero)(getchar)(putchar)
+    io.stdout:flush()
+end
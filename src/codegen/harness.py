@@ -0,0 +1,16 @@
+
+# IO integers are native ints, getchar returns the character + 1 or 0 on EOF
+def _main(entry):
+    import sys
+
+    def getchar(x):
+        sys.stdout.buffer.flush()
+        c = sys.stdin.buffer.read(1)
+        return c[0] + 1 if c else 0
+
+    def putchar(n):
+        sys.stdout.buffer.write((n - 1).to_bytes(1, "little"))
+        return lambda x: x
+
+    entry(0)(lambda n: n + 1)(lambda n: n - 1)(lambda n: (lambda a: lambda b: a) if n == 0 else (lambda a: lambda b: b))(getchar)(putchar)
+    sys.stdout.buffer.flush()
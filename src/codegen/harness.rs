@@ -0,0 +1,15 @@
+
+fn io_main(entry: &Lambda) {
+    use std::io::{Read, Write};
+
+    let mut input = std::io::stdin().lock().bytes();
+
+    run_io(entry, move || {
+        std::io::stdout().flush().expect("failed to write to stdout");
+        input.next().map(|c| c.expect("failed to read from stdin"))
+    }, |c| {
+        std::io::stdout().write_all(&[c]).expect("failed to write to stdout");
+    });
+
+    std::io::stdout().flush().expect("failed to write to stdout");
+}
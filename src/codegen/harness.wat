@@ -0,0 +1,88 @@
+
+  ;; IO integers are identity lambdas that carry their value in data, the
+  ;; harness uses table slots 2 to 11 and the constants from 48 to 192
+  (elem (i32.const 2) func
+    $io_ident $io_succ_fn $io_pred_fn $io_true_1 $io_true_0
+    $io_false_1 $io_false_0 $io_iszero_fn $io_getchar_fn $io_putchar_fn)
+
+  ;; identity, false_1, true, false, succ, pred, iszero, getchar and putchar
+  (data (i32.const 48)
+    "\ff\ff\ff\7f\02\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\07\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\06\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\08\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\03\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\04\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\09\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\0a\00\00\00\00\00\00\00\00\00\00\00"
+    "\ff\ff\ff\7f\0b\00\00\00\00\00\00\00\00\00\00\00")
+
+  (func $io_ident (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $lambda_unref (local.get $self))
+    (call $cont_call (local.get $cont) (local.get $arg)))
+
+  (func $io_integer (param $value i32) (result i32)
+    (local $l i32)
+    (local.set $l (call $lambda_mk (i32.const 2) (i32.const 0)))
+    (i32.store offset=8 (local.get $l) (local.get $value))
+    (local.get $l))
+
+  (func $io_value (param $l i32) (result i32)
+    (local $value i32)
+    (local.set $value (i32.load offset=8 (local.get $l)))
+    (call $lambda_unref (local.get $l))
+    (local.get $value))
+
+  (func $io_succ_fn (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $cont_call (local.get $cont)
+      (call $io_integer (i32.add (call $io_value (local.get $arg)) (i32.const 1)))))
+
+  (func $io_pred_fn (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $cont_call (local.get $cont)
+      (call $io_integer (i32.sub (call $io_value (local.get $arg)) (i32.const 1)))))
+
+  (func $io_true_1 (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (local $a i32)
+    (local.set $a (i32.load offset=16 (local.get $self)))
+    (call $lambda_ref (local.get $a) (i32.const 1))
+    (call $lambda_unref (local.get $self))
+    (call $lambda_unref (local.get $arg))
+    (call $cont_call (local.get $cont) (local.get $a)))
+
+  (func $io_true_0 (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $cont_call (local.get $cont)
+      (call $capture (call $lambda_mk (i32.const 5) (i32.const 1)) (i32.const 0) (local.get $arg))))
+
+  (func $io_false_1 (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $cont_call (local.get $cont) (local.get $arg)))
+
+  (func $io_false_0 (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $lambda_unref (local.get $arg))
+    (call $cont_call (local.get $cont) (i32.const 64)))
+
+  (func $io_iszero_fn (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $cont_call (local.get $cont)
+      (select (i32.const 80) (i32.const 96) (i32.eqz (call $io_value (local.get $arg))))))
+
+  ;; the host returns a negative value from getchar on EOF
+  (func $io_getchar_fn (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (local $c i32)
+    (call $lambda_unref (local.get $arg))
+    (local.set $c (call $getchar))
+    (call $cont_call (local.get $cont)
+      (call $io_integer
+        (select (i32.const 0) (i32.add (local.get $c) (i32.const 1)) (i32.lt_s (local.get $c) (i32.const 0))))))
+
+  (func $io_putchar_fn (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $putchar (i32.sub (call $io_value (local.get $arg)) (i32.const 1)))
+    (call $cont_call (local.get $cont) (i32.const 48)))
+
+  (func $io_run (param $entry i32)
+    (local $l i32)
+    (local.set $l (call $lambda_ret (local.get $entry) (call $io_integer (i32.const 0))))
+    (local.set $l (call $lambda_ret (local.get $l) (i32.const 112)))
+    (local.set $l (call $lambda_ret (local.get $l) (i32.const 128)))
+    (local.set $l (call $lambda_ret (local.get $l) (i32.const 144)))
+    (local.set $l (call $lambda_ret (local.get $l) (i32.const 160)))
+    (local.set $l (call $lambda_ret (local.get $l) (i32.const 176)))
+    (call $lambda_unref (local.get $l)))
@@ -0,0 +1,74 @@
+
+#include <cstdio>
+
+// IO integers are identity lambdas that carry their value in data
+namespace io {
+    Lambda* ident(Lambda* arg, Lambda* self, Cont* cont) {
+        self->unref();
+        return cont->call(arg);
+    }
+
+    Lambda* integer(size_t value) {
+        return Lambda::mk<0>(ident, {}, value);
+    }
+
+    Lambda* succ(Lambda* arg, Lambda* self, Cont* cont) {
+        size_t value = arg->data();
+        arg->unref();
+        return cont->call(integer(value + 1));
+    }
+
+    Lambda* pred(Lambda* arg, Lambda* self, Cont* cont) {
+        size_t value = arg->data();
+        arg->unref();
+        return cont->call(integer(value - 1));
+    }
+
+    Lambda* true_1(Lambda* arg, Lambda* self, Cont* cont) {
+        Lambda* a = self->captures[0];
+        self->drop<1>({1});
+        arg->unref();
+        return cont->call(a);
+    }
+
+    Lambda* true_0(Lambda* arg, Lambda* self, Cont* cont) {
+        return cont->call(Lambda::mk<1>(true_1, {arg}));
+    }
+
+    Lambda* false_1(Lambda* arg, Lambda* self, Cont* cont) {
+        return cont->call(arg);
+    }
+
+    Lambda* false_0(Lambda* arg, Lambda* self, Cont* cont) {
+        arg->unref();
+        return cont->call(Lambda::constant<false_1>());
+    }
+
+    Lambda* iszero(Lambda* arg, Lambda* self, Cont* cont) {
+        size_t value = arg->data();
+        arg->unref();
+        return cont->call(value == 0 ? Lambda::constant<true_0>() : Lambda::constant<false_0>());
+    }
+
+    Lambda* getchar(Lambda* arg, Lambda* self, Cont* cont) {
+        arg->unref();
+        int c = std::getchar();
+        return cont->call(integer(c == EOF ? 0 : c + 1));
+    }
+
+    Lambda* putchar(Lambda* arg, Lambda* self, Cont* cont) {
+        std::putchar((int) arg->data() - 1);
+        arg->unref();
+        return cont->call(Lambda::constant<ident>());
+    }
+
+    void run(Lambda* entry) {
+        Lambda* l = entry->ret(integer(0));
+        l = l->ret(Lambda::constant<succ>());
+        l = l->ret(Lambda::constant<pred>());
+        l = l->ret(Lambda::constant<iszero>());
+        l = l->ret(Lambda::constant<getchar>());
+        l = l->ret(Lambda::constant<putchar>());
+        l->unref();
+    }
+}
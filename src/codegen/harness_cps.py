@@ -0,0 +1,43 @@
+
+# IO integers are native ints, getchar returns the character + 1 or 0 on EOF
+def _main(entry):
+    import sys
+
+    def ident(x, self, cont):
+        return cont[0], x, cont[1]
+
+    def succ(n, self, cont):
+        return cont[0], n + 1, cont[1]
+
+    def pred(n, self, cont):
+        return cont[0], n - 1, cont[1]
+
+    def true_1(b, self, cont):
+        return cont[0], self[1], cont[1]
+
+    def true_0(a, self, cont):
+        return cont[0], (true_1, a), cont[1]
+
+    def false_1(b, self, cont):
+        return cont[0], b, cont[1]
+
+    def false_0(a, self, cont):
+        return cont[0], (false_1,), cont[1]
+
+    def iszero(n, self, cont):
+        return cont[0], ((true_0,) if n == 0 else (false_0,)), cont[1]
+
+    def getchar(x, self, cont):
+        sys.stdout.buffer.flush()
+        c = sys.stdin.buffer.read(1)
+        return cont[0], (c[0] + 1 if c else 0), cont[1]
+
+    def putchar(n, self, cont):
+        sys.stdout.buffer.write((n - 1).to_bytes(1, "little"))
+        return cont[0], (ident,), cont[1]
+
+    function = _run(entry, 0)
+    for primitive in (succ, pred, iszero, getchar, putchar):
+        function = _run(function, (primitive,))
+
+    sys.stdout.buffer.flush()
@@ -1,6 +1,9 @@
+use std::str::FromStr;
+
 use super::*;
 
-static RESERVED_WORDS: [&str; 64] = [
+static RESERVED_WORDS: [&str; 69] = [
+    "Buffer",
     "abstract",
     "arguments",
     "await",
@@ -22,6 +25,7 @@ static RESERVED_WORDS: [&str; 64] = [
     "enum",
     "eval",
     "export",
+    "exports",
     "extends",
     "false",
     "final",
@@ -39,13 +43,16 @@ static RESERVED_WORDS: [&str; 64] = [
     "interface",
     "let",
     "long",
+    "module",
     "native",
     "new",
     "null",
     "package",
     "private",
+    "process",
     "protected",
     "public",
+    "require",
     "return",
     "short",
     "static",
@@ -67,15 +74,48 @@ static RESERVED_WORDS: [&str; 64] = [
     "yield"
 ];
 
-#[derive(Debug, Clone, Copy)]
-pub struct JavaScript;
+static CODEGEN_HARNESS: &str = include_str!("harness.js");
+
+/// how the definitions are made available to other code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModuleFormat {
+    /// a plain script without exports
+    #[default]
+    Script,
+    /// an ES module with export declarations
+    Esm,
+    /// a CommonJS module assigning module.exports
+    CommonJs
+}
+
+impl FromStr for ModuleFormat {
+    type Err = &'static str;
+    fn from_str(module: &str) -> Result<ModuleFormat, Self::Err> {
+        match module {
+            "script" | "none" => Ok(ModuleFormat::Script),
+            "esm" | "es" => Ok(ModuleFormat::Esm),
+            "commonjs" | "cjs" => Ok(ModuleFormat::CommonJs),
+            _ => Err("unsupported module format")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JavaScript {
+    pub module: ModuleFormat,
+    /// definitions exported by a module, all of them if empty
+    pub exports: Vec<String>,
+    /// run this definition with IO primitives on stdin and stdout when the
+    /// file is executed by node
+    pub executable: Option<String>
+}
 
 fn generate_identifier(ident: Identifier<'_>) -> String {
     util::generate_identifier(ident, &RESERVED_WORDS)
 }
 
 fn generate_lambda(lambda: &Lambda<'_>) -> String {
-    format!("{} => {}", generate_identifier(&lambda.argument), generate_application(&lambda.body))
+    format!("{} => {}", generate_identifier(lambda.argument), generate_application(&lambda.body))
 }
 
 fn generate_expression(expr: &Expression<'_>) -> String {
@@ -102,7 +142,74 @@ fn generate_application(app: &Application<'_>) -> String {
 }
 
 fn generate_assignment(ass: &Assignment<'_>) -> String {
-    format!("const {} = {};", generate_identifier(&ass.target), generate_application(&ass.value))
+    format!("const {} = {};", generate_identifier(ass.target), generate_application(&ass.value))
+}
+
+/// names that do not start with a digit can be exported without quotes
+fn generate_export_name(name: &str) -> String {
+    if util::is_numeric(name) {
+        format!("\"{}\"", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn generate_exports(exports: &[&str], module: ModuleFormat) -> String {
+    let names: Vec<_> = exports.iter()
+        .map(|&name| (generate_identifier(name), name))
+        .collect();
+
+    match module {
+        ModuleFormat::Script => String::new(),
+        ModuleFormat::Esm => {
+            let specifiers: Vec<_> = names.iter()
+                .map(|(ident, name)| if ident == name {
+                    ident.clone()
+                } else {
+                    format!("{} as {}", ident, generate_export_name(name))
+                })
+                .collect();
+
+            format!("export {{ {} }};\n", specifiers.join(", "))
+        }
+        ModuleFormat::CommonJs => {
+            let properties: Vec<_> = names.iter()
+                .map(|(ident, name)| if ident == name {
+                    ident.clone()
+                } else {
+                    format!("\"{}\": {}", name, ident)
+                })
+                .collect();
+
+            format!("module.exports = {{ {} }};\n", properties.join(", "))
+        }
+    }
+}
+
+/// the IO harness only runs when the file is the program node was started
+/// with, so that modules can still be imported
+fn generate_harness(entry: &str, module: ModuleFormat) -> String {
+    let (condition, fs) = match module {
+        ModuleFormat::Script => (None, "require(\"fs\")"),
+        ModuleFormat::Esm => (
+            Some("process.argv[1] !== undefined && import.meta.url === (await import(\"url\")).pathToFileURL(process.argv[1]).href"),
+            "await import(\"fs\")"
+        ),
+        ModuleFormat::CommonJs => (Some("require.main === module"), "require(\"fs\")")
+    };
+
+    let call = format!("{}({}, {});\n", CODEGEN_HARNESS.trim_end(), generate_identifier(entry), fs);
+
+    match condition {
+        None => call,
+        Some(condition) => {
+            let body: Vec<_> = call.lines()
+                .map(|line| if line.is_empty() { String::new() } else { format!("    {}", line) })
+                .collect();
+
+            format!("if ({}) {{\n{}\n}}\n", condition, body.join("\n"))
+        }
+    }
 }
 
 impl CodegenTarget for JavaScript {
@@ -113,6 +220,21 @@ impl CodegenTarget for JavaScript {
             res += &format!("{}\n", generate_assignment(ass));
         }
 
+        let mut exports: Vec<&str> = if self.exports.is_empty() {
+            program.iter().map(|ass| ass.target).collect()
+        } else {
+            self.exports.iter().map(|name| name.as_str()).collect()
+        };
+
+        let mut seen = std::collections::BTreeSet::new();
+        exports.retain(|name| seen.insert(*name));
+
+        res += &generate_exports(&exports, self.module);
+
+        if let Some(entry) = &self.executable {
+            res += &generate_harness(entry, self.module);
+        }
+
         res
     }
 }
@@ -0,0 +1,307 @@
+use super::*;
+use crate::analyze::closure_conversion as flat;
+use flat::Local;
+use flat::Variable;
+use flat::Value;
+use flat::Body;
+use flat::Initializer;
+
+/// the names of the runtime and of the C library functions it declares
+static RESERVED_WORDS: [&str; 48] = [
+    "abort",
+    "Cont",
+    "cont",
+    "cont_call",
+    "cont_mk",
+    "fflush",
+    "free",
+    "getchar",
+    "io_false",
+    "io_false_0",
+    "io_false_1",
+    "io_getchar",
+    "io_getchar_fn",
+    "io_ident",
+    "io_identity",
+    "io_integer",
+    "io_iszero",
+    "io_iszero_fn",
+    "io_pred",
+    "io_pred_fn",
+    "io_putchar",
+    "io_putchar_fn",
+    "io_run",
+    "io_succ",
+    "io_succ_fn",
+    "io_true",
+    "io_true_0",
+    "io_true_1",
+    "io_value",
+    "Lambda",
+    "lambda_alloc",
+    "lambda_call",
+    "lambda_drop",
+    "lambda_immortal",
+    "lambda_init",
+    "lambda_mk",
+    "lambda_nop",
+    "lambda_nop_fn",
+    "lambda_ref",
+    "lambda_ret",
+    "lambda_return",
+    "lambda_return_fn",
+    "lambda_unref",
+    "main",
+    "malloc",
+    "putchar",
+    "realloc",
+    "self"
+];
+
+static CODEGEN_PRELUDE: &str = include_str!("prelude.ll");
+static CODEGEN_HARNESS: &str = include_str!("harness.ll");
+
+/// emits textual LLVM IR with opaque pointers, implementations pass control
+/// on with musttail calls so the native stack does not grow
+#[derive(Debug, Clone, Default)]
+pub struct Llvm {
+    /// emit a main() that passes the IO primitives to this definition
+    pub executable: Option<String>
+}
+
+/// counts the references an implementation passes on and collects the
+/// instructions that compute its values
+#[derive(Debug, Clone, Default)]
+struct ImplementationContext {
+    arg_references: usize,
+    capture_references: Vec<usize>,
+    temporaries: usize,
+    code: String
+}
+
+impl ImplementationContext {
+    fn new(function: &flat::Function<'_>) -> Self {
+        ImplementationContext {
+            capture_references: vec![0; function.captures.len()],
+            ..Default::default()
+        }
+    }
+
+    fn reference(&mut self, var: Variable<'_>) {
+        match var {
+            Variable::Argument => self.arg_references += 1,
+            Variable::Capture(i) => self.capture_references[i] += 1,
+            Variable::Global(_) => {}
+        }
+    }
+
+    /// temporaries contain a dot, so they never clash with named locals
+    fn temporary(&mut self) -> String {
+        self.temporaries += 1;
+        format!("%.{}", self.temporaries - 1)
+    }
+
+    fn emit(&mut self, instruction: String) {
+        self.code += &format!("  {}\n", instruction);
+    }
+}
+
+fn generate_identifier(ident: Identifier<'_>) -> String {
+    util::generate_suffix_identifier(ident, &RESERVED_WORDS, None)
+}
+
+fn generate_function_name(name: flat::FunctionName<'_>) -> String {
+    let lambda_id = name.lambda.map(|i| i + 1).unwrap_or(0);
+    util::generate_suffix_identifier(
+        name.assignment, &RESERVED_WORDS,
+        Some(format!("_{}_{}", lambda_id, name.continuation))
+    )
+}
+
+fn generate_local(local: Local<'_>) -> String {
+    match local {
+        Local::Unnamed => util::generate_suffix_identifier(
+            "arg", &RESERVED_WORDS, Some(String::new())
+        ),
+        Local::Temporary(id) => util::generate_suffix_identifier(
+            "ret", &RESERVED_WORDS,
+            Some(format!("_{}", id))
+        ),
+        Local::Identifier(ident) => generate_identifier(ident)
+    }
+}
+
+fn generate_global_load(ident: Identifier<'_>, ictx: &mut ImplementationContext) -> String {
+    let temp = ictx.temporary();
+    ictx.emit(format!("{} = load ptr, ptr @{}", temp, generate_identifier(ident)));
+    temp
+}
+
+fn generate_variable<'i>(var: Variable<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    ictx.reference(var);
+
+    match var {
+        Variable::Argument => format!("%{}", generate_local(function.argument)),
+        Variable::Capture(i) => format!("%{}", generate_local(function.captures[i])),
+        Variable::Global(ident) => generate_global_load(ident, ictx)
+    }
+}
+
+/// closures without captures are statically allocated constants
+fn generate_closure<'i>(closure: &flat::Closure<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    let name = generate_function_name(program.function(closure.function).name);
+
+    if closure.captures.is_empty() {
+        return format!("@{}.c", name);
+    }
+
+    let captures: Vec<_> = closure.captures.iter()
+        .map(|&var| generate_variable(var, function, ictx))
+        .collect();
+
+    let lambda = ictx.temporary();
+    ictx.emit(format!("{} = call ptr @lambda_mk(ptr @{}, i64 {})", lambda, name, captures.len()));
+
+    for (i, capture) in captures.iter().enumerate() {
+        let slot = ictx.temporary();
+        ictx.emit(format!("{} = getelementptr %Lambda, ptr {}, i32 0, i32 4, i64 {}", slot, lambda, i));
+        ictx.emit(format!("store ptr {}, ptr {}", capture, slot));
+    }
+
+    lambda
+}
+
+fn generate_value<'i>(value: &Value<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    match value {
+        Value::Variable(var) => generate_variable(*var, function, ictx),
+        Value::Closure(closure) => generate_closure(closure, program, function, ictx)
+    }
+}
+
+fn generate_constant(name: &str) -> String {
+    format!("@{0}.c = internal global %Lambda {{ i64 9223372036854775807, ptr @{0}, i64 0, i64 0, [0 x ptr] zeroinitializer }}\n", name)
+}
+
+fn generate_implementation(program: &flat::Program<'_>, id: usize) -> String {
+    let function = program.function(id);
+    let name = generate_function_name(function.name);
+    let arg_name = generate_local(function.argument);
+
+    let mut ictx = ImplementationContext::new(function);
+
+    let call = match &function.body {
+        Body::Call { function: func, argument, continuation } => {
+            let func = generate_value(func, program, function, &mut ictx);
+            let arg = generate_value(argument, program, function, &mut ictx);
+            let cont = match continuation {
+                Some(cont) => {
+                    let lambda = generate_closure(cont, program, function, &mut ictx);
+                    let temp = ictx.temporary();
+                    ictx.emit(format!("{} = call ptr @cont_mk(ptr {}, ptr %cont)", temp, lambda));
+                    temp
+                }
+                None => String::from("%cont")
+            };
+
+            format!("@lambda_call(ptr {}, ptr {}, ptr {})", arg, func, cont)
+        }
+        Body::Return(value) => {
+            let arg = generate_value(value, program, function, &mut ictx);
+
+            format!("@cont_call(ptr {}, ptr null, ptr %cont)", arg)
+        }
+    };
+
+    let mut res = format!("define ptr @{}(ptr %{}, ptr %self, ptr %cont) {{\n", name, arg_name);
+
+    // captures are loaded before self is dropped
+    for (i, refcount) in ictx.capture_references.iter().enumerate() {
+        if *refcount > 0 {
+            let local = generate_local(function.captures[i]);
+            res += &format!("  %{0}.p = getelementptr %Lambda, ptr %self, i32 0, i32 4, i64 {1}\n", local, i);
+            res += &format!("  %{0} = load ptr, ptr %{0}.p\n", local);
+        }
+    }
+
+    if ictx.arg_references == 0 {
+        res += &format!("  call void @lambda_unref(ptr %{})\n", arg_name);
+    } else if ictx.arg_references > 1 {
+        res += &format!("  call ptr @lambda_ref(ptr %{}, i64 {})\n", arg_name, ictx.arg_references - 1);
+    }
+
+    // closures without captures are immortal constants, others hand their
+    // references to the captures over when they are dropped
+    let mut globals = String::new();
+    if function.captures.is_empty() {
+        globals += &generate_constant(&name);
+    } else {
+        let uses: Vec<_> = ictx.capture_references.iter()
+            .map(|refcount| format!("i64 {}", refcount))
+            .collect();
+
+        res += &format!("  call void @lambda_drop(ptr %self, ptr @{}.uses)\n", name);
+        globals += &format!("@{}.uses = private constant [{} x i64] [{}]\n", name, uses.len(), uses.join(", "));
+    }
+
+    let result = ictx.temporary();
+    res += &ictx.code;
+    res += &format!("  {} = musttail call ptr {}\n", result, call);
+    res += &format!("  ret ptr {}\n}}\n", result);
+
+    res + &globals
+}
+
+fn generate_definition<'i>(program: &flat::Program<'i>, def: &flat::Definition<'i>, init: &mut ImplementationContext) -> String {
+    let mut res = String::from("\n");
+
+    for &id in def.functions.iter() {
+        res += &format!("{}\n", generate_implementation(program, id));
+    }
+
+    let value = match &def.value {
+        Initializer::Value(Value::Variable(var)) => match var {
+            Variable::Global(ident) => generate_global_load(ident, init),
+            _ => unreachable!("local variable in global scope")
+        },
+        Initializer::Value(Value::Closure(closure)) => format!("@{}.c",
+            generate_function_name(program.function(closure.function).name)
+        ),
+        Initializer::Call(id) => {
+            let result = init.temporary();
+            init.emit(format!("{} = call ptr @lambda_ret(ptr @{}.c, ptr null)",
+                result, generate_function_name(program.function(*id).name)
+            ));
+
+            let immortal = init.temporary();
+            init.emit(format!("{} = call ptr @lambda_immortal(ptr {})", immortal, result));
+            immortal
+        }
+    };
+
+    let target = generate_identifier(def.target);
+    init.emit(format!("store ptr {}, ptr @{}", value, target));
+
+    res + &format!("@{} = global ptr null\n", target)
+}
+
+impl FlatCodegenTarget for Llvm {
+    fn generate(&self, program: &flat::Program<'_>) -> String {
+        let mut res = String::from(CODEGEN_PRELUDE);
+        let mut init = ImplementationContext::default();
+
+        for def in program.iter() {
+            res += &generate_definition(program, def, &mut init);
+        }
+
+        res += &format!("\ndefine void @lambda_init() {{\n{}  ret void\n}}\n", init.code);
+
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\ndefine i32 @main() {{\n  call void @lambda_init()\n  %entry = load ptr, ptr @{}\n  call void @io_run(ptr %entry)\n  ret i32 0\n}}\n",
+                generate_identifier(entry)
+            );
+        }
+
+        res
+    }
+}
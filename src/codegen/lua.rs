@@ -1,6 +1,8 @@
+use std::collections::HashSet;
+
 use super::*;
 
-static RESERVED_WORDS: [&str; 21] = [
+static RESERVED_WORDS: [&str; 22] = [
     "and",
     "break",
     "do",
@@ -10,6 +12,7 @@ static RESERVED_WORDS: [&str; 21] = [
     "false",
     "for",
     "function",
+    "goto",
     "if",
     "in",
     "local",
@@ -24,51 +27,132 @@ static RESERVED_WORDS: [&str; 21] = [
     "while"
 ];
 
-#[derive(Debug, Clone, Copy)]
-pub struct Lua;
+static CODEGEN_HARNESS: &str = include_str!("harness.lua");
+
+/// lua allows 200 locals per function, the rest of the registers are left
+/// for evaluating expressions
+const MAX_LOCALS: usize = 180;
+
+/// definitions past the local limit are stored in this table instead
+const DEFINITIONS_TABLE: &str = "_defs";
+
+/// emits a chunk that keeps its definitions local and returns a module table
+#[derive(Debug, Clone, Default)]
+pub struct Lua {
+    /// definitions in the module table, all of them if empty
+    pub exports: Vec<String>,
+    /// run this definition with IO primitives on stdin and stdout when the
+    /// chunk is run as a script
+    pub executable: Option<String>
+}
+
+/// resolves identifiers to lambda arguments, locals or table entries
+struct Scope<'i> {
+    tabled: HashSet<Identifier<'i>>,
+    arguments: Vec<Identifier<'i>>
+}
 
 fn generate_identifier(ident: Identifier<'_>) -> String {
     util::generate_identifier(ident, &RESERVED_WORDS)
 }
 
-fn generate_lambda(lambda: &Lambda<'_>) -> String {
-    format!("function ({}) return {} end", generate_identifier(&lambda.argument), generate_application(&lambda.body))
+fn generate_reference<'i>(ident: Identifier<'i>, scope: &Scope<'i>) -> String {
+    if !scope.arguments.contains(&ident) && scope.tabled.contains(ident) {
+        format!("{}.{}", DEFINITIONS_TABLE, generate_identifier(ident))
+    } else {
+        generate_identifier(ident)
+    }
+}
+
+fn generate_lambda<'i>(lambda: &Lambda<'i>, scope: &mut Scope<'i>) -> String {
+    scope.arguments.push(lambda.argument);
+    let body = generate_application(&lambda.body, scope);
+    scope.arguments.pop();
+
+    format!("function ({}) return {} end", generate_identifier(lambda.argument), body)
 }
 
-fn generate_expression(expr: &Expression<'_>) -> String {
+fn generate_expression<'i>(expr: &Expression<'i>, scope: &mut Scope<'i>) -> String {
     match expr {
-        Expression::Identifier(ident) => generate_identifier(ident),
-        Expression::Parenthesis(app) => format!("({})", generate_application(app)),
-        Expression::Lambda(lambda) => generate_lambda(lambda)
+        Expression::Identifier(ident) => generate_reference(ident, scope),
+        Expression::Parenthesis(app) => format!("({})", generate_application(app, scope)),
+        Expression::Lambda(lambda) => generate_lambda(lambda, scope)
     }
 }
 
-fn generate_application(app: &Application<'_>) -> String {
+fn generate_application<'i>(app: &Application<'i>, scope: &mut Scope<'i>) -> String {
     let mut iter = app.iter();
     let mut res = String::new();
 
     if let Some(expr) = iter.next() {
-        res += &generate_expression(expr);
+        res += &generate_expression(expr, scope);
     }
 
     for expr in iter {
-        res += &format!("({})", generate_expression(expr));
+        res += &format!("({})", generate_expression(expr, scope));
     }
 
     res
 }
 
-fn generate_assignment(ass: &Assignment<'_>) -> String {
-    format!("{} = {}", generate_identifier(&ass.target), generate_application(&ass.value))
+fn generate_assignment<'i>(ass: &Assignment<'i>, scope: &mut Scope<'i>) -> String {
+    let value = generate_application(&ass.value, scope);
+
+    if scope.tabled.contains(ass.target) {
+        format!("{} = {}", generate_reference(ass.target, scope), value)
+    } else {
+        format!("local {} = {}", generate_identifier(ass.target), value)
+    }
+}
+
+/// keys that are not valid names are written as strings
+fn generate_key(name: &str) -> String {
+    if generate_identifier(name) == name {
+        name.to_string()
+    } else {
+        format!("[\"{}\"]", name)
+    }
 }
 
 impl CodegenTarget for Lua {
     fn generate(&self, program: &Program<'_>) -> String {
         let mut res = String::new();
 
+        let mut scope = Scope {
+            tabled: program.iter().skip(MAX_LOCALS).map(|ass| ass.target).collect(),
+            arguments: Vec::new()
+        };
+
+        // the harness is defined before any definition can shadow the standard library
+        if self.executable.is_some() {
+            res += CODEGEN_HARNESS;
+        }
+
+        if !scope.tabled.is_empty() {
+            res += &format!("local {} = {{}}\n", DEFINITIONS_TABLE);
+        }
+
         for ass in program.iter() {
-            res += &format!("{}\n", generate_assignment(ass));
+            res += &format!("{}\n", generate_assignment(ass, &mut scope));
+        }
+
+        // require passes the module name to the chunk, a script run without
+        // arguments gets none
+        if let Some(entry) = &self.executable {
+            res += &format!("if (...) == nil then\n    _main({})\nend\n", generate_reference(entry, &scope));
+        }
+
+        let exports: Vec<&str> = if self.exports.is_empty() {
+            program.iter().map(|ass| ass.target).collect()
+        } else {
+            self.exports.iter().map(|name| name.as_str()).collect()
+        };
+
+        res += "return {\n";
+        for name in exports.iter() {
+            res += &format!("    {} = {},\n", generate_key(name), generate_reference(name, &scope));
         }
+        res += "}\n";
 
         res
     }
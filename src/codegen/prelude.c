@@ -0,0 +1,223 @@
+#include <stddef.h>
+#include <stdlib.h>
+
+typedef struct Lambda Lambda;
+typedef struct Cont Cont;
+typedef Lambda* LambdaFn(Lambda* arg, Lambda* self, Cont* cont);
+
+// immortal lambdas start above this, uncounted unrefs never bring them to zero
+#define LAMBDA_IMMORTAL ((size_t) -1 / 2)
+
+// lambdas without captures are statically allocated and immortal
+#define LAMBDA_CONSTANT(f) { LAMBDA_IMMORTAL, f, 0, 0 }
+
+struct Lambda {
+    size_t refcount;
+    LambdaFn* function;
+    size_t data;
+    size_t length;
+    Lambda* captures[];
+};
+
+struct Cont {
+    Cont* continuation;
+    Lambda* lambda;
+};
+
+typedef struct {
+    LambdaFn* function;
+    Lambda* arg;
+    Lambda* self;
+    Cont* cont;
+} LambdaCall;
+
+static LambdaCall lambda_next_call;
+
+static void* lambda_alloc(size_t size) {
+    void* p = malloc(size);
+
+    if (p == NULL) {
+        abort();
+    }
+
+    return p;
+}
+
+Lambda* lambda_mk(LambdaFn* f, size_t length, Lambda* const* captures) {
+    Lambda* l = lambda_alloc(sizeof (Lambda) + length * sizeof (Lambda*));
+    l->refcount = 1;
+    l->function = f;
+    l->data = 0;
+    l->length = length;
+
+    for (size_t i = 0; i < length; i++) {
+        l->captures[i] = captures[i];
+    }
+
+    return l;
+}
+
+Lambda* lambda_ref(Lambda* l, size_t n) {
+    l->refcount += n;
+    return l;
+}
+
+// lambdas whose captures still have to be released, a worklist keeps
+// freeing long chains of captures off the native stack
+static Lambda** lambda_dead;
+static size_t lambda_dead_size;
+
+static void lambda_dead_reserve(size_t length) {
+    if (length > lambda_dead_size) {
+        lambda_dead_size = 2 * length;
+        Lambda** grown = realloc(lambda_dead, lambda_dead_size * sizeof (Lambda*));
+
+        if (grown == NULL) {
+            abort();
+        }
+
+        lambda_dead = grown;
+    }
+}
+
+void lambda_unref(Lambda* l) {
+    if (--l->refcount) {
+        return;
+    }
+
+    size_t length = 0;
+    lambda_dead_reserve(1);
+    lambda_dead[length++] = l;
+
+    while (length > 0) {
+        Lambda* next = lambda_dead[--length];
+        lambda_dead_reserve(length + next->length);
+
+        for (size_t i = 0; i < next->length; i++) {
+            if (!--next->captures[i]->refcount) {
+                lambda_dead[length++] = next->captures[i];
+            }
+        }
+
+        free(next);
+    }
+}
+
+// drops self after taking uses[i] references to each capture, the
+// references of the last owner are moved instead of counted again
+void lambda_drop(Lambda* self, const size_t* uses) {
+    if (self->refcount == 1) {
+        for (size_t i = 0; i < self->length; i++) {
+            if (uses[i] == 0) {
+                lambda_unref(self->captures[i]);
+            } else if (uses[i] > 1) {
+                lambda_ref(self->captures[i], uses[i] - 1);
+            }
+        }
+
+        free(self);
+    } else {
+        self->refcount--;
+
+        for (size_t i = 0; i < self->length; i++) {
+            if (uses[i] > 0) {
+                lambda_ref(self->captures[i], uses[i]);
+            }
+        }
+    }
+}
+
+// immortal lambdas are never freed, references to them are not counted,
+// everything they capture lives as long as they do
+Lambda* lambda_immortal(Lambda* l) {
+    size_t size = 16;
+    size_t length = 0;
+    Lambda** todo = lambda_alloc(size * sizeof (Lambda*));
+    todo[length++] = l;
+
+    while (length > 0) {
+        Lambda* next = todo[--length];
+
+        if (next->refcount < LAMBDA_IMMORTAL / 2) {
+            next->refcount += LAMBDA_IMMORTAL;
+
+            if (length + next->length > size) {
+                size = 2 * (length + next->length);
+                Lambda** grown = realloc(todo, size * sizeof (Lambda*));
+
+                if (grown == NULL) {
+                    abort();
+                }
+
+                todo = grown;
+            }
+
+            for (size_t i = 0; i < next->length; i++) {
+                todo[length++] = next->captures[i];
+            }
+        }
+    }
+
+    free(todo);
+    return l;
+}
+
+// implementations return the next call instead of making it, the driver
+// loop in lambda_ret makes the calls so the native stack does not grow
+Lambda* lambda_jump(LambdaFn* f, Lambda* arg, Lambda* self, Cont* cont) {
+    lambda_next_call.function = f;
+    lambda_next_call.arg = arg;
+    lambda_next_call.self = self;
+    lambda_next_call.cont = cont;
+    return NULL;
+}
+
+Lambda* lambda_call(Lambda* l, Lambda* arg, Cont* cont) {
+    return lambda_jump(l->function, arg, l, cont);
+}
+
+Cont* cont_mk(Lambda* l, Cont* cc) {
+    Cont* c = lambda_alloc(sizeof (Cont));
+    c->continuation = cc;
+    c->lambda = l;
+    return c;
+}
+
+// continuations are consumed exactly once, so they are freed when called
+Lambda* cont_call(Cont* c, Lambda* arg) {
+    Lambda* l = c->lambda;
+    Cont* cc = c->continuation;
+    free(c);
+    return lambda_call(l, arg, cc);
+}
+
+static Lambda* lambda_nop_fn(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    return cont_call(cont, arg);
+}
+
+static Lambda* lambda_return_fn(Lambda* arg, Lambda* self, Cont* cont) {
+    (void) self;
+    (void) cont;
+    return arg;
+}
+
+static Lambda lambda_nop = LAMBDA_CONSTANT(lambda_nop_fn);
+static Lambda lambda_return = LAMBDA_CONSTANT(lambda_return_fn);
+
+// calls l with arg and runs until it returns, a missing arg is passed as
+// the identity
+Lambda* lambda_ret(Lambda* l, Lambda* arg) {
+    if (arg == NULL) {
+        arg = &lambda_nop;
+    }
+
+    Lambda* res = lambda_call(l, arg, cont_mk(&lambda_return, NULL));
+
+    while (res == NULL) {
+        LambdaCall call = lambda_next_call;
+        res = call.function(call.arg, call.self, call.cont);
+    }
+
+    return res;
+}
@@ -1,11 +1,15 @@
+#include <cstddef>
 #include <functional>
 
 class lambda {
     std::function<lambda(lambda)> function;
 
 public:
+    // a native value passed in by the host, such as an IO integer
+    size_t data;
+
     template <typename F>
-    lambda(F f) : function(f) {}
+    lambda(F f, size_t value = 0) : function(f), data(value) {}
 
     lambda operator()(lambda arg) const {
         return function(arg);
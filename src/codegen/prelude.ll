@@ -0,0 +1,265 @@
+; lambdas hold their refcount, implementation, data and captures, immortal
+; lambdas start at a refcount of 2^63 - 1 so uncounted unrefs never free them
+%Lambda = type { i64, ptr, i64, i64, [0 x ptr] }
+
+; continuations hold the continuation they return to and the lambda to resume
+%Cont = type { ptr, ptr }
+
+declare ptr @malloc(i64)
+declare ptr @realloc(ptr, i64)
+declare void @free(ptr)
+declare void @abort()
+
+define internal ptr @lambda_alloc(i64 %size) {
+entry:
+  %p = call ptr @malloc(i64 %size)
+  %failed = icmp eq ptr %p, null
+  br i1 %failed, label %fail, label %done
+
+fail:
+  call void @abort()
+  unreachable
+
+done:
+  ret ptr %p
+}
+
+define ptr @lambda_mk(ptr %f, i64 %length) {
+  %end = getelementptr %Lambda, ptr null, i32 0, i32 4, i64 %length
+  %size = ptrtoint ptr %end to i64
+  %l = call ptr @lambda_alloc(i64 %size)
+  store i64 1, ptr %l
+  %function = getelementptr %Lambda, ptr %l, i32 0, i32 1
+  store ptr %f, ptr %function
+  %data = getelementptr %Lambda, ptr %l, i32 0, i32 2
+  store i64 0, ptr %data
+  %len = getelementptr %Lambda, ptr %l, i32 0, i32 3
+  store i64 %length, ptr %len
+  ret ptr %l
+}
+
+define ptr @lambda_ref(ptr %l, i64 %n) {
+  %refcount = load i64, ptr %l
+  %new = add i64 %refcount, %n
+  store i64 %new, ptr %l
+  ret ptr %l
+}
+
+define void @lambda_unref(ptr %l) {
+entry:
+  %refcount = load i64, ptr %l
+  %new = sub i64 %refcount, 1
+  store i64 %new, ptr %l
+  %dead = icmp eq i64 %new, 0
+  br i1 %dead, label %release, label %done
+
+release:
+  %len = getelementptr %Lambda, ptr %l, i32 0, i32 3
+  %length = load i64, ptr %len
+  br label %loop
+
+loop:
+  %i = phi i64 [ 0, %release ], [ %i.next, %body ]
+  %more = icmp ult i64 %i, %length
+  br i1 %more, label %body, label %free
+
+body:
+  %cp = getelementptr %Lambda, ptr %l, i32 0, i32 4, i64 %i
+  %c = load ptr, ptr %cp
+  call void @lambda_unref(ptr %c)
+  %i.next = add i64 %i, 1
+  br label %loop
+
+free:
+  call void @free(ptr %l)
+  br label %done
+
+done:
+  ret void
+}
+
+; drops self after taking uses[i] references to each capture, the
+; references of the last owner are moved instead of counted again
+define void @lambda_drop(ptr %self, ptr %uses) {
+entry:
+  %refcount = load i64, ptr %self
+  %len = getelementptr %Lambda, ptr %self, i32 0, i32 3
+  %length = load i64, ptr %len
+  %last = icmp eq i64 %refcount, 1
+  br i1 %last, label %move, label %share
+
+move:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %move.next ]
+  %move.more = icmp ult i64 %i, %length
+  br i1 %move.more, label %move.body, label %move.done
+
+move.body:
+  %move.cp = getelementptr %Lambda, ptr %self, i32 0, i32 4, i64 %i
+  %move.c = load ptr, ptr %move.cp
+  %move.up = getelementptr i64, ptr %uses, i64 %i
+  %move.u = load i64, ptr %move.up
+  %unused = icmp eq i64 %move.u, 0
+  br i1 %unused, label %move.unref, label %move.ref
+
+move.unref:
+  call void @lambda_unref(ptr %move.c)
+  br label %move.next
+
+move.ref:
+  %extra = sub i64 %move.u, 1
+  call ptr @lambda_ref(ptr %move.c, i64 %extra)
+  br label %move.next
+
+move.next:
+  %i.next = add i64 %i, 1
+  br label %move
+
+move.done:
+  call void @free(ptr %self)
+  ret void
+
+share:
+  %shared = sub i64 %refcount, 1
+  store i64 %shared, ptr %self
+  br label %share.loop
+
+share.loop:
+  %j = phi i64 [ 0, %share ], [ %j.next, %share.body ]
+  %share.more = icmp ult i64 %j, %length
+  br i1 %share.more, label %share.body, label %share.done
+
+share.body:
+  %share.cp = getelementptr %Lambda, ptr %self, i32 0, i32 4, i64 %j
+  %share.c = load ptr, ptr %share.cp
+  %share.up = getelementptr i64, ptr %uses, i64 %j
+  %share.u = load i64, ptr %share.up
+  call ptr @lambda_ref(ptr %share.c, i64 %share.u)
+  %j.next = add i64 %j, 1
+  br label %share.loop
+
+share.done:
+  ret void
+}
+
+; immortal lambdas are never freed, references to them are not counted,
+; everything they capture lives as long as they do
+define ptr @lambda_immortal(ptr %l) {
+entry:
+  %initial = call ptr @lambda_alloc(i64 128)
+  store ptr %l, ptr %initial
+  br label %loop
+
+loop:
+  %todo = phi ptr [ %initial, %entry ], [ %todo, %pop ], [ %todo.next, %push.done ]
+  %size = phi i64 [ 16, %entry ], [ %size, %pop ], [ %size.next, %push.done ]
+  %length = phi i64 [ 1, %entry ], [ %popped, %pop ], [ %pushed, %push.done ]
+  %empty = icmp eq i64 %length, 0
+  br i1 %empty, label %done, label %pop
+
+pop:
+  %popped = sub i64 %length, 1
+  %np = getelementptr ptr, ptr %todo, i64 %popped
+  %next = load ptr, ptr %np
+  %refcount = load i64, ptr %next
+  %mortal = icmp ult i64 %refcount, 4611686018427387903
+  br i1 %mortal, label %mark, label %loop
+
+mark:
+  %immortal = add i64 %refcount, 9223372036854775807
+  store i64 %immortal, ptr %next
+  %len = getelementptr %Lambda, ptr %next, i32 0, i32 3
+  %captures = load i64, ptr %len
+  %needed = add i64 %popped, %captures
+  %full = icmp ugt i64 %needed, %size
+  br i1 %full, label %grow, label %push
+
+grow:
+  %grown = mul i64 %needed, 2
+  %bytes = mul i64 %grown, 8
+  %reallocated = call ptr @realloc(ptr %todo, i64 %bytes)
+  %failed = icmp eq ptr %reallocated, null
+  br i1 %failed, label %fail, label %push
+
+fail:
+  call void @abort()
+  unreachable
+
+push:
+  %todo.next = phi ptr [ %todo, %mark ], [ %reallocated, %grow ]
+  %size.next = phi i64 [ %size, %mark ], [ %grown, %grow ]
+  br label %push.loop
+
+push.loop:
+  %k = phi i64 [ 0, %push ], [ %k.next, %push.body ]
+  %more = icmp ult i64 %k, %captures
+  br i1 %more, label %push.body, label %push.done
+
+push.body:
+  %cp = getelementptr %Lambda, ptr %next, i32 0, i32 4, i64 %k
+  %c = load ptr, ptr %cp
+  %index = add i64 %popped, %k
+  %slot = getelementptr ptr, ptr %todo.next, i64 %index
+  store ptr %c, ptr %slot
+  %k.next = add i64 %k, 1
+  br label %push.loop
+
+push.done:
+  %pushed = add i64 %popped, %captures
+  br label %loop
+
+done:
+  call void @free(ptr %todo)
+  ret ptr %l
+}
+
+define ptr @cont_mk(ptr %l, ptr %cc) {
+  %end = getelementptr %Cont, ptr null, i32 1
+  %size = ptrtoint ptr %end to i64
+  %c = call ptr @lambda_alloc(i64 %size)
+  store ptr %cc, ptr %c
+  %lp = getelementptr %Cont, ptr %c, i32 0, i32 1
+  store ptr %l, ptr %lp
+  ret ptr %c
+}
+
+; every implementation has the signature of lambda_call, so calls between
+; them are guaranteed tail calls and the native stack does not grow
+define ptr @lambda_call(ptr %arg, ptr %self, ptr %cont) {
+  %fp = getelementptr %Lambda, ptr %self, i32 0, i32 1
+  %f = load ptr, ptr %fp
+  %res = musttail call ptr %f(ptr %arg, ptr %self, ptr %cont)
+  ret ptr %res
+}
+
+; continuations are consumed exactly once, so they are freed when called,
+; the unused second argument keeps the signature of lambda_call
+define ptr @cont_call(ptr %arg, ptr %unused, ptr %cont) {
+  %cc = load ptr, ptr %cont
+  %lp = getelementptr %Cont, ptr %cont, i32 0, i32 1
+  %l = load ptr, ptr %lp
+  call void @free(ptr %cont)
+  %res = musttail call ptr @lambda_call(ptr %arg, ptr %l, ptr %cc)
+  ret ptr %res
+}
+
+define internal ptr @lambda_nop_fn(ptr %arg, ptr %self, ptr %cont) {
+  %res = musttail call ptr @cont_call(ptr %arg, ptr null, ptr %cont)
+  ret ptr %res
+}
+
+define internal ptr @lambda_return_fn(ptr %arg, ptr %self, ptr %cont) {
+  ret ptr %arg
+}
+
+@lambda_nop = internal global %Lambda { i64 9223372036854775807, ptr @lambda_nop_fn, i64 0, i64 0, [0 x ptr] zeroinitializer }
+@lambda_return = internal global %Lambda { i64 9223372036854775807, ptr @lambda_return_fn, i64 0, i64 0, [0 x ptr] zeroinitializer }
+
+; calls l with arg and returns the result, a missing arg is passed as the
+; identity
+define ptr @lambda_ret(ptr %l, ptr %arg) {
+  %missing = icmp eq ptr %arg, null
+  %a = select i1 %missing, ptr @lambda_nop, ptr %arg
+  %cont = call ptr @cont_mk(ptr @lambda_return, ptr null)
+  %res = call ptr @lambda_call(ptr %a, ptr %l, ptr %cont)
+  ret ptr %res
+}
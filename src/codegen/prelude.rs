@@ -0,0 +1,138 @@
+#![allow(dead_code, non_snake_case, non_upper_case_globals)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// a lambda value, cloning it is cheap and shares the closure
+#[derive(Clone)]
+pub struct Lambda(Rc<Closure>);
+
+enum Closure {
+    Compiled(Implementation, Vec<Lambda>),
+    Native(Box<dyn Fn(Lambda) -> Lambda>),
+    /// an identity lambda that carries an integer
+    Integer(usize)
+}
+
+// implementations return the next call instead of making it, so the loop in
+// apply keeps the native stack flat
+type Implementation = fn(Lambda, &[Lambda], Cont) -> Step;
+
+// continuations are consumed exactly once, None returns from apply
+type Cont = Option<Box<Frame>>;
+
+struct Frame {
+    lambda: Lambda,
+    next: Cont
+}
+
+enum Step {
+    Call(Lambda, Lambda, Cont),
+    Return(Lambda)
+}
+
+fn lambda_call(function: Lambda, argument: Lambda, cont: Cont) -> Step {
+    Step::Call(function, argument, cont)
+}
+
+fn cont_mk(lambda: Lambda, next: Cont) -> Cont {
+    Some(Box::new(Frame { lambda, next }))
+}
+
+fn cont_return(cont: Cont, value: Lambda) -> Step {
+    match cont {
+        Some(frame) => {
+            let Frame { lambda, next } = *frame;
+            Step::Call(lambda, value, next)
+        }
+        None => Step::Return(value)
+    }
+}
+
+fn lambda_nop_fn(arg: Lambda, _: &[Lambda], cont: Cont) -> Step {
+    cont_return(cont, arg)
+}
+
+impl Lambda {
+    fn new(function: Implementation, captures: Vec<Lambda>) -> Lambda {
+        Lambda(Rc::new(Closure::Compiled(function, captures)))
+    }
+
+    /// wraps a host function, it may apply its argument to other lambdas
+    pub fn native(f: impl Fn(Lambda) -> Lambda + 'static) -> Lambda {
+        Lambda(Rc::new(Closure::Native(Box::new(f))))
+    }
+
+    pub fn identity() -> Lambda {
+        Lambda::new(lambda_nop_fn, Vec::new())
+    }
+
+    /// an identity lambda that carries value, like the integers of the IO
+    /// primitives
+    pub fn integer(value: usize) -> Lambda {
+        Lambda(Rc::new(Closure::Integer(value)))
+    }
+
+    pub fn to_integer(&self) -> Option<usize> {
+        match *self.0 {
+            Closure::Integer(value) => Some(value),
+            _ => None
+        }
+    }
+
+    /// the church booleans a -> b -> a and a -> b -> b
+    pub fn boolean(value: bool) -> Lambda {
+        if value {
+            Lambda::native(|a| Lambda::native(move |_| a.clone()))
+        } else {
+            Lambda::native(|_| Lambda::identity())
+        }
+    }
+
+    fn enter(self, arg: Lambda, cont: Cont) -> Step {
+        match &*self.0 {
+            Closure::Compiled(function, captures) => function(arg, captures, cont),
+            Closure::Native(f) => cont_return(cont, f(arg)),
+            Closure::Integer(_) => cont_return(cont, arg)
+        }
+    }
+
+    /// applies the lambda to arg and runs the call to completion
+    pub fn apply(&self, arg: Lambda) -> Lambda {
+        let mut step = self.clone().enter(arg, None);
+
+        loop {
+            step = match step {
+                Step::Call(function, argument, cont) => function.enter(argument, cont),
+                Step::Return(value) => return value
+            };
+        }
+    }
+}
+
+fn io_value(l: &Lambda) -> usize {
+    l.to_integer().expect("IO primitive applied to a lambda that is not an integer")
+}
+
+/// applies entry to zero, succ, pred, iszero, getchar and putchar, getchar
+/// returns None at the end of the input
+pub fn run_io(entry: &Lambda, getchar: impl FnMut() -> Option<u8> + 'static, putchar: impl FnMut(u8) + 'static) {
+    let getchar = RefCell::new(getchar);
+    let putchar = RefCell::new(putchar);
+
+    let primitives = vec![
+        Lambda::native(|n| Lambda::integer(io_value(&n).wrapping_add(1))),
+        Lambda::native(|n| Lambda::integer(io_value(&n).wrapping_sub(1))),
+        Lambda::native(|n| Lambda::boolean(io_value(&n) == 0)),
+        Lambda::native(move |_| {
+            let c = (getchar.borrow_mut())();
+            Lambda::integer(c.map_or(0, |c| usize::from(c) + 1))
+        }),
+        Lambda::native(move |n| {
+            (putchar.borrow_mut())(io_value(&n).wrapping_sub(1) as u8);
+            Lambda::identity()
+        })
+    ];
+
+    primitives.into_iter().fold(entry.apply(Lambda::integer(0)), |l, primitive| l.apply(primitive));
+}
@@ -0,0 +1,199 @@
+  ;; lambdas are words in linear memory holding their refcount, the table
+  ;; index of their implementation, data, the number of captures and the
+  ;; captures, continuations hold the continuation they return to and the
+  ;; lambda to resume, address 0 is never allocated
+  (type $impl (func (param i32 i32 i32) (result i32)))
+
+  (memory (export "memory") 1)
+
+  ;; implementations return 0 and leave the next call in these globals
+  ;; instead of making it, the driver loop in lambda_ret makes the calls
+  (global $next_function (mut i32) (i32.const 0))
+  (global $next_arg (mut i32) (i32.const 0))
+  (global $next_self (mut i32) (i32.const 0))
+  (global $next_cont (mut i32) (i32.const 0))
+
+  ;; immortal lambdas start at 2^31 - 1, uncounted unrefs never free them,
+  ;; lambda_nop lives at 16 and lambda_return at 32
+  (data (i32.const 16) "\ff\ff\ff\7f\00\00\00\00\00\00\00\00\00\00\00\00")
+  (data (i32.const 32) "\ff\ff\ff\7f\01\00\00\00\00\00\00\00\00\00\00\00")
+  (elem (i32.const 0) func $lambda_nop_fn $lambda_return_fn)
+
+  (func $reserve (param $end i32)
+    (local $pages i32)
+    (local.set $pages
+      (i32.sub
+        (i32.shr_u (i32.add (local.get $end) (i32.const 65535)) (i32.const 16))
+        (memory.size)))
+    (if (i32.gt_s (local.get $pages) (i32.const 0))
+      (then
+        (if (i32.eq (memory.grow (local.get $pages)) (i32.const -1))
+          (then unreachable)))))
+
+  ;; blocks are recycled through one free list per size in words, the heads
+  ;; of the lists start at 192
+  (func $alloc (param $words i32) (result i32)
+    (local $list i32)
+    (local $block i32)
+    (local.set $list (i32.add (i32.const 192) (i32.shl (local.get $words) (i32.const 2))))
+    (local.set $block (i32.load (local.get $list)))
+    (if (local.get $block)
+      (then
+        (i32.store (local.get $list) (i32.load (local.get $block)))
+        (return (local.get $block))))
+    (local.set $block (global.get $heap_top))
+    (global.set $heap_top (i32.add (local.get $block) (i32.shl (local.get $words) (i32.const 2))))
+    (call $reserve (global.get $heap_top))
+    (local.get $block))
+
+  (func $release (param $block i32) (param $words i32)
+    (local $list i32)
+    (local.set $list (i32.add (i32.const 192) (i32.shl (local.get $words) (i32.const 2))))
+    (i32.store (local.get $block) (i32.load (local.get $list)))
+    (i32.store (local.get $list) (local.get $block)))
+
+  (func $lambda_mk (param $f i32) (param $length i32) (result i32)
+    (local $l i32)
+    (local.set $l (call $alloc (i32.add (local.get $length) (i32.const 4))))
+    (i32.store (local.get $l) (i32.const 1))
+    (i32.store offset=4 (local.get $l) (local.get $f))
+    (i32.store offset=8 (local.get $l) (i32.const 0))
+    (i32.store offset=12 (local.get $l) (local.get $length))
+    (local.get $l))
+
+  (func $capture (param $l i32) (param $i i32) (param $c i32) (result i32)
+    (i32.store offset=16
+      (i32.add (local.get $l) (i32.shl (local.get $i) (i32.const 2)))
+      (local.get $c))
+    (local.get $l))
+
+  (func $lambda_ref (param $l i32) (param $n i32)
+    (i32.store (local.get $l) (i32.add (i32.load (local.get $l)) (local.get $n))))
+
+  (func $lambda_unref (param $l i32)
+    (local $i i32)
+    (i32.store (local.get $l) (i32.sub (i32.load (local.get $l)) (i32.const 1)))
+    (if (i32.eqz (i32.load (local.get $l)))
+      (then
+        (block $done
+          (loop $next
+            (br_if $done (i32.ge_u (local.get $i) (i32.load offset=12 (local.get $l))))
+            (call $lambda_unref
+              (i32.load offset=16 (i32.add (local.get $l) (i32.shl (local.get $i) (i32.const 2)))))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $next)))
+        (call $release (local.get $l) (i32.add (i32.load offset=12 (local.get $l)) (i32.const 4))))))
+
+  ;; drops self after taking uses[i] references to each capture, the
+  ;; references of the last owner are moved instead of counted again
+  (func $lambda_drop (param $self i32) (param $uses i32)
+    (local $i i32)
+    (local $length i32)
+    (local $c i32)
+    (local $n i32)
+    (local.set $length (i32.load offset=12 (local.get $self)))
+    (if (i32.eq (i32.load (local.get $self)) (i32.const 1))
+      (then
+        (block $done
+          (loop $next
+            (br_if $done (i32.ge_u (local.get $i) (local.get $length)))
+            (local.set $c
+              (i32.load offset=16 (i32.add (local.get $self) (i32.shl (local.get $i) (i32.const 2)))))
+            (local.set $n
+              (i32.load (i32.add (local.get $uses) (i32.shl (local.get $i) (i32.const 2)))))
+            (if (i32.eqz (local.get $n))
+              (then (call $lambda_unref (local.get $c)))
+              (else (call $lambda_ref (local.get $c) (i32.sub (local.get $n) (i32.const 1)))))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $next)))
+        (call $release (local.get $self) (i32.add (local.get $length) (i32.const 4))))
+      (else
+        (i32.store (local.get $self) (i32.sub (i32.load (local.get $self)) (i32.const 1)))
+        (block $done
+          (loop $next
+            (br_if $done (i32.ge_u (local.get $i) (local.get $length)))
+            (call $lambda_ref
+              (i32.load offset=16 (i32.add (local.get $self) (i32.shl (local.get $i) (i32.const 2))))
+              (i32.load (i32.add (local.get $uses) (i32.shl (local.get $i) (i32.const 2)))))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $next))))))
+
+  ;; immortal lambdas are never freed, references to them are not counted,
+  ;; everything they capture lives as long as they do, nothing is allocated
+  ;; while marking so the memory above the heap holds the lambdas left to mark
+  (func $lambda_immortal (param $l i32) (result i32)
+    (local $top i32)
+    (local $next i32)
+    (local $i i32)
+    (local.set $top (global.get $heap_top))
+    (call $reserve (i32.add (local.get $top) (i32.const 4)))
+    (i32.store (local.get $top) (local.get $l))
+    (local.set $top (i32.add (local.get $top) (i32.const 4)))
+    (block $done
+      (loop $pop
+        (br_if $done (i32.eq (local.get $top) (global.get $heap_top)))
+        (local.set $top (i32.sub (local.get $top) (i32.const 4)))
+        (local.set $next (i32.load (local.get $top)))
+        (if (i32.lt_u (i32.load (local.get $next)) (i32.const 0x3fffffff))
+          (then
+            (i32.store (local.get $next) (i32.add (i32.load (local.get $next)) (i32.const 0x7fffffff)))
+            (call $reserve
+              (i32.add (local.get $top) (i32.shl (i32.load offset=12 (local.get $next)) (i32.const 2))))
+            (local.set $i (i32.const 0))
+            (block $pushed
+              (loop $push
+                (br_if $pushed (i32.ge_u (local.get $i) (i32.load offset=12 (local.get $next))))
+                (i32.store (local.get $top)
+                  (i32.load offset=16 (i32.add (local.get $next) (i32.shl (local.get $i) (i32.const 2)))))
+                (local.set $top (i32.add (local.get $top) (i32.const 4)))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $push)))))
+        (br $pop)))
+    (local.get $l))
+
+  (func $lambda_call (param $l i32) (param $arg i32) (param $cont i32) (result i32)
+    (global.set $next_function (i32.load offset=4 (local.get $l)))
+    (global.set $next_arg (local.get $arg))
+    (global.set $next_self (local.get $l))
+    (global.set $next_cont (local.get $cont))
+    (i32.const 0))
+
+  (func $cont_mk (param $l i32) (param $cc i32) (result i32)
+    (local $c i32)
+    (local.set $c (call $alloc (i32.const 2)))
+    (i32.store (local.get $c) (local.get $cc))
+    (i32.store offset=4 (local.get $c) (local.get $l))
+    (local.get $c))
+
+  ;; continuations are consumed exactly once, so they are freed when called
+  (func $cont_call (param $c i32) (param $arg i32) (result i32)
+    (local $l i32)
+    (local $cc i32)
+    (local.set $cc (i32.load (local.get $c)))
+    (local.set $l (i32.load offset=4 (local.get $c)))
+    (call $release (local.get $c) (i32.const 2))
+    (call $lambda_call (local.get $l) (local.get $arg) (local.get $cc)))
+
+  (func $lambda_nop_fn (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (call $cont_call (local.get $cont) (local.get $arg)))
+
+  (func $lambda_return_fn (type $impl) (param $arg i32) (param $self i32) (param $cont i32) (result i32)
+    (local.get $arg))
+
+  ;; calls l with arg and runs until it returns, a missing arg is passed as
+  ;; the identity
+  (func $lambda_ret (param $l i32) (param $arg i32) (result i32)
+    (local $res i32)
+    (if (i32.eqz (local.get $arg))
+      (then (local.set $arg (i32.const 16))))
+    (local.set $res
+      (call $lambda_call (local.get $l) (local.get $arg) (call $cont_mk (i32.const 32) (i32.const 0))))
+    (block $done
+      (loop $next
+        (br_if $done (local.get $res))
+        (local.set $res
+          (call_indirect (type $impl)
+            (global.get $next_arg) (global.get $next_self) (global.get $next_cont)
+            (global.get $next_function)))
+        (br $next)))
+    (local.get $res))
@@ -1,15 +1,127 @@
 #include <cstddef>
 #include <cstdint>
 #include <array>
+#include <vector>
 #include <algorithm>
+#include <utility>
+
+#ifdef LAMBDA_LEAK_CHECK
+#include <cstdio>
+#define LAMBDA_COUNT_ALLOCATIONS
+#endif
 
 class Lambda;
 class Cont;
 typedef Lambda* LambdaFn(Lambda*, Lambda*, Cont*);
 
+#ifdef LAMBDA_POOL_ALLOCATOR
+#ifndef LAMBDA_POOL_CAPTURES
+#define LAMBDA_POOL_CAPTURES 4
+#endif
+
+// recycles continuations and lambdas with up to LAMBDA_POOL_CAPTURES captures
+// through one free list per size, the chunks they are cut from are never
+// returned to the system
+class Pool {
+    static const size_t CHUNK = 256;
+    static const size_t CLASSES = LAMBDA_POOL_CAPTURES + 5;
+
+    struct Block {
+        Block* next;
+    };
+
+    static Block* free_lists[CLASSES];
+
+public:
+    // sizes are given in words
+    static bool pooled(size_t size) {
+        return size < CLASSES;
+    }
+
+    static void* alloc(size_t size) {
+        Block*& list = free_lists[size];
+
+        if (list == nullptr) {
+            uint8_t* chunk = new uint8_t[CHUNK * size * sizeof (void*)];
+
+            for (size_t i = 0; i < CHUNK; i++) {
+                Block* block = (Block*) (chunk + i * size * sizeof (void*));
+                block->next = list;
+                list = block;
+            }
+        }
+
+        Block* block = list;
+        list = block->next;
+        return block;
+    }
+
+    static void release(void* p, size_t size) {
+        Block* block = (Block*) p;
+        block->next = free_lists[size];
+        free_lists[size] = block;
+    }
+};
+
+Pool::Block* Pool::free_lists[Pool::CLASSES] = {};
+#endif
+
+// compile with -DLAMBDA_COUNT_ALLOCATIONS to count live objects, after a call
+// returns Lambda::live and Cont::live are back to their previous values,
+// -DLAMBDA_LEAK_CHECK additionally reports objects that are alive at exit
+//
+// with -DLAMBDA_MARK_SWEEP references are not counted, lambdas are traced from
+// the immortal lambdas and the pending call of the driver loop instead, which
+// needs -DLAMBDA_TRAMPOLINE
 class Lambda {
     friend class Cont;
 
+    // immortal lambdas start above this, uncounted unrefs never bring them to zero
+    static const size_t IMMORTAL = SIZE_MAX / 2;
+
+#ifdef LAMBDA_MARK_SWEEP
+    // the refcount of a mortal lambda stays at one, a collection sets it to
+    // MARKED while the lambda is reachable
+    static constexpr size_t MARKED = 2;
+
+    // collections start once this many lambdas are tracked
+    static constexpr size_t MIN_COLLECTION = 4096;
+
+    // every mortal lambda that was alive at the last collection or has been
+    // allocated since, immortal lambdas are dropped when they are swept
+    static std::vector<Lambda*> heap;
+    static size_t next_collection;
+
+    static void mark(std::vector<Lambda*> todo) {
+        while (!todo.empty()) {
+            Lambda* l = todo.back();
+            todo.pop_back();
+
+            // immortal lambdas only capture immortal lambdas
+            if (l->refcount == 1) {
+                l->refcount = MARKED;
+                todo.insert(todo.end(), l->captures, l->captures + l->length);
+            }
+        }
+    }
+
+    static void sweep() {
+        size_t kept = 0;
+
+        for (Lambda* l : heap) {
+            if (l->refcount == MARKED) {
+                l->refcount = 1;
+                heap[kept++] = l;
+            } else if (l->refcount == 1) {
+                l->destroy();
+            }
+        }
+
+        heap.resize(kept);
+        next_collection = std::max(2 * kept, MIN_COLLECTION);
+    }
+#endif
+
     size_t refcount;
     LambdaFn* function;
 
@@ -23,13 +135,62 @@ class Lambda {
         data() = value;
     }
 
+    // size of a lambda in words, including its data
+    static size_t words(size_t length) {
+        return (sizeof (Lambda) + (length + 1) * sizeof (Lambda*)) / sizeof (void*);
+    }
+
+    void destroy() {
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+        live--;
+#endif
+#ifdef LAMBDA_POOL_ALLOCATOR
+        if (Pool::pooled(words(length))) {
+            Pool::release(this, words(length));
+            return;
+        }
+#endif
+        delete[] (uint8_t*) this;
+    }
+
+#ifdef LAMBDA_TRAMPOLINE
+    struct Call {
+        LambdaFn* function;
+        Lambda* arg;
+        Lambda* self;
+        Cont* cont;
+    };
+
+    static Call next_call;
+#endif
+
     static Lambda* nop_lambda(Lambda* arg, Lambda* self, Cont* cont);
     static Lambda* ret_lambda(Lambda* arg, Lambda* self, Cont* cont);
 
 public:
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+    static size_t live;
+#endif
+
     size_t length;
     Lambda* captures[];
 
+#ifdef LAMBDA_MARK_SWEEP
+    Lambda* ref(size_t = 1) {
+        return this;
+    }
+
+    void unref() {}
+
+    template <size_t N>
+    void drop(std::array<size_t, N>) {}
+
+    // frees every mortal lambda that cannot be reached from the roots
+    static void collect(std::vector<Lambda*> roots) {
+        mark(std::move(roots));
+        sweep();
+    }
+#else
     Lambda* ref(size_t n = 1) {
         refcount += n;
         return this;
@@ -41,28 +202,120 @@ public:
                 captures[i]->unref();
             }
 
-            delete[] (uint8_t*) this;
+            destroy();
         }
     }
 
+    // drops self after taking uses[i] references to each capture, the
+    // references of the last owner are moved instead of counted again
+    template <size_t N>
+    void drop(std::array<size_t, N> uses) {
+        if (refcount == 1) {
+            for (size_t i = 0; i < N; i++) {
+                if (uses[i] == 0) {
+                    captures[i]->unref();
+                } else if (uses[i] > 1) {
+                    captures[i]->ref(uses[i] - 1);
+                }
+            }
+
+            destroy();
+        } else {
+            refcount--;
+
+            for (size_t i = 0; i < N; i++) {
+                if (uses[i] > 0) {
+                    captures[i]->ref(uses[i]);
+                }
+            }
+        }
+    }
+#endif
+
+    // immortal lambdas are never freed, references to them are not counted,
+    // everything they capture lives as long as they do
+    Lambda* immortal() {
+        std::vector<Lambda*> todo{this};
+
+        while (!todo.empty()) {
+            Lambda* l = todo.back();
+            todo.pop_back();
+
+            if (l->refcount < IMMORTAL / 2) {
+                l->refcount += IMMORTAL;
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+                live--;
+#endif
+                todo.insert(todo.end(), l->captures, l->captures + l->length);
+            }
+        }
+
+        return this;
+    }
+
     size_t& data() {
         return (size_t&) captures[length];
     }
 
     Lambda* call(Lambda* arg, Cont* cont) {
-        return function(arg, this, cont);
+        return jump(function, arg, this, cont);
+    }
+
+#ifdef LAMBDA_TRAMPOLINE
+    // implementations return the next call instead of making it, the driver
+    // loop in ret makes the calls so the native stack does not grow
+    static Lambda* jump(LambdaFn* f, Lambda* arg, Lambda* self, Cont* cont) {
+        next_call = Call{f, arg, self, cont};
+        return nullptr;
     }
+#else
+    static Lambda* jump(LambdaFn* f, Lambda* arg, Lambda* self, Cont* cont) {
+        return f(arg, self, cont);
+    }
+#endif
 
     Lambda* ret(Lambda* arg = nullptr);
 
     template <size_t N>
     static Lambda* mk(LambdaFn* f, std::array<Lambda*, N> captures, size_t value = 0) {
-        uint8_t * buf = new uint8_t[sizeof (Lambda) + (N + 1) * sizeof (Lambda*)];
-        return new (buf) Lambda(f, captures, value);
+#ifdef LAMBDA_POOL_ALLOCATOR
+        uint8_t * buf = Pool::pooled(words(N))
+            ? (uint8_t*) Pool::alloc(words(N))
+            : new uint8_t[words(N) * sizeof (void*)];
+#else
+        uint8_t * buf = new uint8_t[words(N) * sizeof (void*)];
+#endif
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+        live++;
+#endif
+        Lambda* l = new (buf) Lambda(f, captures, value);
+#ifdef LAMBDA_MARK_SWEEP
+        heap.push_back(l);
+#endif
+        return l;
+    }
+
+    // lambdas without captures are shared and immortal
+    template <LambdaFn* F>
+    static Lambda* constant() {
+        static Lambda* lambda = Lambda::mk<0>(F, {})->immortal();
+        return lambda;
     }
 };
 
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+size_t Lambda::live = 0;
+#endif
+
+#ifdef LAMBDA_MARK_SWEEP
+std::vector<Lambda*> Lambda::heap;
+size_t Lambda::next_collection = Lambda::MIN_COLLECTION;
+#endif
+
 class Cont {
+    // the driver loop finds the roots of a collection in the continuations
+    friend class Lambda;
+
     Cont* continuation;
     Lambda* lambda;
 
@@ -72,20 +325,75 @@ class Cont {
         lambda(Lambda::mk(f, cs))
     {}
 
+    Cont(Lambda* l, Cont* cc) :
+        continuation(cc),
+        lambda(l)
+    {}
+
+#ifdef LAMBDA_POOL_ALLOCATOR
+    // continuations are consumed exactly once, so they go back to the pool
+    // as soon as they are called
+    static void* operator new(size_t size) {
+        return Pool::alloc(size / sizeof (void*));
+    }
+
+    static void operator delete(void* p) {
+        Pool::release(p, sizeof (Cont) / sizeof (void*));
+    }
+#endif
+
 public:
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+    static size_t live;
+#endif
+
     Lambda* call(Lambda* arg) {
         Lambda* l = lambda;
         Cont* c = continuation;
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+        live--;
+#endif
         delete this;
         return l->call(arg, c);
     }
 
     template <size_t N>
     static Cont* mk(LambdaFn* f, std::array<Lambda*, N> cs, Cont* cc) {
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+        live++;
+#endif
         return new Cont(f, cs, cc);
     }
+
+    static Cont* mk(Lambda* l, Cont* cc) {
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+        live++;
+#endif
+        return new Cont(l, cc);
+    }
 };
 
+#ifdef LAMBDA_COUNT_ALLOCATIONS
+size_t Cont::live = 0;
+#endif
+
+#ifdef LAMBDA_LEAK_CHECK
+// lambdas are immutable and only capture older objects, so they cannot form
+// cycles and anything still alive at exit was leaked, the mark-sweep collector
+// runs once more without roots first
+struct LeakCheck {
+    ~LeakCheck() {
+#ifdef LAMBDA_MARK_SWEEP
+        Lambda::collect({});
+#endif
+
+        if (Lambda::live != 0 || Cont::live != 0) {
+            fprintf(stderr, "leak check: %zu lambdas and %zu continuations still alive\n", Lambda::live, Cont::live);
+        }
+    }
+} leak_check;
+#endif
+
 Lambda* Lambda::nop_lambda(Lambda* arg, Lambda* self, Cont* cont) {
     self->unref();
     return cont->call(arg);
@@ -98,9 +406,36 @@ Lambda* Lambda::ret_lambda(Lambda* arg, Lambda* self, Cont* cont) {
 
 Lambda* Lambda::ret(Lambda* arg) {
     if (arg == nullptr) {
-        arg = Lambda::mk<0>(Lambda::nop_lambda, {});
+        arg = Lambda::constant<Lambda::nop_lambda>();
+    }
+
+    Lambda* res = this->call(arg, Cont::mk(Lambda::constant<Lambda::ret_lambda>(), nullptr));
+
+#ifdef LAMBDA_TRAMPOLINE
+    while (res == nullptr) {
+#ifdef LAMBDA_MARK_SWEEP
+        // between two calls everything that is alive can be reached from the
+        // pending call, its continuations or the immortal lambdas
+        if (heap.size() >= next_collection) {
+            std::vector<Lambda*> roots{next_call.arg, next_call.self};
+
+            for (Cont* c = next_call.cont; c != nullptr; c = c->continuation) {
+                roots.push_back(c->lambda);
+            }
+
+            collect(std::move(roots));
+        }
+#endif
+
+        Call call = next_call;
+        res = call.function(call.arg, call.self, call.cont);
     }
+#endif
 
-    return this->call(arg, Cont::mk<0>(Lambda::ret_lambda, {}, nullptr));
+    return res;
 }
 
+#ifdef LAMBDA_TRAMPOLINE
+Lambda::Call Lambda::next_call;
+#endif
+
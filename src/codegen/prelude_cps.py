@@ -0,0 +1,14 @@
+# closures are tuples of an implementation and its captures, continuations
+# are pairs of the closure to resume and the continuation it returns to
+
+# implementations return the next call as a tuple of function, argument and
+# continuation instead of making it, so the python stack never grows
+_halt = (None,)
+
+def _run(function, argument):
+    cont = (_halt, None)
+
+    while function is not _halt:
+        function, argument, cont = function[0](argument, function, cont)
+
+    return argument
@@ -37,15 +37,21 @@ static RESERVED_WORDS: [&str; 34] = [
     "yield"
 ];
 
-#[derive(Debug, Clone, Copy)]
-pub struct Python;
+static CODEGEN_HARNESS: &str = include_str!("harness.py");
+
+#[derive(Debug, Clone, Default)]
+pub struct Python {
+    /// run this definition with IO primitives on stdin and stdout when the
+    /// module is executed as a script
+    pub executable: Option<String>
+}
 
 fn generate_identifier(ident: Identifier<'_>) -> String {
     util::generate_identifier(ident, &RESERVED_WORDS)
 }
 
 fn generate_lambda(lambda: &Lambda<'_>) -> String {
-    format!("lambda {}: {}", generate_identifier(&lambda.argument), generate_application(&lambda.body))
+    format!("lambda {}: {}", generate_identifier(lambda.argument), generate_application(&lambda.body))
 }
 
 fn generate_expression(expr: &Expression<'_>) -> String {
@@ -72,7 +78,7 @@ fn generate_application(app: &Application<'_>) -> String {
 }
 
 fn generate_assignment(ass: &Assignment<'_>) -> String {
-    format!("{} = {}", generate_identifier(&ass.target), generate_application(&ass.value))
+    format!("{} = {}", generate_identifier(ass.target), generate_application(&ass.value))
 }
 
 impl CodegenTarget for Python {
@@ -83,6 +89,11 @@ impl CodegenTarget for Python {
             res += &format!("{}\n", generate_assignment(ass));
         }
 
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\nif __name__ == \"__main__\":\n    _main({})\n", generate_identifier(entry));
+        }
+
         res
     }
 }
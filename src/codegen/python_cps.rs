@@ -0,0 +1,184 @@
+use super::*;
+use crate::analyze::closure_conversion as flat;
+use flat::Local;
+use flat::Variable;
+use flat::Value;
+use flat::Body;
+use flat::Initializer;
+
+static RESERVED_WORDS: [&str; 36] = [
+    "and",
+    "as",
+    "assert",
+    "async",
+    "break",
+    "class",
+    "cont",
+    "continue",
+    "def",
+    "del",
+    "elif",
+    "else",
+    "except",
+    "finally",
+    "False",
+    "for",
+    "from",
+    "global",
+    "if",
+    "import",
+    "in",
+    "is",
+    "lambda",
+    "None",
+    "nonlocal",
+    "not",
+    "or",
+    "pass",
+    "raise",
+    "return",
+    "self",
+    "True",
+    "try",
+    "while",
+    "with",
+    "yield"
+];
+
+static CODEGEN_PRELUDE: &str = include_str!("prelude_cps.py");
+static CODEGEN_HARNESS: &str = include_str!("harness_cps.py");
+
+/// emits python where every call returns to a driver loop, so deep
+/// recursion does not hit the recursion limit
+#[derive(Debug, Clone, Default)]
+pub struct PythonCPS {
+    /// run this definition with IO primitives on stdin and stdout when the
+    /// module is executed as a script
+    pub executable: Option<String>
+}
+
+fn generate_identifier(ident: Identifier<'_>) -> String {
+    util::generate_suffix_identifier(ident, &RESERVED_WORDS, None)
+}
+
+fn generate_function_name(name: flat::FunctionName<'_>) -> String {
+    let lambda_id = name.lambda.map(|i| i + 1).unwrap_or(0);
+    util::generate_suffix_identifier(
+        name.assignment, &RESERVED_WORDS,
+        Some(format!("_{}_{}", lambda_id, name.continuation))
+    )
+}
+
+fn generate_local(local: Local<'_>) -> String {
+    match local {
+        Local::Unnamed => String::from("_"),
+        Local::Temporary(id) => util::generate_suffix_identifier(
+            "ret", &RESERVED_WORDS,
+            Some(format!("_{}", id))
+        ),
+        Local::Identifier(ident) => generate_identifier(ident)
+    }
+}
+
+fn generate_variable<'i>(var: Variable<'i>, function: &flat::Function<'i>) -> String {
+    match var {
+        Variable::Argument => generate_local(function.argument),
+        Variable::Capture(i) => generate_local(function.captures[i]),
+        Variable::Global(ident) => generate_identifier(ident)
+    }
+}
+
+/// closures are tuples of their implementation and their captures
+fn generate_closure<'i>(closure: &flat::Closure<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>) -> String {
+    let name = generate_function_name(program.function(closure.function).name);
+
+    if closure.captures.is_empty() {
+        format!("({},)", name)
+    } else {
+        let captures: Vec<_> = closure.captures.iter()
+            .map(|&var| generate_variable(var, function))
+            .collect();
+
+        format!("({}, {})", name, captures.join(", "))
+    }
+}
+
+fn generate_value<'i>(value: &Value<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>) -> String {
+    match value {
+        Value::Variable(var) => generate_variable(*var, function),
+        Value::Closure(closure) => generate_closure(closure, program, function)
+    }
+}
+
+fn generate_implementation(program: &flat::Program<'_>, id: usize) -> String {
+    let function = program.function(id);
+
+    let mut res = format!("def {}({}, self, cont):\n",
+        generate_function_name(function.name), generate_local(function.argument)
+    );
+
+    for (i, &capture) in function.captures.iter().enumerate() {
+        res += &format!("    {} = self[{}]\n", generate_local(capture), i + 1);
+    }
+
+    match &function.body {
+        Body::Call { function: func, argument, continuation } => {
+            let cont = match continuation {
+                Some(cont) => format!("({}, cont)", generate_closure(cont, program, function)),
+                None => String::from("cont")
+            };
+
+            res += &format!("    return {}, {}, {}\n",
+                generate_value(func, program, function),
+                generate_value(argument, program, function),
+                cont
+            );
+        }
+        Body::Return(value) => {
+            res += &format!("    return cont[0], {}, cont[1]\n", generate_value(value, program, function));
+        }
+    }
+
+    res
+}
+
+fn generate_definition<'i>(program: &flat::Program<'i>, def: &flat::Definition<'i>) -> String {
+    let mut res = String::from("\n");
+
+    for &id in def.functions.iter() {
+        res += &format!("{}\n", generate_implementation(program, id));
+    }
+
+    // closures at the top level only reference globals and capture nothing
+    let value = match &def.value {
+        Initializer::Value(Value::Variable(var)) => match var {
+            Variable::Global(ident) => generate_identifier(ident),
+            _ => unreachable!("local variable in global scope")
+        },
+        Initializer::Value(Value::Closure(closure)) => format!("({},)",
+            generate_function_name(program.function(closure.function).name)
+        ),
+        Initializer::Call(id) => format!("_run(({},), None)",
+            generate_function_name(program.function(*id).name)
+        )
+    };
+
+    res + &format!("{} = {}\n", generate_identifier(def.target), value)
+}
+
+impl FlatCodegenTarget for PythonCPS {
+    fn generate(&self, program: &flat::Program<'_>) -> String {
+        let mut res = String::from(CODEGEN_PRELUDE);
+
+        for def in program.iter() {
+            res += &generate_definition(program, def);
+        }
+
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\nif __name__ == \"__main__\":\n    _main({})\n", generate_identifier(entry));
+        }
+
+        res
+    }
+}
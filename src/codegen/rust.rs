@@ -0,0 +1,289 @@
+use super::*;
+use crate::analyze::closure_conversion as flat;
+use flat::Local;
+use flat::Variable;
+use flat::Value;
+use flat::Body;
+use flat::Initializer;
+
+/// rust keywords, the names of the runtime and the items of the standard
+/// prelude it uses
+static RESERVED_WORDS: [&str; 81] = [
+    "abstract",
+    "as",
+    "async",
+    "await",
+    "become",
+    "Box",
+    "box",
+    "break",
+    "captures",
+    "Closure",
+    "const",
+    "Cont",
+    "cont",
+    "cont_mk",
+    "cont_return",
+    "continue",
+    "core",
+    "crate",
+    "do",
+    "dyn",
+    "else",
+    "enum",
+    "Err",
+    "extern",
+    "false",
+    "final",
+    "fn",
+    "for",
+    "Frame",
+    "gen",
+    "if",
+    "impl",
+    "Implementation",
+    "in",
+    "io_main",
+    "io_value",
+    "Lambda",
+    "lambda_call",
+    "lambda_nop_fn",
+    "let",
+    "loop",
+    "macro",
+    "main",
+    "match",
+    "mod",
+    "move",
+    "mut",
+    "None",
+    "Ok",
+    "Option",
+    "override",
+    "priv",
+    "pub",
+    "Rc",
+    "ref",
+    "RefCell",
+    "Result",
+    "return",
+    "run_io",
+    "Self",
+    "self",
+    "Some",
+    "static",
+    "std",
+    "Step",
+    "String",
+    "struct",
+    "super",
+    "trait",
+    "true",
+    "try",
+    "type",
+    "typeof",
+    "unsafe",
+    "unsized",
+    "use",
+    "Vec",
+    "virtual",
+    "where",
+    "while",
+    "yield"
+];
+
+static CODEGEN_PRELUDE: &str = include_str!("prelude.rs");
+static CODEGEN_HARNESS: &str = include_str!("harness.rs");
+
+/// emits a rust module where lambdas are reference counted closures and
+/// every call returns to a driver loop, definitions become functions that
+/// return their value and are initialized on first use
+#[derive(Debug, Clone, Default)]
+pub struct Rust {
+    /// definitions that are public, all of them if empty
+    pub exports: Vec<String>,
+    /// emit a main() that passes the IO primitives to this definition
+    pub executable: Option<String>
+}
+
+/// moves the argument into its last use and clones it everywhere else
+#[derive(Debug, Clone, Default)]
+struct ImplementationContext {
+    arg_references: usize
+}
+
+impl ImplementationContext {
+    fn new(function: &flat::Function<'_>) -> Self {
+        let values: Vec<&Value<'_>> = match &function.body {
+            Body::Call { function, argument, .. } => vec![function, argument],
+            Body::Return(value) => vec![value]
+        };
+
+        let mut variables: Vec<Variable<'_>> = values.into_iter()
+            .flat_map(|value| match value {
+                Value::Variable(var) => vec![*var],
+                Value::Closure(closure) => closure.captures.clone()
+            })
+            .collect();
+
+        if let Body::Call { continuation: Some(cont), .. } = &function.body {
+            variables.extend(cont.captures.iter().copied());
+        }
+
+        ImplementationContext {
+            arg_references: variables.iter().filter(|&&var| var == Variable::Argument).count()
+        }
+    }
+}
+
+fn generate_identifier(ident: Identifier<'_>) -> String {
+    util::generate_suffix_identifier(ident, &RESERVED_WORDS, None)
+}
+
+fn generate_function_name(name: flat::FunctionName<'_>) -> String {
+    let lambda_id = name.lambda.map(|i| i + 1).unwrap_or(0);
+    util::generate_suffix_identifier(
+        name.assignment, &RESERVED_WORDS,
+        Some(format!("_{}_{}", lambda_id, name.continuation))
+    )
+}
+
+/// the thread local that holds the value of a definition
+fn generate_global_name(ident: Identifier<'_>) -> String {
+    util::generate_suffix_identifier(ident, &RESERVED_WORDS, Some(String::from("_g")))
+}
+
+fn generate_local(local: Local<'_>) -> String {
+    match local {
+        Local::Unnamed => util::generate_suffix_identifier(
+            "arg", &RESERVED_WORDS, Some(String::new())
+        ),
+        Local::Temporary(id) => util::generate_suffix_identifier(
+            "ret", &RESERVED_WORDS,
+            Some(format!("_{}", id))
+        ),
+        Local::Identifier(ident) => generate_identifier(ident)
+    }
+}
+
+fn generate_variable<'i>(var: Variable<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    match var {
+        Variable::Argument => {
+            ictx.arg_references -= 1;
+
+            if ictx.arg_references == 0 {
+                generate_local(function.argument)
+            } else {
+                format!("{}.clone()", generate_local(function.argument))
+            }
+        }
+        Variable::Capture(i) => format!("captures[{}].clone()", i),
+        Variable::Global(ident) => format!("{}()", generate_identifier(ident))
+    }
+}
+
+fn generate_closure<'i>(closure: &flat::Closure<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    let name = generate_function_name(program.function(closure.function).name);
+
+    if closure.captures.is_empty() {
+        format!("Lambda::new({}, Vec::new())", name)
+    } else {
+        let captures: Vec<_> = closure.captures.iter()
+            .map(|&var| generate_variable(var, function, ictx))
+            .collect();
+
+        format!("Lambda::new({}, vec![{}])", name, captures.join(", "))
+    }
+}
+
+fn generate_value<'i>(value: &Value<'i>, program: &flat::Program<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    match value {
+        Value::Variable(var) => generate_variable(*var, function, ictx),
+        Value::Closure(closure) => generate_closure(closure, program, function, ictx)
+    }
+}
+
+fn generate_implementation(program: &flat::Program<'_>, id: usize) -> String {
+    let function = program.function(id);
+    let mut ictx = ImplementationContext::new(function);
+
+    let arg_name = if ictx.arg_references == 0 {
+        String::from("_")
+    } else {
+        generate_local(function.argument)
+    };
+    let captures_name = if function.captures.is_empty() { "_" } else { "captures" };
+
+    let mut res = format!("fn {}({}: Lambda, {}: &[Lambda], cont: Cont) -> Step {{\n",
+        generate_function_name(function.name), arg_name, captures_name
+    );
+
+    // arguments are generated in the order rust evaluates them, so the
+    // argument is moved by the last expression that uses it
+    match &function.body {
+        Body::Call { function: func, argument, continuation } => {
+            let func = generate_value(func, program, function, &mut ictx);
+            let arg = generate_value(argument, program, function, &mut ictx);
+            let cont = match continuation {
+                Some(cont) => format!("cont_mk({}, cont)", generate_closure(cont, program, function, &mut ictx)),
+                None => String::from("cont")
+            };
+
+            res += &format!("    lambda_call({}, {}, {})\n", func, arg, cont);
+        }
+        Body::Return(value) => {
+            res += &format!("    cont_return(cont, {})\n", generate_value(value, program, function, &mut ictx));
+        }
+    }
+
+    res + "}\n"
+}
+
+fn generate_definition<'i>(program: &flat::Program<'i>, def: &flat::Definition<'i>, public: bool) -> String {
+    let mut res = String::from("\n");
+
+    for &id in def.functions.iter() {
+        res += &format!("{}\n", generate_implementation(program, id));
+    }
+
+    // closures at the top level only reference globals and capture nothing
+    let value = match &def.value {
+        Initializer::Value(Value::Variable(var)) => match var {
+            Variable::Global(ident) => format!("{}()", generate_identifier(ident)),
+            _ => unreachable!("local variable in global scope")
+        },
+        Initializer::Value(Value::Closure(closure)) => format!("Lambda::new({}, Vec::new())",
+            generate_function_name(program.function(closure.function).name)
+        ),
+        Initializer::Call(id) => format!("Lambda::new({}, Vec::new()).apply(Lambda::identity())",
+            generate_function_name(program.function(*id).name)
+        )
+    };
+
+    let global = generate_global_name(def.target);
+
+    res += &format!("thread_local! {{\n    static {}: Lambda = {};\n}}\n\n", global, value);
+    res += &format!("{}fn {}() -> Lambda {{\n    {}.with(Lambda::clone)\n}}\n",
+        if public { "pub " } else { "" }, generate_identifier(def.target), global
+    );
+
+    res
+}
+
+impl FlatCodegenTarget for Rust {
+    fn generate(&self, program: &flat::Program<'_>) -> String {
+        let mut res = String::from(CODEGEN_PRELUDE);
+
+        for def in program.iter() {
+            let public = self.exports.is_empty() || self.exports.iter().any(|name| name == def.target);
+            res += &generate_definition(program, def, public);
+        }
+
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\nfn main() {{\n    io_main(&{}());\n}}\n", generate_identifier(entry));
+        }
+
+        res
+    }
+}
@@ -0,0 +1,742 @@
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+use super::*;
+use crate::parser::LambdaParser;
+use crate::optimize::OptimizationOptions;
+use crate::pass::Ir;
+use crate::pass::PassManager;
+
+fn generate(target: &dyn CodegenTarget, code: &str) -> String {
+    let program = LambdaParser::parse_program(code).unwrap();
+    target.generate(&analyze_program(&program).program)
+}
+
+fn generate_flat(target: &dyn FlatCodegenTarget, code: &str) -> String {
+    let mut pm = PassManager::analysis();
+    pm.add_flat_passes();
+
+    match pm.run(Ir::Source(LambdaParser::parse_program(code).unwrap())).unwrap().ir {
+        Ir::Flat(program) => target.generate(&program),
+        ir => panic!("unexpected {} IR", ir.stage())
+    }
+}
+
+fn count_implementations(code: &str) -> usize {
+    code.lines()
+        .filter(|line| line.starts_with("Lambda* ") && !line.contains("::") && line.ends_with("Cont* cont) {"))
+        .count()
+}
+
+/// checks that a program of a toolchain can be started, when it cannot the
+/// test is skipped with a message that bypasses the output capture, or fails
+/// if LAMBDA_REQUIRE_TOOLCHAINS is set
+fn toolchain(program: &str) -> bool {
+    if Command::new(program).arg("--version").stdin(Stdio::null()).output().is_ok() {
+        return true;
+    }
+
+    assert!(std::env::var_os("LAMBDA_REQUIRE_TOOLCHAINS").is_none(), "{} not found", program);
+    writeln!(std::io::stderr(), "warning: {} not found, skipping the test", program).unwrap();
+
+    false
+}
+
+/// runs a program with the given input and returns its output and what it
+/// wrote to stderr
+fn run_with_stderr(program: &str, args: &[&str], input: &str) -> (String, String) {
+    let mut child = Command::new(program).args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn().unwrap();
+    // written from a thread so that neither side blocks on a full pipe, a
+    // program that does not read its input may exit before it is written
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.as_bytes().to_vec();
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(output.status.success(), "{} failed to run {:?}:\n{}", program, args, stderr);
+
+    (String::from_utf8(output.stdout).unwrap(), stderr)
+}
+
+/// runs a program with the given input and returns its output
+fn run(program: &str, args: &[&str], input: &str) -> String {
+    run_with_stderr(program, args, input).0
+}
+
+/// a fresh directory for the files of a test
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lambda-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+const EXAMPLES: [&str; 2] = [include_str!("../../examples/hello.lambda"), include_str!("../../examples/test.lambda")];
+
+/// what every example prints when it is run with the IO primitives
+const GREETING: &str = "Hello\nWorld\n";
+
+/// the examples at the given optimization levels, ending in flat IR if
+/// `flat` is set
+fn examples(levels: &[u32], flat: bool) -> Vec<Ir<'static>> {
+    let mut res = Vec::new();
+
+    for code in EXAMPLES.iter() {
+        for &level in levels.iter() {
+            let mut pm = PassManager::new();
+            pm.add_check_passes();
+            pm.add_optimization_passes(&OptimizationOptions { level, ..Default::default() });
+            pm.add_analysis_passes();
+
+            if flat {
+                pm.add_flat_passes();
+            }
+
+            res.push(pm.run(Ir::Source(LambdaParser::parse_program(code).unwrap())).unwrap().ir);
+        }
+    }
+
+    res
+}
+
+fn generate_examples(target: &dyn CodegenTarget, levels: &[u32]) -> Vec<String> {
+    examples(levels, false).iter()
+        .map(|ir| match ir {
+            Ir::ContinuationCaptures(program) => target.generate(program),
+            ir => panic!("unexpected {} IR", ir.stage())
+        })
+        .collect()
+}
+
+fn generate_examples_flat(target: &dyn FlatCodegenTarget, levels: &[u32]) -> Vec<String> {
+    examples(levels, true).iter()
+        .map(|ir| match ir {
+            Ir::Flat(program) => target.generate(program),
+            ir => panic!("unexpected {} IR", ir.stage())
+        })
+        .collect()
+}
+
+#[test]
+fn test_shared_implementations() {
+    let code = generate_flat(&CPlusPlusCPS::default(), "f = a -> a\ng = x -> x\nh = y -> z -> y\nk = b -> c -> b\n");
+
+    assert_eq!(count_implementations(&code), 3);
+    assert!(code.contains("Lambda* g = Lambda::constant<f_1_0_>();"));
+    assert!(code.contains("Lambda* k = Lambda::constant<h_1_0_>();"));
+}
+
+#[test]
+fn test_shared_continuations() {
+    let code = generate_flat(&CPlusPlusCPS::default(), "f = a -> a\ng = x -> f (f x)\nh = y -> f (f y)\n");
+
+    assert_eq!(count_implementations(&code), 3);
+    assert!(code.contains("Lambda* h = Lambda::constant<g_1_0_>();"));
+}
+
+#[test]
+fn test_distinct_implementations() {
+    // same shape, but different globals and capture positions
+    let code = generate_flat(&CPlusPlusCPS::default(), "f = a -> a\ng = a -> a\nh = x -> f x\nk = x -> g x\nl = a -> b -> a b\nm = a -> b -> b a\n");
+
+    assert_eq!(count_implementations(&code), 7);
+}
+
+#[test]
+fn test_known_calls() {
+    let code = generate_flat(&CPlusPlusCPS::default(), "f = a -> a\ng = f\nh = x -> g (y -> y) x\nk = x -> x f\n");
+
+    // g is an alias of f, and the lambda literal is called through a shared constant
+    assert!(code.contains("return f_1_0_(Lambda::constant<f_1_0_>(), g, Cont::mk<1>(h_1_1_, {x}, cont));"));
+    // arguments are unknown, so they are still called through the function pointer
+    assert!(code.contains("return x->call(f, cont);"));
+}
+
+#[test]
+fn test_refcount_elision() {
+    let code = generate_flat(&CPlusPlusCPS::default(), "f = a -> a\ng = x -> y -> f y x x\n");
+
+    // captures are moved out of self, globals and constants are never counted
+    assert!(code.contains("    Lambda* x = self->captures[0];\n    self->drop<1>({2});\n"));
+    let generated = &code[code.find("Lambda* f_1_0_").unwrap()..];
+    assert!(!generated.contains("f->ref("));
+    assert!(!generated.contains("self->unref()"));
+}
+
+#[test]
+fn test_allocator() {
+    let code = "f = a -> b -> c -> d -> a b c d\n";
+    let pool = generate_flat(&CPlusPlusCPS::default(), code);
+    let heap = generate_flat(&CPlusPlusCPS { allocator: Allocator::Heap, ..Default::default() }, code);
+
+    // the pools fit the three captures of the innermost lambda
+    assert!(pool.starts_with("#define LAMBDA_POOL_ALLOCATOR\n#define LAMBDA_POOL_CAPTURES 3\n"));
+    assert!(!heap.contains("#define LAMBDA_POOL_ALLOCATOR"));
+}
+
+#[test]
+fn test_trampoline() {
+    let code = "f = a -> a\ng = x -> f x\n";
+    let direct = generate_flat(&CPlusPlusCPS::default(), code);
+    let trampoline = generate_flat(&CPlusPlusCPS { trampoline: true, ..Default::default() }, code);
+
+    assert!(direct.contains("return f_1_0_(x, f, cont);"));
+    assert!(trampoline.starts_with("#define LAMBDA_TRAMPOLINE\n"));
+    assert!(trampoline.contains("return Lambda::jump(f_1_0_, x, f, cont);"));
+}
+
+#[test]
+fn test_leak_check() {
+    let code = "f = a -> a\n";
+    let checked = generate_flat(&CPlusPlusCPS { leak_check: true, ..Default::default() }, code);
+
+    assert!(checked.starts_with("#define LAMBDA_LEAK_CHECK\n"));
+    assert!(!generate_flat(&CPlusPlusCPS::default(), code).contains("#define LAMBDA_LEAK_CHECK"));
+}
+
+#[test]
+fn test_collector() {
+    let code = "f = a -> a\ng = x -> f x\n";
+    let collected = generate_flat(&CPlusPlusCPS { collector: Collector::MarkSweep, ..Default::default() }, code);
+
+    // the collector runs from the driver loop, so it needs the trampoline
+    assert!(collected.starts_with("#define LAMBDA_TRAMPOLINE\n#define LAMBDA_MARK_SWEEP\n"));
+    assert!(collected.contains("return Lambda::jump(f_1_0_, x, f, cont);"));
+    assert!(!generate_flat(&CPlusPlusCPS::default(), code).contains("#define LAMBDA_MARK_SWEEP"));
+}
+
+#[test]
+fn test_executable() {
+    let code = "io = a -> a\nstart = io\n";
+    let executable = Some(String::from("start"));
+
+    let direct = generate(&CPlusPlus { executable: executable.clone() }, code);
+    let cps = generate_flat(&CPlusPlusCPS { executable, ..Default::default() }, code);
+
+    for code in [direct, cps].iter() {
+        // user definitions must not collide with the harness
+        assert!(code.contains("_io = "));
+        assert!(code.contains("namespace io {"));
+        assert!(code.ends_with("\nint main() {\n    io::run(start);\n}\n"));
+    }
+
+    assert!(!generate(&CPlusPlus::default(), code).contains("int main()"));
+}
+
+/// compiles C++ sources with g++ and checks that they print the greeting
+fn run_cpp(test: &str, sources: &[String], flags: &[&str]) {
+    let dir = scratch_dir(test);
+
+    for (i, source) in sources.iter().enumerate() {
+        let file = dir.join(format!("example{}.cpp", i));
+        let binary = dir.join(format!("example{}", i));
+        std::fs::write(&file, source).unwrap();
+
+        let mut args = vec!["-std=c++17", "-o", path_str(&binary), path_str(&file)];
+        args.extend_from_slice(flags);
+
+        run("g++", &args, "");
+        assert_eq!(run(path_str(&binary), &[], ""), GREETING);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_executable_run() {
+    if !toolchain("g++") {
+        return;
+    }
+
+    // g++ takes half a minute for the nested lambdas of an optimized example
+    let sources = generate_examples(&CPlusPlus { executable: Some(String::from("start")) }, &[0]);
+    run_cpp("cpp", &sources, &["-O0"]);
+}
+
+#[test]
+fn test_cps_executable_run() {
+    if !toolchain("g++") {
+        return;
+    }
+
+    let executable = Some(String::from("start"));
+
+    // without the trampoline the stack only stays flat when g++ turns calls into jumps
+    let sources = generate_examples_flat(&CPlusPlusCPS { executable: executable.clone(), ..Default::default() }, &[0, 2]);
+    run_cpp("cpp-cps", &sources, &["-O1"]);
+
+    let sources = generate_examples_flat(&CPlusPlusCPS { executable, trampoline: true, ..Default::default() }, &[0]);
+    run_cpp("cpp-cps-trampoline", &sources, &["-O0"]);
+}
+
+/// copies stdin to stdout, allocating a few lambdas for every character
+const ECHO: &str = "\
+y = g -> (f -> f f) f -> g x -> f f x
+start = zero -> succ -> pred -> iszero -> getchar -> putchar ->
+    y (loop -> x -> (c -> iszero c (x -> x) (x -> loop (putchar c)) zero) (getchar x)) zero
+";
+
+#[test]
+fn test_mark_sweep_run() {
+    if !toolchain("g++") {
+        return;
+    }
+
+    let target = CPlusPlusCPS {
+        collector: Collector::MarkSweep,
+        leak_check: true,
+        executable: Some(String::from("start")),
+        ..Default::default()
+    };
+
+    // the echo input is long enough for many collections
+    let input = "lambda\n".repeat(20_000);
+    let mut programs: Vec<_> = generate_examples_flat(&target, &[0, 2]).into_iter()
+        .map(|source| (source, GREETING))
+        .collect();
+    programs.push((generate_flat(&target, ECHO), &input));
+
+    let dir = scratch_dir("cpp-mark-sweep");
+    for (i, (source, output)) in programs.iter().enumerate() {
+        let file = dir.join(format!("example{}.cpp", i));
+        let binary = dir.join(format!("example{}", i));
+        std::fs::write(&file, source).unwrap();
+
+        run("g++", &["-std=c++17", "-O0", "-o", path_str(&binary), path_str(&file)], "");
+
+        // the leak check reports on stderr
+        assert_eq!(run_with_stderr(path_str(&binary), &[], &input), (output.to_string(), String::new()));
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_javascript_module() {
+    let code = "do = a -> a\n1 = do\nstart = x -> 1 x\n";
+
+    let esm = generate(&JavaScript { module: ModuleFormat::Esm, ..Default::default() }, code);
+    assert!(esm.ends_with("export { _do as do, _1 as \"1\", start };\n"));
+
+    let exports = vec![String::from("1"), String::from("start")];
+    let cjs = generate(&JavaScript { module: ModuleFormat::CommonJs, exports, ..Default::default() }, code);
+    assert!(cjs.ends_with("module.exports = { \"1\": _1, start };\n"));
+
+    assert!(!generate(&JavaScript::default(), code).contains("export"));
+}
+
+#[test]
+fn test_javascript_executable() {
+    let code = "require = a -> a\nstart = require\n";
+    let executable = Some(String::from("start"));
+
+    let script = generate(&JavaScript { executable: executable.clone(), ..Default::default() }, code);
+    assert!(script.contains("const _require = "));
+    assert!(script.ends_with("})(start, require(\"fs\"));\n"));
+
+    // modules only run the harness when executed directly
+    let cjs = generate(&JavaScript { module: ModuleFormat::CommonJs, executable, ..Default::default() }, code);
+    assert!(cjs.contains("if (require.main === module) {\n"));
+    assert!(cjs.ends_with("    })(start, require(\"fs\"));\n}\n"));
+}
+
+#[test]
+fn test_javascript_run() {
+    if !toolchain("node") {
+        return;
+    }
+
+    let dir = scratch_dir("javascript");
+    let formats = [(ModuleFormat::Script, "js"), (ModuleFormat::Esm, "mjs"), (ModuleFormat::CommonJs, "cjs")];
+
+    for &(module, extension) in formats.iter() {
+        let target = JavaScript { module, executable: Some(String::from("start")), ..Default::default() };
+
+        for (i, source) in generate_examples(&target, &[0, 2]).iter().enumerate() {
+            let file = dir.join(format!("example{}.{}", i, extension));
+            std::fs::write(&file, source).unwrap();
+
+            assert_eq!(run("node", &[path_str(&file)], ""), GREETING);
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_python_cps() {
+    let code = generate_flat(&PythonCPS::default(), "k = a -> b -> a\nself = k k k\n");
+
+    // calls are returned to the driver loop instead of being made
+    assert!(code.contains("def k_2_0_(b, self, cont):\n    a = self[1]\n    return cont[0], a, cont[1]\n"));
+    assert!(code.contains("def _self_0_0_(_, self, cont):\n    return k, k, ((_self_0_1_,), cont)\n"));
+    assert!(code.ends_with("_self = _run((_self_0_0_,), None)\n"));
+}
+
+#[test]
+fn test_python_executable() {
+    let code = "start = x -> x\n";
+    let executable = Some(String::from("start"));
+
+    let direct = generate(&Python { executable: executable.clone() }, code);
+    let cps = generate_flat(&PythonCPS { executable }, code);
+
+    for code in [direct, cps].iter() {
+        assert!(code.contains("def _main(entry):\n"));
+        assert!(code.ends_with("\nif __name__ == \"__main__\":\n    _main(start)\n"));
+    }
+
+    assert!(!generate(&Python::default(), code).contains("__main__"));
+}
+
+#[test]
+fn test_python_run() {
+    if !toolchain("python3") {
+        return;
+    }
+
+    let executable = Some(String::from("start"));
+    let mut sources = generate_examples(&Python { executable: executable.clone() }, &[0, 2]);
+    sources.extend(generate_examples_flat(&PythonCPS { executable }, &[0, 2]));
+
+    let dir = scratch_dir("python");
+    for (i, source) in sources.iter().enumerate() {
+        let file = dir.join(format!("example{}.py", i));
+        std::fs::write(&file, source).unwrap();
+
+        assert_eq!(run("python3", &[path_str(&file)], ""), GREETING);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_lua_module() {
+    let code = "print = a -> a\ndo = print\n1 = x -> do x\n";
+
+    let module = generate(&Lua::default(), code);
+    assert!(module.starts_with("local print = function (a) return a end\nlocal _do = print\n"));
+    assert!(module.ends_with("return {\n    print = print,\n    [\"do\"] = _do,\n    [\"1\"] = _1,\n}\n"));
+
+    let exports = vec![String::from("1")];
+    assert!(generate(&Lua { exports, ..Default::default() }, code).ends_with("return {\n    [\"1\"] = _1,\n}\n"));
+}
+
+#[test]
+fn test_lua_local_limit() {
+    let mut code = String::from("f0 = x -> x\n");
+    for i in 1..200 {
+        code += &format!("f{} = x -> f{} x\n", i, i - 1);
+    }
+
+    let module = generate(&Lua::default(), &code);
+    assert!(module.starts_with("local _defs = {}\n"));
+    assert!(module.contains("\nlocal f179 = function (x) return f178(x) end\n"));
+    assert!(module.contains("\n_defs.f181 = function (x) return _defs.f180(x) end\n"));
+    assert_eq!(module.matches("local ").count(), 181);
+}
+
+#[test]
+fn test_lua_executable() {
+    let code = "io = x -> x\nstart = io\n";
+    let module = generate(&Lua { executable: Some(String::from("start")), ..Default::default() }, code);
+
+    // the harness is defined before definitions can shadow the standard library
+    assert!(module.starts_with("-- IO integers"));
+    assert!(module.find("local function _main(entry)") < module.find("local io = "));
+    assert!(module.contains("\nif (...) == nil then\n    _main(start)\nend\nreturn {\n"));
+}
+
+#[test]
+fn test_lua_run() {
+    // enough definitions that the entry and some of the chain live in _defs
+    let mut code = String::from("f0 = x -> x\n");
+    for i in 1..200 {
+        code += &format!("f{} = x -> f{} x\n", i, i - 1);
+    }
+    code += "Y = g -> (f -> f f) f -> g x -> f f x\n";
+    code += "start = zero -> succ -> pred -> iszero -> getchar -> putchar ->\n";
+    code += "    Y (loop -> x -> f199 (c -> iszero c (x -> x) (x -> loop (putchar c)) zero) (getchar x)) zero\n";
+
+    let module = generate(&Lua { executable: Some(String::from("start")), ..Default::default() }, &code);
+    assert!(module.contains("\n_defs.start = "));
+
+    if !toolchain("lua") {
+        return;
+    }
+
+    let dir = scratch_dir("lua");
+    std::fs::write(dir.join("echo.lua"), module).unwrap();
+
+    // run directly, the script echoes its input
+    let direct = run("lua", &[path_str(&dir.join("echo.lua"))], "hello\nworld\n");
+
+    // required as a module, it only returns its definitions
+    let script = format!(
+        "package.path = {:?} .. package.path\nlocal echo = require(\"echo\")\nio.write(echo.f199(echo.f0)(\"ok\"), \" \", type(echo.start))\n",
+        format!("{}/?.lua;", dir.display())
+    );
+    std::fs::write(dir.join("main.lua"), script).unwrap();
+    let required = run("lua", &[path_str(&dir.join("main.lua"))], "hello\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(direct, "hello\nworld\n");
+    assert_eq!(required, "ok function");
+}
+
+#[test]
+fn test_c() {
+    let code = generate_flat(&C::default(), "k = a -> b -> a\nfree = k k k\n");
+
+    // user definitions must not collide with the C library
+    assert!(code.contains("Lambda* _free;\n"));
+    assert!(code.contains("\
+Lambda* k_2_0_(Lambda* b, Lambda* self, Cont* cont) {
+    Lambda* a = self->captures[0];
+    lambda_unref(b);
+    lambda_drop(self, (size_t[]) { 1 });
+    return cont_call(cont, a);
+}
+"));
+    assert!(code.contains("Lambda* k_1_0_(Lambda* a, Lambda* self, Cont* cont) {\n    (void) self;\n"));
+    assert!(code.contains("static Lambda k_1_0_c_ = LAMBDA_CONSTANT(k_1_0_);\n"));
+    assert!(code.ends_with("\nvoid lambda_init(void) {\n    k = &k_1_0_c_;\n    _free = lambda_immortal(lambda_ret(&_free_0_0_c_, NULL));\n}\n"));
+}
+
+#[test]
+fn test_c_executable() {
+    let code = "start = x -> x\n";
+
+    let executable = generate_flat(&C { executable: Some(String::from("start")) }, code);
+    assert!(executable.contains("void io_run(Lambda* entry) {"));
+    assert!(executable.ends_with("\nint main(void) {\n    lambda_init();\n    io_run(start);\n}\n"));
+
+    assert!(!generate_flat(&C::default(), code).contains("int main"));
+}
+
+#[test]
+fn test_c_run() {
+    if !toolchain("gcc") {
+        return;
+    }
+
+    let dir = scratch_dir("c");
+    for (i, source) in generate_examples_flat(&C { executable: Some(String::from("start")) }, &[0, 2]).iter().enumerate() {
+        let file = dir.join(format!("example{}.c", i));
+        let binary = dir.join(format!("example{}", i));
+        std::fs::write(&file, source).unwrap();
+
+        run("gcc", &["-std=c99", "-pedantic", "-Wall", "-Wextra", "-Werror", "-o", path_str(&binary), path_str(&file)], "");
+        assert_eq!(run(path_str(&binary), &[], ""), GREETING);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_llvm() {
+    let code = generate_flat(&Llvm::default(), "k = a -> b -> a\nmalloc = k k k\n");
+
+    // calls between implementations are guaranteed tail calls
+    assert!(code.contains("\
+define ptr @k_2_0_(ptr %b, ptr %self, ptr %cont) {
+  %a.p = getelementptr %Lambda, ptr %self, i32 0, i32 4, i64 0
+  %a = load ptr, ptr %a.p
+  call void @lambda_unref(ptr %b)
+  call void @lambda_drop(ptr %self, ptr @k_2_0_.uses)
+  %.0 = musttail call ptr @cont_call(ptr %a, ptr null, ptr %cont)
+  ret ptr %.0
+}
+@k_2_0_.uses = private constant [1 x i64] [i64 1]
+"));
+    assert!(code.contains("@k_1_0_.c = internal global %Lambda { i64 9223372036854775807, ptr @k_1_0_, i64 0, i64 0, [0 x ptr] zeroinitializer }\n"));
+    assert!(code.ends_with("\
+define void @lambda_init() {
+  store ptr @k_1_0_.c, ptr @k
+  %.0 = call ptr @lambda_ret(ptr @_malloc_0_0_.c, ptr null)
+  %.1 = call ptr @lambda_immortal(ptr %.0)
+  store ptr %.1, ptr @_malloc
+  ret void
+}
+"));
+}
+
+#[test]
+fn test_llvm_executable() {
+    let code = "start = x -> x\n";
+
+    let executable = generate_flat(&Llvm { executable: Some(String::from("start")) }, code);
+    assert!(executable.contains("define void @io_run(ptr %entry) {"));
+    assert!(executable.ends_with("\
+define i32 @main() {
+  call void @lambda_init()
+  %entry = load ptr, ptr @start
+  call void @io_run(ptr %entry)
+  ret i32 0
+}
+"));
+
+    assert!(!generate_flat(&Llvm::default(), code).contains("@main"));
+}
+
+#[test]
+fn test_llvm_run() {
+    if !toolchain("lli") {
+        return;
+    }
+
+    // the output uses opaque pointers, which LLVM 14 does not enable by default
+    let version = run("lli", &["--version"], "");
+    let flags: &[&str] = if version.contains("LLVM version 14.") { &["-opaque-pointers"] } else { &[] };
+
+    let dir = scratch_dir("llvm");
+    for (i, source) in generate_examples_flat(&Llvm { executable: Some(String::from("start")) }, &[0, 2]).iter().enumerate() {
+        let file = dir.join(format!("example{}.ll", i));
+        std::fs::write(&file, source).unwrap();
+
+        let mut args = flags.to_vec();
+        args.push(path_str(&file));
+
+        assert_eq!(run("lli", &args, ""), GREETING);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_rust() {
+    let code = generate_flat(&Rust { exports: vec![String::from("self")], executable: None }, "k = a -> b -> a\ndup = x -> x x\nself = k k k\n");
+
+    // the argument is moved into its last use
+    assert!(code.contains("\
+fn dup_1_0_(x: Lambda, _: &[Lambda], cont: Cont) -> Step {
+    lambda_call(x.clone(), x, cont)
+}
+"));
+    assert!(code.contains("\
+fn k_2_0_(_: Lambda, captures: &[Lambda], cont: Cont) -> Step {
+    cont_return(cont, captures[0].clone())
+}
+"));
+    assert!(code.contains("\n    static _self_g_: Lambda = Lambda::new(_self_0_0_, Vec::new()).apply(Lambda::identity());\n"));
+    assert!(code.contains("\nfn k() -> Lambda {\n    k_g_.with(Lambda::clone)\n}\n"));
+    assert!(code.ends_with("\npub fn _self() -> Lambda {\n    _self_g_.with(Lambda::clone)\n}\n"));
+}
+
+#[test]
+fn test_rust_executable() {
+    let code = "start = x -> x\n";
+
+    let executable = generate_flat(&Rust { exports: Vec::new(), executable: Some(String::from("start")) }, code);
+    assert!(executable.contains("\npub fn start() -> Lambda {\n"));
+    assert!(executable.ends_with("\nfn main() {\n    io_main(&start());\n}\n"));
+
+    assert!(!generate_flat(&Rust::default(), code).contains("fn main"));
+}
+
+#[test]
+fn test_rust_run() {
+    if !toolchain("rustc") {
+        return;
+    }
+
+    let target = Rust { exports: Vec::new(), executable: Some(String::from("start")) };
+
+    let dir = scratch_dir("rust");
+    for (i, source) in generate_examples_flat(&target, &[0, 2]).iter().enumerate() {
+        let file = dir.join(format!("example{}.rs", i));
+        let binary = dir.join(format!("example{}", i));
+        std::fs::write(&file, source).unwrap();
+
+        run("rustc", &["--edition", "2018", "-o", path_str(&binary), path_str(&file)], "");
+        assert_eq!(run(path_str(&binary), &[], ""), GREETING);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_wat() {
+    let code = generate_flat(&Wat::default(), "k = a -> b -> a\nself = k k k\n");
+
+    // implementations are numbered in the table after the runtime and the harness
+    assert!(code.contains("\
+  (func $k_2_0_ (type $impl) (param $b i32) (param $self i32) (param $cont i32) (result i32)
+    (local $a i32)
+    (local.set $a (i32.load offset=16 (local.get $self)))
+    (call $lambda_unref (local.get $b))
+    (call $lambda_drop (local.get $self) (i32.const 216))
+    (call $cont_call (local.get $cont) (local.get $a)))
+  (data (i32.const 216) \"\\01\\00\\00\\00\")
+"));
+    assert!(code.contains("(call $lambda_call (global.get $k) (global.get $k) (call $cont_mk (i32.const 236) (local.get $cont)))"));
+    assert!(code.contains("\n  (elem (i32.const 12) func $k_2_0_ $k_1_0_ $_self_0_1_ $_self_0_0_)\n"));
+    assert!(code.contains("(global.set $_self (call $lambda_immortal (call $lambda_ret (i32.const 252) (i32.const 0))))"));
+    assert!(!code.contains("(import"));
+}
+
+#[test]
+fn test_wat_executable() {
+    let code = generate_flat(&Wat { executable: Some(String::from("start")) }, "start = x -> x\n");
+
+    assert!(code.starts_with("(module\n  (import \"io\" \"getchar\" (func $getchar (result i32)))\n"));
+    assert!(code.ends_with("\
+  (func (export \"main\")
+    (call $lambda_init)
+    (call $io_run (global.get $start)))
+)
+"));
+}
+
+/// instantiates the module given as argument with IO on stdin and stdout
+const WASM_HOST: &str = "\
+const fs = require(\"fs\");
+const input = fs.readFileSync(0);
+const output = [];
+let position = 0;
+
+const io = {
+    getchar: () => position < input.length ? input[position++] : -1,
+    putchar: c => output.push(c)
+};
+
+WebAssembly.instantiate(fs.readFileSync(process.argv[2]), { io }).then(({ instance }) => {
+    instance.exports.main();
+    process.stdout.write(Buffer.from(output));
+});
+";
+
+#[test]
+fn test_wat_run() {
+    if !toolchain("node") {
+        return;
+    }
+
+    let dir = scratch_dir("wat");
+    std::fs::write(dir.join("host.js"), WASM_HOST).unwrap();
+
+    for (i, source) in generate_examples_flat(&Wat { executable: Some(String::from("start")) }, &[0, 2]).iter().enumerate() {
+        let file = dir.join(format!("example{}.wasm", i));
+        std::fs::write(&file, ::wat::parse_str(source).unwrap()).unwrap();
+
+        assert_eq!(run("node", &[path_str(&dir.join("host.js")), path_str(&file)], ""), GREETING);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,306 @@
+use super::*;
+use crate::analyze::closure_conversion as flat;
+use flat::Local;
+use flat::Variable;
+use flat::Value;
+use flat::Body;
+use flat::Initializer;
+
+/// locals of implementations and globals of the runtime, functions live in
+/// their own namespace
+static RESERVED_WORDS: [&str; 7] = [
+    "cont",
+    "heap_top",
+    "next_arg",
+    "next_cont",
+    "next_function",
+    "next_self",
+    "self"
+];
+
+static CODEGEN_PRELUDE: &str = include_str!("prelude.wat");
+static CODEGEN_HARNESS: &str = include_str!("harness.wat");
+
+/// table slots below this are used by the runtime and the harness
+const FIRST_FUNCTION: usize = 12;
+
+/// the heads of the free lists start here, after the constants of the
+/// runtime and the harness
+const FREE_LISTS: usize = 192;
+
+/// the words in front of the captures of a lambda
+const LAMBDA_HEADER: usize = 4;
+
+/// emits a WebAssembly text module, closures and continuations live on a
+/// refcounted heap in linear memory and calls go through the function table
+#[derive(Debug, Clone, Default)]
+pub struct Wat {
+    /// export a main() that passes the IO primitives to this definition,
+    /// getchar and putchar are imported from the "io" module of the host
+    pub executable: Option<String>
+}
+
+/// addresses of the statically allocated data of every function, which is
+/// its constant closure if it has no captures or its uses array for
+/// lambda_drop otherwise
+#[derive(Debug, Clone)]
+struct Layout {
+    addresses: Vec<usize>,
+    heap: usize
+}
+
+impl Layout {
+    fn new(program: &flat::Program<'_>) -> Self {
+        // the harness allocates lambdas with one capture and continuations
+        // take two words
+        let max_words = program.functions.iter()
+            .map(|function| function.captures.len())
+            .max()
+            .unwrap_or(0)
+            .max(1) + LAMBDA_HEADER;
+
+        let mut next = FREE_LISTS + 4 * (max_words + 1);
+        let addresses = program.functions.iter()
+            .map(|function| {
+                let address = next;
+                next += 4 * if function.captures.is_empty() { LAMBDA_HEADER } else { function.captures.len() };
+                address
+            })
+            .collect();
+
+        Layout {
+            addresses,
+            heap: next
+        }
+    }
+}
+
+/// counts the references an implementation passes on, globals and
+/// constants are immortal and need no references
+#[derive(Debug, Clone, Default)]
+struct ImplementationContext {
+    arg_references: usize,
+    capture_references: Vec<usize>
+}
+
+impl ImplementationContext {
+    fn new(function: &flat::Function<'_>) -> Self {
+        ImplementationContext {
+            arg_references: 0,
+            capture_references: vec![0; function.captures.len()]
+        }
+    }
+
+    fn reference(&mut self, var: Variable<'_>) {
+        match var {
+            Variable::Argument => self.arg_references += 1,
+            Variable::Capture(i) => self.capture_references[i] += 1,
+            Variable::Global(_) => {}
+        }
+    }
+}
+
+fn generate_identifier(ident: Identifier<'_>) -> String {
+    util::generate_suffix_identifier(ident, &RESERVED_WORDS, None)
+}
+
+fn generate_function_name(name: flat::FunctionName<'_>) -> String {
+    let lambda_id = name.lambda.map(|i| i + 1).unwrap_or(0);
+    util::generate_suffix_identifier(
+        name.assignment, &RESERVED_WORDS,
+        Some(format!("_{}_{}", lambda_id, name.continuation))
+    )
+}
+
+fn generate_local(local: Local<'_>) -> String {
+    match local {
+        Local::Unnamed => util::generate_suffix_identifier(
+            "arg", &RESERVED_WORDS, Some(String::new())
+        ),
+        Local::Temporary(id) => util::generate_suffix_identifier(
+            "ret", &RESERVED_WORDS,
+            Some(format!("_{}", id))
+        ),
+        Local::Identifier(ident) => generate_identifier(ident)
+    }
+}
+
+/// little endian words as a string literal for a data segment
+fn generate_data(words: &[u32]) -> String {
+    let bytes: String = words.iter()
+        .flat_map(|word| word.to_le_bytes())
+        .map(|byte| format!("\\{:02x}", byte))
+        .collect();
+
+    format!("\"{}\"", bytes)
+}
+
+fn generate_variable<'i>(var: Variable<'i>, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    ictx.reference(var);
+
+    match var {
+        Variable::Argument => format!("(local.get ${})", generate_local(function.argument)),
+        Variable::Capture(i) => format!("(local.get ${})", generate_local(function.captures[i])),
+        Variable::Global(ident) => format!("(global.get ${})", generate_identifier(ident))
+    }
+}
+
+/// closures without captures are constants in the data segment
+fn generate_closure<'i>(closure: &flat::Closure<'i>, layout: &Layout, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    if closure.captures.is_empty() {
+        return format!("(i32.const {})", layout.addresses[closure.function]);
+    }
+
+    let mut res = format!("(call $lambda_mk (i32.const {}) (i32.const {}))",
+        FIRST_FUNCTION + closure.function, closure.captures.len()
+    );
+
+    for (i, &var) in closure.captures.iter().enumerate() {
+        res = format!("(call $capture {} (i32.const {}) {})", res, i, generate_variable(var, function, ictx));
+    }
+
+    res
+}
+
+fn generate_value<'i>(value: &Value<'i>, layout: &Layout, function: &flat::Function<'i>, ictx: &mut ImplementationContext) -> String {
+    match value {
+        Value::Variable(var) => generate_variable(*var, function, ictx),
+        Value::Closure(closure) => generate_closure(closure, layout, function, ictx)
+    }
+}
+
+fn generate_implementation(program: &flat::Program<'_>, layout: &Layout, id: usize) -> String {
+    let function = program.function(id);
+    let name = generate_function_name(function.name);
+    let arg_name = generate_local(function.argument);
+
+    let mut ictx = ImplementationContext::new(function);
+
+    let ret = match &function.body {
+        Body::Call { function: func, argument, continuation } => {
+            let func = generate_value(func, layout, function, &mut ictx);
+            let arg = generate_value(argument, layout, function, &mut ictx);
+            let cont = match continuation {
+                Some(cont) => format!("(call $cont_mk {} (local.get $cont))", generate_closure(cont, layout, function, &mut ictx)),
+                None => String::from("(local.get $cont)")
+            };
+
+            format!("    (call $lambda_call {} {} {}))\n", func, arg, cont)
+        }
+        Body::Return(value) => {
+            let arg = generate_value(value, layout, function, &mut ictx);
+
+            format!("    (call $cont_call (local.get $cont) {}))\n", arg)
+        }
+    };
+
+    let mut res = format!("  (func ${} (type $impl) (param ${} i32) (param $self i32) (param $cont i32) (result i32)\n",
+        name, arg_name
+    );
+
+    let captures: Vec<_> = ictx.capture_references.iter()
+        .enumerate()
+        .filter(|(_, refcount)| **refcount > 0)
+        .map(|(i, _)| (i, generate_local(function.captures[i])))
+        .collect();
+
+    for (_, local) in captures.iter() {
+        res += &format!("    (local ${} i32)\n", local);
+    }
+
+    // captures are loaded before self is dropped
+    for (i, local) in captures.iter() {
+        res += &format!("    (local.set ${} (i32.load offset={} (local.get $self)))\n",
+            local, 4 * (LAMBDA_HEADER + i)
+        );
+    }
+
+    if ictx.arg_references == 0 {
+        res += &format!("    (call $lambda_unref (local.get ${}))\n", arg_name);
+    } else if ictx.arg_references > 1 {
+        res += &format!("    (call $lambda_ref (local.get ${}) (i32.const {}))\n", arg_name, ictx.arg_references - 1);
+    }
+
+    // closures without captures are immortal constants, others hand their
+    // references to the captures over when they are dropped
+    let data: Vec<_> = if function.captures.is_empty() {
+        vec![i32::MAX as u32, (FIRST_FUNCTION + id) as u32, 0, 0]
+    } else {
+        res += &format!("    (call $lambda_drop (local.get $self) (i32.const {}))\n", layout.addresses[id]);
+        ictx.capture_references.iter().map(|&refcount| refcount as u32).collect()
+    };
+
+    res += &ret;
+    res + &format!("  (data (i32.const {}) {})\n", layout.addresses[id], generate_data(&data))
+}
+
+/// returns the definition of a global and the instruction in lambda_init
+/// that initializes it
+fn generate_definition<'i>(program: &flat::Program<'i>, layout: &Layout, def: &flat::Definition<'i>) -> (String, String) {
+    let mut res = String::from("\n");
+
+    for &id in def.functions.iter() {
+        res += &format!("{}\n", generate_implementation(program, layout, id));
+    }
+
+    let value = match &def.value {
+        Initializer::Value(Value::Variable(var)) => match var {
+            Variable::Global(ident) => format!("(global.get ${})", generate_identifier(ident)),
+            _ => unreachable!("local variable in global scope")
+        },
+        Initializer::Value(Value::Closure(closure)) => format!("(i32.const {})", layout.addresses[closure.function]),
+        Initializer::Call(id) => format!("(call $lambda_immortal (call $lambda_ret (i32.const {}) (i32.const 0)))",
+            layout.addresses[*id]
+        )
+    };
+
+    let target = generate_identifier(def.target);
+
+    (
+        res + &format!("  (global ${} (mut i32) (i32.const 0))\n", target),
+        format!("    (global.set ${} {})\n", target, value)
+    )
+}
+
+impl FlatCodegenTarget for Wat {
+    fn generate(&self, program: &flat::Program<'_>) -> String {
+        let layout = Layout::new(program);
+        let mut res = String::from("(module\n");
+
+        // imports come before every other definition of a module
+        if self.executable.is_some() {
+            res += "  (import \"io\" \"getchar\" (func $getchar (result i32)))\n";
+            res += "  (import \"io\" \"putchar\" (func $putchar (param i32)))\n\n";
+        }
+
+        res += CODEGEN_PRELUDE;
+        res += &format!("\n  (global $heap_top (mut i32) (i32.const {}))\n", layout.heap);
+        res += &format!("  (table {} funcref)\n", FIRST_FUNCTION + program.functions.len());
+
+        let mut init = String::new();
+        for def in program.iter() {
+            let (definition, instruction) = generate_definition(program, &layout, def);
+            res += &definition;
+            init += &instruction;
+        }
+
+        let functions: Vec<_> = program.functions.iter()
+            .map(|function| format!("${}", generate_function_name(function.name)))
+            .collect();
+
+        if !functions.is_empty() {
+            res += &format!("\n  (elem (i32.const {}) func {})\n", FIRST_FUNCTION, functions.join(" "));
+        }
+
+        res += &format!("\n  (func $lambda_init (export \"lambda_init\")\n{}  )\n", init);
+
+        if let Some(entry) = &self.executable {
+            res += CODEGEN_HARNESS;
+            res += &format!("\n  (func (export \"main\")\n    (call $lambda_init)\n    (call $io_run (global.get ${})))\n",
+                generate_identifier(entry)
+            );
+        }
+
+        res + ")\n"
+    }
+}
@@ -1,8 +1,13 @@
 use crate::parser::ParseError;
+use crate::analyze::textual::ParseError as IrParseError;
 use crate::ast::maker::AstMakeError;
+use crate::pass::PassError;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
-    ParseError(ParseError),
-    AstMakeError(AstMakeError)
+    /// pest errors are large, boxing them keeps every Result small
+    ParseError(Box<ParseError>),
+    AstMakeError(AstMakeError),
+    IrParseError(Box<IrParseError>),
+    PassError(PassError)
 }
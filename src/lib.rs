@@ -1,9 +1,7 @@
-#![feature(associated_type_defaults)]
-#![feature(specialization)]
-#![feature(never_type)]
-
 pub mod error;
 pub mod parser;
 pub mod ast;
 pub mod analyze;
+pub mod pass;
+pub mod optimize;
 pub mod codegen;
@@ -0,0 +1,107 @@
+use crate::error::Error;
+use crate::pass::Ir;
+use crate::pass::Pass;
+use crate::pass::unexpected_ir;
+
+pub mod term;
+pub mod inline;
+pub mod partial_eval;
+pub mod eta;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizationOptions {
+    pub level: u32,
+    pub inline_threshold: usize,
+    pub eval_budget: usize,
+    pub fold_threshold: usize
+}
+
+impl Default for OptimizationOptions {
+    fn default() -> Self {
+        OptimizationOptions {
+            level: 0,
+            inline_threshold: 16,
+            eval_budget: 100_000,
+            fold_threshold: 512
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Inline {
+    pub threshold: usize
+}
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Source(program) => Ok(Ir::Source(
+                inline::transform_program(&program, self.threshold)
+            )),
+            ir => Err(unexpected_ir(self.name(), "source", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PartialEval {
+    pub budget: usize,
+    pub threshold: usize
+}
+
+impl Pass for PartialEval {
+    fn name(&self) -> &'static str {
+        "partial-eval"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, diagnostics: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Source(program) => Ok(Ir::Source(
+                partial_eval::transform_program(&program, self.budget, self.threshold, diagnostics)
+            )),
+            ir => Err(unexpected_ir(self.name(), "source", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EtaReduce;
+
+impl Pass for EtaReduce {
+    fn name(&self) -> &'static str {
+        "eta"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Source(program) => Ok(Ir::Source(eta::transform_program(&program))),
+            ir => Err(unexpected_ir(self.name(), "source", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EtaLint;
+
+impl Pass for EtaLint {
+    fn name(&self) -> &'static str {
+        "lint-eta"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, diagnostics: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Source(program) => {
+                eta::lint_program(&program, diagnostics);
+                Ok(Ir::Source(program))
+            }
+            ir => Err(unexpected_ir(self.name(), "source", &ir))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,156 @@
+use std::rc::Rc;
+use std::collections::BTreeMap;
+
+use crate::ast::nodata::*;
+use super::term::Term;
+
+#[derive(Debug, Clone)]
+struct Context<'i> {
+    arities: BTreeMap<Identifier<'i>, usize>,
+    bound: Vec<Identifier<'i>>
+}
+
+impl<'i> Context<'i> {
+    fn new() -> Self {
+        Context {
+            arities: BTreeMap::new(),
+            bound: Vec::new()
+        }
+    }
+
+    /// number of arguments a term accepts before doing any work
+    fn arity(&self, term: &Term<'i>) -> usize {
+        match term {
+            Term::Variable(ident) => if self.bound.contains(ident) {
+                0
+            } else {
+                self.arities.get(ident).copied().unwrap_or(0)
+            },
+            Term::Lambda(_, body) => match body.as_ref() {
+                Term::Lambda(..) => 1 + self.arity(body),
+                _ => 1
+            },
+            Term::Apply(..) => 0
+        }
+    }
+
+    /// checks whether evaluating a term finishes without doing any work
+    /// apart from building closures, so it can be moved out of a lambda
+    fn is_partial(&self, term: &Term<'i>) -> bool {
+        match term {
+            Term::Variable(_) | Term::Lambda(..) => true,
+            Term::Apply(head, args) => {
+                matches!(head.as_ref(), Term::Variable(_))
+                    && args.len() < self.arity(head)
+                    && args.iter().all(|arg| self.is_partial(arg))
+            }
+        }
+    }
+
+    /// reduces argument -> f argument to f
+    fn eta_lambda(&self, argument: Identifier<'i>, body: &Rc<Term<'i>>) -> Option<Rc<Term<'i>>> {
+        let (head, args) = match body.as_ref() {
+            Term::Apply(head, args) => (head, args),
+            _ => return None
+        };
+
+        let (last, rest) = args.split_last()?;
+        if last.as_ref() != &Term::Variable(argument) {
+            return None;
+        }
+
+        let function = Term::apply(head.clone(), rest.to_vec());
+        if function.occurrences(argument).0 != 0 || !self.is_partial(&function) {
+            return None;
+        }
+
+        Some(function)
+    }
+
+    fn reduce(&mut self, term: &Rc<Term<'i>>) -> Rc<Term<'i>> {
+        match term.as_ref() {
+            Term::Variable(_) => term.clone(),
+            Term::Lambda(argument, body) => {
+                self.bound.push(argument);
+                let body = self.reduce(body);
+                self.bound.pop();
+
+                self.eta_lambda(argument, &body)
+                    .unwrap_or_else(|| Rc::new(Term::Lambda(argument, body)))
+            }
+            Term::Apply(head, args) => {
+                let head = self.reduce(head);
+                let args = args.iter()
+                    .map(|arg| self.reduce(arg))
+                    .collect();
+
+                Term::apply(head, args)
+            }
+        }
+    }
+
+    fn lint(&mut self, term: &Rc<Term<'i>>, target: Identifier<'i>, diagnostics: &mut Vec<String>) {
+        match term.as_ref() {
+            Term::Variable(_) => {}
+            Term::Lambda(argument, body) => {
+                self.bound.push(argument);
+                let reduced = self.reduce(body);
+                let function = self.eta_lambda(argument, &reduced);
+
+                match function {
+                    Some(function) => diagnostics.push(format!(
+                        "warning: '{}' in definition of '{}' can be reduced to '{}'",
+                        term.to_application(), target, function.to_application()
+                    )),
+                    None => self.lint(body, target, diagnostics)
+                }
+
+                self.bound.pop();
+            }
+            Term::Apply(head, args) => {
+                self.lint(head, target, diagnostics);
+
+                for arg in args.iter() {
+                    self.lint(arg, target, diagnostics);
+                }
+            }
+        }
+    }
+
+    fn define(&mut self, target: Identifier<'i>, term: &Term<'i>) {
+        let arity = self.arity(term);
+        self.arities.insert(target, arity);
+    }
+}
+
+pub fn transform_program<'i>(program: &Program<'i>) -> Program<'i> {
+    let mut ctx = Context::new();
+
+    let assignments = program.iter()
+        .map(|ass| {
+            let term = ctx.reduce(&Term::from_application(&ass.value));
+            ctx.define(ass.target, &term);
+
+            Assignment {
+                target: ass.target,
+                value: term.to_application(),
+                data: ()
+            }
+        })
+        .collect();
+
+    Program {
+        assignments,
+        data: ()
+    }
+}
+
+pub fn lint_program(program: &Program<'_>, diagnostics: &mut Vec<String>) {
+    let mut ctx = Context::new();
+
+    for ass in program.iter() {
+        let term = Term::from_application(&ass.value);
+        ctx.lint(&term, ass.target, diagnostics);
+        ctx.define(ass.target, &term);
+    }
+}
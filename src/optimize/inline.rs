@@ -0,0 +1,82 @@
+use std::rc::Rc;
+use std::collections::BTreeMap;
+
+use crate::ast::nodata::*;
+use super::term::Term;
+
+#[derive(Debug, Clone)]
+struct Context<'i> {
+    threshold: usize,
+    inlinable: BTreeMap<Identifier<'i>, Rc<Term<'i>>>,
+    bound: Vec<Identifier<'i>>
+}
+
+impl<'i> Context<'i> {
+    /// the value of a global that may replace the variable, this is only
+    /// used for heads of applications, globals passed as arguments stay references
+    fn inlinable(&self, ident: Identifier<'i>) -> Option<Rc<Term<'i>>> {
+        if self.bound.contains(&ident) {
+            return None;
+        }
+
+        self.inlinable.get(ident)
+            .filter(|value| value.free_variables().iter().all(|free| !self.bound.contains(free)))
+            .cloned()
+    }
+
+    fn inline(&mut self, term: &Rc<Term<'i>>) -> Rc<Term<'i>> {
+        match term.as_ref() {
+            Term::Variable(_) => term.clone(),
+            Term::Lambda(argument, body) => {
+                self.bound.push(argument);
+                let body = self.inline(body);
+                self.bound.pop();
+
+                Rc::new(Term::Lambda(argument, body))
+            }
+            Term::Apply(head, args) => {
+                let head = match head.as_ref() {
+                    Term::Variable(ident) => self.inlinable(ident).unwrap_or_else(|| head.clone()),
+                    _ => self.inline(head)
+                };
+                let args = args.iter()
+                    .map(|arg| self.inline(arg))
+                    .collect();
+
+                Term::apply(head, args)
+            }
+        }
+    }
+}
+
+pub fn transform_program<'i>(program: &Program<'i>, threshold: usize) -> Program<'i> {
+    let mut ctx = Context {
+        threshold,
+        inlinable: BTreeMap::new(),
+        bound: Vec::new()
+    };
+
+    let assignments = program.iter()
+        .map(|ass| {
+            let term = ctx.inline(&Term::from_application(&ass.value)).simplify();
+
+            // a redefinition replaces the earlier value, even if it is not inlinable
+            if matches!(term.as_ref(), Term::Lambda(..)) && term.size() <= ctx.threshold {
+                ctx.inlinable.insert(ass.target, term.clone());
+            } else {
+                ctx.inlinable.remove(ass.target);
+            }
+
+            Assignment {
+                target: ass.target,
+                value: term.to_application(),
+                data: ()
+            }
+        })
+        .collect();
+
+    Program {
+        assignments,
+        data: ()
+    }
+}
@@ -0,0 +1,320 @@
+use std::rc::Rc;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::ast::nodata::*;
+use super::term::Term;
+
+#[derive(Debug)]
+struct Closure<'i> {
+    argument: Identifier<'i>,
+    body: Rc<Term<'i>>,
+    env: Env<'i>
+}
+
+#[derive(Debug)]
+struct Binding<'i> {
+    name: Identifier<'i>,
+    value: Value<'i>,
+    next: Env<'i>
+}
+
+type Value<'i> = Rc<Closure<'i>>;
+type Env<'i> = Option<Rc<Binding<'i>>>;
+
+// values built by long evaluations form deep chains, drop them iteratively
+impl Drop for Closure<'_> {
+    fn drop(&mut self) {
+        let mut envs = vec![self.env.take()];
+
+        while let Some(env) = envs.pop() {
+            if let Some(Ok(binding)) = env.map(Rc::try_unwrap) {
+                let Binding { value, next, .. } = binding;
+                envs.push(next);
+
+                if let Ok(mut closure) = Rc::try_unwrap(value) {
+                    envs.push(closure.env.take());
+                }
+            }
+        }
+    }
+}
+
+fn lookup<'i>(env: &Env<'i>, name: Identifier<'i>) -> Option<Value<'i>> {
+    let mut env = env;
+
+    while let Some(binding) = env {
+        if binding.name == name {
+            return Some(binding.value.clone());
+        }
+
+        env = &binding.next;
+    }
+
+    None
+}
+
+fn bind<'i>(env: Env<'i>, name: Identifier<'i>, value: Value<'i>) -> Env<'i> {
+    Some(Rc::new(Binding { name, value, next: env }))
+}
+
+enum Frame<'i> {
+    Argument(Rc<Term<'i>>, Env<'i>),
+    Call(Value<'i>)
+}
+
+#[derive(Debug)]
+struct Folded<'i> {
+    term: Rc<Term<'i>>,
+    size: usize,
+    free: BTreeSet<Identifier<'i>>
+}
+
+/// the globals that values are read back as, the value is kept alive so that
+/// its address cannot be reused while it is a key
+type Names<'i> = HashMap<*const Closure<'i>, (Value<'i>, Identifier<'i>)>;
+
+struct Readback<'a, 'i> {
+    names: &'a Names<'i>,
+    visited: HashSet<*const Closure<'i>>,
+    threshold: usize
+}
+
+impl<'a, 'i> Readback<'a, 'i> {
+    fn value(&mut self, value: &Value<'i>) -> Option<Rc<Folded<'i>>> {
+        let key = Rc::as_ptr(value);
+
+        if let Some((_, name)) = self.names.get(&key) {
+            return Some(Rc::new(Folded {
+                term: Rc::new(Term::Variable(name)),
+                size: 1,
+                free: std::iter::once(*name).collect()
+            }));
+        }
+
+        // a shared value would be duplicated in the normal form, which
+        // unrolls recursive functions built by y
+        if !self.visited.insert(key) {
+            return None;
+        }
+
+        let mut size = 1;
+        let mut free = BTreeSet::new();
+        let body = self.fill(&value.body, &value.env, &mut vec![value.argument], &mut size, &mut free)?;
+
+        Some(Rc::new(Folded {
+            term: Rc::new(Term::Lambda(value.argument, body)),
+            size,
+            free
+        }))
+    }
+
+    /// replaces the variables of a closure body with the values they are bound to
+    fn fill(&mut self, term: &Rc<Term<'i>>, env: &Env<'i>, bound: &mut Vec<Identifier<'i>>, size: &mut usize, free: &mut BTreeSet<Identifier<'i>>)
+        -> Option<Rc<Term<'i>>>
+    {
+        let res = match term.as_ref() {
+            Term::Variable(name) => if bound.contains(name) {
+                *size += 1;
+                term.clone()
+            } else if let Some(value) = lookup(env, name) {
+                let folded = self.value(&value)?;
+
+                if folded.free.iter().any(|name| bound.contains(name)) {
+                    return None;
+                }
+
+                *size += folded.size;
+                free.extend(folded.free.iter().copied());
+                folded.term.clone()
+            } else {
+                *size += 1;
+                free.insert(name);
+                term.clone()
+            },
+            Term::Lambda(argument, body) => {
+                bound.push(argument);
+                let body = self.fill(body, env, bound, size, free);
+                bound.pop();
+
+                *size += 1;
+                Rc::new(Term::Lambda(argument, body?))
+            }
+            Term::Apply(head, args) => {
+                let head = self.fill(head, env, bound, size, free)?;
+                let args = args.iter()
+                    .map(|arg| self.fill(arg, env, bound, size, free))
+                    .collect::<Option<Vec<_>>>()?;
+
+                Term::apply(head, args)
+            }
+        };
+
+        if *size > self.threshold {
+            None
+        } else {
+            Some(res)
+        }
+    }
+}
+
+struct Context<'i> {
+    budget: usize,
+    /// the steps left for the current definition, shared by all of its terms
+    steps: usize,
+    threshold: usize,
+    globals: BTreeMap<Identifier<'i>, Value<'i>>,
+    names: Names<'i>,
+    folded: usize
+}
+
+impl<'i> Context<'i> {
+    /// evaluates a closed term by call-by-value, gives up when the step
+    /// budget is exhausted or an unknown name is encountered
+    fn evaluate(&mut self, term: &Rc<Term<'i>>) -> Option<Value<'i>> {
+        let mut stack = Vec::new();
+        let mut term = term.clone();
+        let mut env = None;
+
+        loop {
+            let value = match term.as_ref() {
+                Term::Variable(name) => lookup(&env, name)
+                    .or_else(|| self.globals.get(name).cloned())?,
+                Term::Lambda(argument, body) => Rc::new(Closure {
+                    argument,
+                    body: body.clone(),
+                    env: env.clone()
+                }),
+                Term::Apply(head, args) => {
+                    for arg in args.iter().rev() {
+                        stack.push(Frame::Argument(arg.clone(), env.clone()));
+                    }
+
+                    term = head.clone();
+                    continue;
+                }
+            };
+
+            match stack.pop() {
+                None => return Some(value),
+                Some(Frame::Argument(arg, arg_env)) => {
+                    stack.push(Frame::Call(value));
+                    term = arg;
+                    env = arg_env;
+                }
+                Some(Frame::Call(function)) => {
+                    if self.steps == 0 {
+                        return None;
+                    }
+
+                    self.steps -= 1;
+
+                    term = function.body.clone();
+                    env = bind(function.env.clone(), function.argument, value);
+                }
+            }
+        }
+    }
+
+    fn readback(&self, value: &Value<'i>) -> Option<Rc<Folded<'i>>> {
+        Readback {
+            names: &self.names,
+            visited: HashSet::new(),
+            threshold: self.threshold
+        }.value(value)
+    }
+
+    /// replaces closed applications with their normal forms
+    fn fold(&mut self, term: &Rc<Term<'i>>, bound: &mut Vec<Identifier<'i>>) -> Rc<Term<'i>> {
+        match term.as_ref() {
+            Term::Variable(_) => term.clone(),
+            Term::Lambda(argument, body) => {
+                bound.push(argument);
+                let body = self.fold(body, bound);
+                bound.pop();
+
+                Rc::new(Term::Lambda(argument, body))
+            }
+            Term::Apply(head, args) => {
+                let closed = term.free_variables().iter().all(|name| !bound.contains(name));
+
+                if closed {
+                    let folded = self.evaluate(term)
+                        .and_then(|value| self.readback(&value))
+                        .filter(|folded| folded.free.iter().all(|name| !bound.contains(name)));
+
+                    if let Some(folded) = folded {
+                        self.folded += 1;
+                        return folded.term.clone();
+                    }
+                }
+
+                let head = self.fold(head, bound);
+                let args = args.iter()
+                    .map(|arg| self.fold(arg, bound))
+                    .collect();
+
+                Term::apply(head, args)
+            }
+        }
+    }
+
+    fn define(&mut self, name: Identifier<'i>, term: &Rc<Term<'i>>) {
+        let value = self.evaluate(term);
+
+        if let Some(old) = self.globals.remove(name) {
+            if self.names.get(&Rc::as_ptr(&old)).is_some_and(|(_, owner)| *owner == name) {
+                self.names.remove(&Rc::as_ptr(&old));
+            }
+        }
+
+        if let Some(value) = value {
+            self.names.entry(Rc::as_ptr(&value)).or_insert((value.clone(), name));
+            self.globals.insert(name, value);
+        }
+    }
+}
+
+pub fn transform_program<'i>(program: &Program<'i>, budget: usize, threshold: usize, diagnostics: &mut Vec<String>) -> Program<'i> {
+    let mut ctx = Context {
+        budget,
+        steps: budget,
+        threshold,
+        globals: BTreeMap::new(),
+        names: HashMap::new(),
+        folded: 0
+    };
+
+    let assignments = program.iter()
+        .map(|ass| {
+            let term = Term::from_application(&ass.value);
+
+            ctx.folded = 0;
+            ctx.steps = ctx.budget;
+            let folded = ctx.fold(&term, &mut Vec::new());
+
+            if ctx.folded > 0 && folded != term {
+                if term.is_value() || !folded.is_value() {
+                    diagnostics.push(format!("note: folded {} closed terms in definition of '{}'", ctx.folded, ass.target));
+                } else {
+                    diagnostics.push(format!("note: folded definition of '{}'", ass.target));
+                }
+            }
+
+            ctx.define(ass.target, &folded);
+
+            Assignment {
+                target: ass.target,
+                value: folded.to_application(),
+                data: ()
+            }
+        })
+        .collect();
+
+    Program {
+        assignments,
+        data: ()
+    }
+}
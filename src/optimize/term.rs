@@ -0,0 +1,264 @@
+use std::rc::Rc;
+use std::collections::BTreeSet;
+
+use crate::ast::generic::push_expression;
+use crate::ast::nodata::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term<'i> {
+    Variable(Identifier<'i>),
+    Lambda(Identifier<'i>, Rc<Term<'i>>),
+    Apply(Rc<Term<'i>>, Vec<Rc<Term<'i>>>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reached {
+    Variable,
+    Effect,
+    Nothing
+}
+
+impl<'i> Term<'i> {
+    pub fn from_application(app: &Application<'i>) -> Rc<Term<'i>> {
+        let mut iter = app.iter();
+        let head = Term::from_expression(iter.next().expect("empty application in AST"));
+        let args: Vec<_> = iter.map(Term::from_expression).collect();
+
+        Term::apply(head, args)
+    }
+
+    pub fn from_expression(expr: &Expression<'i>) -> Rc<Term<'i>> {
+        match expr {
+            Expression::Identifier(ident) => Rc::new(Term::Variable(ident)),
+            Expression::Parenthesis(app) => Term::from_application(app),
+            Expression::Lambda(lambda) => Rc::new(Term::Lambda(
+                lambda.argument,
+                Term::from_application(&lambda.body)
+            ))
+        }
+    }
+
+    pub fn to_application(&self) -> Rc<Application<'i>> {
+        let mut expressions = Vec::new();
+
+        match self {
+            Term::Apply(head, args) => {
+                push_expression(&mut expressions, head.to_expression());
+
+                for arg in args.iter() {
+                    push_expression(&mut expressions, arg.to_expression());
+                }
+            }
+            _ => expressions.push(self.to_expression())
+        }
+
+        Rc::new(Application {
+            expressions,
+            data: ()
+        })
+    }
+
+    pub fn to_expression(&self) -> Expression<'i> {
+        match self {
+            Term::Variable(ident) => Expression::Identifier(ident),
+            Term::Lambda(argument, body) => Expression::Lambda(Rc::new(Lambda {
+                argument,
+                body: body.to_application(),
+                data: ()
+            })),
+            Term::Apply(..) => Expression::Parenthesis(self.to_application())
+        }
+    }
+
+    /// builds an application, merging nested application heads
+    pub fn apply(head: Rc<Term<'i>>, mut args: Vec<Rc<Term<'i>>>) -> Rc<Term<'i>> {
+        if args.is_empty() {
+            return head;
+        }
+
+        match head.as_ref() {
+            Term::Apply(inner, inner_args) => {
+                let mut all_args = inner_args.clone();
+                all_args.append(&mut args);
+                Rc::new(Term::Apply(inner.clone(), all_args))
+            }
+            _ => Rc::new(Term::Apply(head, args))
+        }
+    }
+
+    pub fn is_value(&self) -> bool {
+        !matches!(self, Term::Apply(..))
+    }
+
+    /// number of variables and lambdas in the term
+    pub fn size(&self) -> usize {
+        match self {
+            Term::Variable(_) => 1,
+            Term::Lambda(_, body) => 1 + body.size(),
+            Term::Apply(head, args) => head.size() + args.iter().map(|arg| arg.size()).sum::<usize>()
+        }
+    }
+
+    pub fn free_variables(&self) -> BTreeSet<Identifier<'i>> {
+        let mut free = BTreeSet::new();
+        self.collect_free_variables(&mut Vec::new(), &mut free);
+        free
+    }
+
+    fn collect_free_variables(&self, bound: &mut Vec<Identifier<'i>>, free: &mut BTreeSet<Identifier<'i>>) {
+        match self {
+            Term::Variable(ident) => if !bound.contains(ident) {
+                free.insert(ident);
+            }
+            Term::Lambda(argument, body) => {
+                bound.push(argument);
+                body.collect_free_variables(bound, free);
+                bound.pop();
+            }
+            Term::Apply(head, args) => {
+                head.collect_free_variables(bound, free);
+
+                for arg in args.iter() {
+                    arg.collect_free_variables(bound, free);
+                }
+            }
+        }
+    }
+
+    /// counts free occurrences of a variable, and how many of those are inside a lambda
+    pub fn occurrences(&self, ident: Identifier<'i>) -> (usize, usize) {
+        match self {
+            Term::Variable(var) => if *var == ident { (1, 0) } else { (0, 0) },
+            Term::Lambda(argument, body) => if *argument == ident {
+                (0, 0)
+            } else {
+                let (count, _) = body.occurrences(ident);
+                (count, count)
+            },
+            Term::Apply(head, args) => {
+                let mut res = head.occurrences(ident);
+
+                for arg in args.iter() {
+                    let (count, under_lambda) = arg.occurrences(ident);
+                    res.0 += count;
+                    res.1 += under_lambda;
+                }
+
+                res
+            }
+        }
+    }
+
+    /// walks the term in call-by-value evaluation order and reports whether
+    /// the variable is reached before the first application is performed
+    pub fn reached_before_effect(&self, ident: Identifier<'i>) -> Reached {
+        match self {
+            Term::Variable(var) => if *var == ident { Reached::Variable } else { Reached::Nothing },
+            Term::Lambda(..) => Reached::Nothing,
+            Term::Apply(head, args) => {
+                let reached = head.reached_before_effect(ident);
+                if reached != Reached::Nothing {
+                    return reached;
+                }
+
+                match args[0].reached_before_effect(ident) {
+                    Reached::Nothing => Reached::Effect,
+                    reached => reached
+                }
+            }
+        }
+    }
+
+    /// capture-avoiding substitution, returns None if a binder would capture a
+    /// free variable of the replacement
+    pub fn substitute(self: &Rc<Self>, ident: Identifier<'i>, replacement: &Rc<Term<'i>>) -> Option<Rc<Term<'i>>> {
+        self.substitute_free(ident, replacement, &replacement.free_variables())
+    }
+
+    fn substitute_free(self: &Rc<Self>, ident: Identifier<'i>, replacement: &Rc<Term<'i>>, free: &BTreeSet<Identifier<'i>>)
+        -> Option<Rc<Term<'i>>>
+    {
+        match self.as_ref() {
+            Term::Variable(var) => Some(if *var == ident {
+                replacement.clone()
+            } else {
+                self.clone()
+            }),
+            Term::Lambda(argument, body) => {
+                if *argument == ident || body.occurrences(ident).0 == 0 {
+                    Some(self.clone())
+                } else if free.contains(argument) {
+                    None
+                } else {
+                    Some(Rc::new(Term::Lambda(argument, body.substitute_free(ident, replacement, free)?)))
+                }
+            }
+            Term::Apply(head, args) => {
+                let head = head.substitute_free(ident, replacement, free)?;
+                let args = args.iter()
+                    .map(|arg| arg.substitute_free(ident, replacement, free))
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(Term::apply(head, args))
+            }
+        }
+    }
+
+    /// performs a single beta reduction of (argument -> body) value if it is
+    /// safe under call-by-value evaluation and does not grow the term
+    pub fn beta_reduce(argument: Identifier<'i>, body: &Rc<Term<'i>>, value: &Rc<Term<'i>>) -> Option<Rc<Term<'i>>> {
+        let (count, under_lambda) = body.occurrences(argument);
+
+        let safe = match value.as_ref() {
+            Term::Variable(_) => true,
+            Term::Lambda(..) => count <= 1,
+            Term::Apply(..) => count == 1 && under_lambda == 0
+                && body.reached_before_effect(argument) == Reached::Variable
+        };
+
+        if !safe {
+            None
+        } else if count == 0 {
+            Some(body.clone())
+        } else {
+            body.substitute(argument, value)
+        }
+    }
+
+    /// beta-reduces until no more safe reductions are possible
+    pub fn simplify(self: &Rc<Self>) -> Rc<Term<'i>> {
+        match self.as_ref() {
+            Term::Variable(_) => self.clone(),
+            Term::Lambda(argument, body) => Rc::new(Term::Lambda(argument, body.simplify())),
+            Term::Apply(head, args) => {
+                // the next argument is at the end, so reductions pop it instead of
+                // shifting the remaining ones
+                let mut stack: Vec<_> = args.iter().rev().map(|arg| arg.simplify()).collect();
+                let mut head = head.simplify();
+
+                loop {
+                    if let Term::Apply(inner, inner_args) = head.as_ref() {
+                        stack.extend(inner_args.iter().rev().cloned());
+                        head = inner.clone();
+                    }
+
+                    let reduced = match (head.as_ref(), stack.last()) {
+                        (Term::Lambda(argument, body), Some(value)) => Term::beta_reduce(argument, body, value),
+                        _ => None
+                    };
+
+                    match reduced {
+                        Some(reduced) => {
+                            stack.pop();
+                            head = reduced.simplify();
+                        }
+                        None => break
+                    }
+                }
+
+                stack.reverse();
+                Term::apply(head, stack)
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use super::*;
+use crate::parser::LambdaParser;
+use crate::pass::PassManager;
+
+fn inline(code: &str, threshold: usize) -> String {
+    let program = LambdaParser::parse_program(code).unwrap();
+    inline::transform_program(&program, threshold).to_string()
+}
+
+#[test]
+fn test_inline() {
+    assert_eq!(
+        inline("true = a -> b -> a\n1st = tup -> tup true\nfirst = list -> 1st (1st list)\n", 16),
+        "true = a -> b -> a\n1st = tup -> tup true\nfirst = list -> list true true\n"
+    );
+}
+
+#[test]
+fn test_inline_threshold() {
+    assert_eq!(
+        inline("true = a -> b -> a\nx = true true\n", 2),
+        "true = a -> b -> a\nx = true true\n"
+    );
+}
+
+#[test]
+fn test_inline_capture() {
+    // reducing `k b` would capture the argument `b`
+    assert_eq!(
+        inline("k = a -> b -> a\nf = b -> k b\n", 16),
+        "k = a -> b -> a\nf = b -> (a -> b -> a) b\n"
+    );
+    // `g` in the definition of `h` refers to a global
+    assert_eq!(
+        inline("g = a -> a\nh = x -> g x\nf = g -> h g\n", 16),
+        "g = a -> a\nh = x -> x\nf = g -> g\n"
+    );
+}
+
+#[test]
+fn test_inline_redefinition() {
+    // the second f is too large to inline, so its use must not see the first one
+    assert_eq!(
+        inline("f = a -> a\nf = a -> b -> c -> a b c\nx = f f\n", 4),
+        "f = a -> a\nf = a -> b -> c -> a b c\nx = f f\n"
+    );
+}
+
+#[test]
+fn test_inline_shadowing() {
+    assert_eq!(
+        inline("ident = a -> a\nf = ident -> ident ident\n", 16),
+        "ident = a -> a\nf = ident -> ident ident\n"
+    );
+}
+
+#[test]
+fn test_inline_strict() {
+    // the argument may have side effects, so it must be evaluated exactly once
+    assert_eq!(
+        inline("twice = x -> x x\nf = h -> twice (h h)\n", 16),
+        "twice = x -> x x\nf = h -> (x -> x x) (h h)\n"
+    );
+    assert_eq!(
+        inline("ignore = x -> a -> a\nf = h -> ignore (h h)\n", 16),
+        "ignore = x -> a -> a\nf = h -> (x -> a -> a) (h h)\n"
+    );
+    assert_eq!(
+        inline("call = x -> y -> x y\nf = h -> call (a -> a)\n", 16),
+        "call = x -> y -> x y\nf = h -> y -> y\n"
+    );
+}
+
+#[test]
+fn test_inline_recursion() {
+    let code = "y = g -> (f -> f f) f -> g x -> f f x\nloop = y loop -> x -> loop x\n";
+    assert_eq!(
+        inline(code, 16),
+        "y = g -> (f -> f f) f -> g x -> f f x\nloop = (f -> f f) f -> x -> f f x\n"
+    );
+}
+
+#[test]
+fn test_inline_long_application() {
+    let code = format!("x = {}\n", vec!["(a -> a)"; 100_000].join(" "));
+    assert_eq!(inline(&code, 16), "x = a -> a\n");
+}
+
+#[test]
+fn test_optimization_passes() {
+    let program = LambdaParser::parse_program("ident = a -> a\nx = ident ident\n").unwrap();
+
+    let mut pm = PassManager::new();
+    pm.add_optimization_passes(&OptimizationOptions { level: 1, ..Default::default() });
+
+    match pm.run(Ir::Source(program)).unwrap().ir {
+        Ir::Source(program) => assert_eq!(program.to_string(), "ident = a -> a\nx = ident\n"),
+        ir => panic!("unexpected {} IR", ir.stage())
+    }
+}
+
+const NATS: &str = "
+ident = a -> a
+true = a -> b -> a
+false = a -> b -> b
+tuple = a -> b -> sel -> sel a b
+zero = tuple true ident
+succ = nat -> tuple false nat
+";
+
+fn partial_eval(code: &str, budget: usize, threshold: usize) -> (String, Vec<String>) {
+    let program = LambdaParser::parse_program(code).unwrap();
+    let mut diagnostics = Vec::new();
+    let program = partial_eval::transform_program(&program, budget, threshold, &mut diagnostics);
+
+    (program.to_string(), diagnostics)
+}
+
+#[test]
+fn test_partial_eval() {
+    let (code, diagnostics) = partial_eval(&format!("{}1 = succ zero\nx = succ (succ zero)\n", NATS), 1000, 100);
+
+    assert!(code.ends_with("\nzero = sel -> sel true ident\nsucc = nat -> tuple false nat\n1 = sel -> sel false zero\nx = sel -> sel false sel -> sel false zero\n"));
+    assert_eq!(diagnostics, vec![
+        "note: folded definition of 'zero'",
+        "note: folded definition of '1'",
+        "note: folded definition of 'x'"
+    ]);
+}
+
+#[test]
+fn test_partial_eval_inner() {
+    let (code, diagnostics) = partial_eval(&format!("{}f = x -> x (succ zero) (x zero)\n", NATS), 1000, 100);
+
+    assert!(code.ends_with("\nf = x -> x (sel -> sel false zero) (x zero)\n"));
+    assert_eq!(diagnostics.last().unwrap(), "note: folded 1 closed terms in definition of 'f'");
+}
+
+#[test]
+fn test_partial_eval_limits() {
+    // the budget is exhausted by the diverging term
+    let (code, _) = partial_eval("omega = (x -> x x) x -> x x\n", 1000, 100);
+    assert_eq!(code, "omega = (x -> x x) x -> x x\n");
+
+    // the normal form exceeds the size threshold
+    let (code, _) = partial_eval(&format!("{}x = succ (succ zero)\n", NATS), 1000, 6);
+    assert!(code.ends_with("\nx = succ sel -> sel false zero\n"));
+}
+
+#[test]
+fn test_partial_eval_shared_budget() {
+    // every term takes one step, but the budget covers the whole definition
+    let code = "ident = a -> a\nf = x -> x (ident ident) (ident ident) (ident ident)\n";
+    let (folded, diagnostics) = partial_eval(code, 2, 100);
+
+    assert_eq!(folded, "ident = a -> a\nf = x -> x ident ident (ident ident)\n");
+    assert_eq!(diagnostics, vec!["note: folded 2 closed terms in definition of 'f'"]);
+}
+
+#[test]
+fn test_partial_eval_recursion() {
+    // folding the fixed point would duplicate the recursive function
+    let code = "y = g -> (f -> f f) f -> g x -> f f x\nloop = y loop -> x -> loop x\n";
+    let (folded, diagnostics) = partial_eval(code, 1000, 100);
+
+    assert_eq!(folded, code);
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn test_partial_eval_capture() {
+    // the global 'k' would be captured by the argument of 'f'
+    let code = "k = a -> a\npair = a -> b -> sel -> sel a b\npair_k = a -> pair k a\nf = k -> k (pair_k pair)\n";
+    let (folded, _) = partial_eval(code, 1000, 100);
+
+    assert_eq!(folded, code);
+}
+
+#[test]
+fn test_eta() {
+    let code = "
+f = a -> a
+pair = a -> b -> sel -> sel a b
+g = x -> f x
+h = a -> b -> pair a b
+k = a -> x -> pair (pair a a) x
+";
+    let program = LambdaParser::parse_program(code).unwrap();
+
+    assert_eq!(
+        eta::transform_program(&program).to_string(),
+        "f = a -> a\npair = a -> b -> sel -> sel a b\ng = f\nh = pair\nk = a -> pair (pair a a)\n"
+    );
+}
+
+#[test]
+fn test_eta_strict() {
+    // reducing these would evaluate 'f f' or 'x x' too early
+    let code = "
+f = a -> a
+w = x -> f f x
+k = x -> (x -> x x) (x -> x x) x
+s = f -> x -> f x x
+u = f -> x -> f f x
+";
+    let program = LambdaParser::parse_program(code).unwrap();
+
+    assert_eq!(eta::transform_program(&program).to_string(), code.trim_start());
+}
+
+#[test]
+fn test_eta_lint() {
+    let code = "pair = a -> b -> sel -> sel a b\nh = a -> b -> pair a b\nf = x -> (y -> pair x y) (z -> h x z)\n";
+    let program = LambdaParser::parse_program(code).unwrap();
+
+    let mut diagnostics = Vec::new();
+    eta::lint_program(&program, &mut diagnostics);
+
+    assert_eq!(diagnostics, vec![
+        "warning: 'a -> b -> pair a b' in definition of 'h' can be reduced to 'pair'",
+        "warning: 'y -> pair x y' in definition of 'f' can be reduced to 'pair x'",
+        "warning: 'z -> h x z' in definition of 'f' can be reduced to 'h x'"
+    ]);
+}
@@ -1,7 +1,9 @@
 use pest::Parser;
 use pest::iterators::Pair as PestPair;
 use pest::iterators::Pairs as PestPairs;
+use pest::Position;
 use pest::error::Error as PestError;
+use pest::error::ErrorVariant;
 use pest_derive::Parser;
 
 use crate::error::Error;
@@ -16,11 +18,55 @@ pub type Pair<'i> = PestPair<'i, Rule>;
 pub type Pairs<'i> = PestPairs<'i, Rule>;
 pub type ParseError = PestError<Rule>;
 
+/// deeper nesting of lambdas and parentheses would overflow the stack of the
+/// parser and of the recursive passes
+pub const MAX_NESTING: usize = 500;
+
+/// finds the first position that is nested deeper than `MAX_NESTING`, a lambda
+/// body extends to the closing parenthesis around it or to the end of its
+/// assignment, which is the next `=` outside of comments
+fn check_nesting(code: &str) -> Result<(), Error> {
+    // the number of lambdas opened inside every open parenthesis
+    let mut lambdas = vec![0];
+    let mut depth = 0;
+    let mut chars = code.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '#' => { chars.find(|&(_, c)| c == '\n'); },
+            '=' => {
+                lambdas = vec![0];
+                depth = 0;
+            },
+            '(' => {
+                lambdas.push(0);
+                depth += 1;
+            },
+            ')' if lambdas.len() > 1 => depth -= 1 + lambdas.pop().unwrap(),
+            '-' if chars.next_if(|&(_, c)| c == '>').is_some() => {
+                *lambdas.last_mut().unwrap() += 1;
+                depth += 1;
+            },
+            _ => ()
+        }
+
+        if depth > MAX_NESTING {
+            let message = format!("lambdas and parentheses are nested deeper than {} levels", MAX_NESTING);
+            let pos = Position::new(code, pos).expect("position of a char is valid");
+
+            return Err(Error::ParseError(Box::new(ParseError::new_from_pos(ErrorVariant::CustomError { message }, pos))));
+        }
+    }
+
+    Ok(())
+}
+
 impl LambdaParser {
     fn parse_rule<'i, T, M>(code: &'i str, rule: Rule, maker: M) -> Result<T, Error>
         where T: 'i, M: Maker<'i, T>
     {
-        let pairs = LambdaParser::parse(rule, code).map_err(Error::ParseError)?;
+        check_nesting(code)?;
+        let pairs = LambdaParser::parse(rule, code).map_err(|e| Error::ParseError(Box::new(e)))?;
 
         from_pairs(pairs, maker)
     }
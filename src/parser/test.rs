@@ -1,5 +1,31 @@
+use std::rc::Rc;
+
 use super::*;
 
+fn lam<'i>(argument: Identifier<'i>, expressions: Vec<Expression<'i>>) -> Lambda<'i> {
+    Lambda { argument, body: Rc::new(app(expressions)), data: () }
+}
+
+fn lambda<'i>(argument: Identifier<'i>, expressions: Vec<Expression<'i>>) -> Expression<'i> {
+    Expression::Lambda(Rc::new(lam(argument, expressions)))
+}
+
+fn paren(expressions: Vec<Expression<'_>>) -> Expression<'_> {
+    Expression::Parenthesis(Rc::new(app(expressions)))
+}
+
+fn app(expressions: Vec<Expression<'_>>) -> Application<'_> {
+    Application { expressions, data: () }
+}
+
+fn assignment<'i>(target: Identifier<'i>, expressions: Vec<Expression<'i>>) -> Assignment<'i> {
+    Assignment { target, value: Rc::new(app(expressions)), data: () }
+}
+
+fn program(assignments: Vec<Assignment<'_>>) -> Program<'_> {
+    Program { assignments, data: () }
+}
+
 #[test]
 fn test_identifier() {
     assert_eq!(LambdaParser::parse_identifier("true"), Ok("true"));
@@ -15,24 +41,24 @@ fn test_identifier() {
 fn test_lambda() {
     assert_eq!(
         LambdaParser::parse_lambda("a -> b"),
-        Ok(Lambda { argument: "a", body: Application { expressions: vec![
+        Ok(lam("a", vec![
             Expression::Identifier("b")
-        ]}})
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_lambda("a -> b -> c"),
-        Ok(Lambda { argument: "a", body: Application { expressions: vec![
-            Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(lam("a", vec![
+            lambda("b", vec![
                 Expression::Identifier("c")
-            ]}})
-        ]}})
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_lambda("a -> b c"),
-        Ok(Lambda { argument: "a", body: Application { expressions: vec![
+        Ok(lam("a", vec![
             Expression::Identifier("b"),
             Expression::Identifier("c")
-        ]}})
+        ]))
     );
 
     assert!(LambdaParser::parse_lambda("(a -> b) -> c").is_err());
@@ -42,27 +68,27 @@ fn test_lambda() {
 fn test_parenthesis() {
     assert_eq!(
         LambdaParser::parse_parenthesis("((a))"),
-        Ok(Application { expressions: vec![
-            Expression::Parenthesis(Application { expressions: vec![
+        Ok(app(vec![
+            paren(vec![
                 Expression::Identifier("a")
-            ]})
-        ]})
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_parenthesis("(a (b c) ((d) e))"),
-        Ok(Application { expressions: vec![
+        Ok(app(vec![
             Expression::Identifier("a"),
-            Expression::Parenthesis(Application { expressions: vec![
+            paren(vec![
                 Expression::Identifier("b"),
                 Expression::Identifier("c"),
-            ]}),
-            Expression::Parenthesis(Application { expressions: vec![
-                Expression::Parenthesis(Application { expressions: vec![
+            ]),
+            paren(vec![
+                paren(vec![
                     Expression::Identifier("d")
-                ]}),
+                ]),
                 Expression::Identifier("e")
-            ]})
-        ]})
+            ])
+        ]))
     );
 }
 
@@ -70,19 +96,19 @@ fn test_parenthesis() {
 fn test_expression() {
     assert_eq!(
         LambdaParser::parse_expression("e -> (a -> a) (c -> c) e"),
-        Ok(Expression::Lambda(Lambda { argument: "e", body: Application { expressions: vec![
-            Expression::Parenthesis(Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
+        Ok(lambda("e", vec![
+            paren(vec![
+                lambda("a", vec![
                     Expression::Identifier("a")
-                ]}})
-            ]}),
-            Expression::Parenthesis(Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "c", body: Application { expressions: vec![
+                ])
+            ]),
+            paren(vec![
+                lambda("c", vec![
                     Expression::Identifier("c")
-                ]}})
-            ]}),
+                ])
+            ]),
             Expression::Identifier("e")
-        ]}}))
+        ]))
     );
 }
 
@@ -90,47 +116,47 @@ fn test_expression() {
 fn test_application() {
     assert_eq!(
         LambdaParser::parse_application("a b"),
-        Ok(Application { expressions: vec![
+        Ok(app(vec![
             Expression::Identifier("a"),
             Expression::Identifier("b")
-        ]})
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_application("a b c"),
-        Ok(Application { expressions: vec![
+        Ok(app(vec![
             Expression::Identifier("a"),
             Expression::Identifier("b"),
             Expression::Identifier("c")
-        ]})
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_application("(a b) c"),
-        Ok(Application { expressions: vec![
-            Expression::Parenthesis(Application { expressions: vec![
+        Ok(app(vec![
+            paren(vec![
                 Expression::Identifier("a"),
                 Expression::Identifier("b"),
-            ]}),
+            ]),
             Expression::Identifier("c")
-        ]})
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_application("a (b c)"),
-        Ok(Application { expressions: vec![
+        Ok(app(vec![
             Expression::Identifier("a"),
-            Expression::Parenthesis(Application { expressions: vec![
+            paren(vec![
                 Expression::Identifier("b"),
                 Expression::Identifier("c"),
-            ]})
-        ]})
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_application("a b -> c"),
-        Ok(Application { expressions: vec![
+        Ok(app(vec![
             Expression::Identifier("a"),
-            Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+            lambda("b", vec![
                 Expression::Identifier("c")
-            ]}})
-        ]})
+            ])
+        ]))
     );
 }
 
@@ -138,23 +164,23 @@ fn test_application() {
 fn test_assignment() {
     assert_eq!(
         LambdaParser::parse_assignment("ident = a -> a"),
-        Ok(Assignment { target: "ident", value: Application { expressions: vec![
-            Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
+        Ok(assignment("ident", vec![
+            lambda("a", vec![
                 Expression::Identifier("a")
-            ]}})
-        ]}})
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_assignment("and = a -> b -> a b false"),
-        Ok(Assignment { target: "and", value: Application { expressions: vec![
-            Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(assignment("and", vec![
+            lambda("a", vec![
+                lambda("b", vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("b"),
                     Expression::Identifier("false")
-                ]}})
-            ]}})
-        ]}})
+                ])
+            ])
+        ]))
     );
 }
 
@@ -162,48 +188,48 @@ fn test_assignment() {
 fn test_program() {
     assert_eq!(
         LambdaParser::parse_program("true = a -> b -> a"),
-        Ok(Program { assignments: vec![
-            Assignment { target: "true", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("true", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("a")
-                    ]}})
-                ]}})
-            ]}}
-        ]})
+                    ])
+                ])
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_program(r"
             true = a -> b -> a
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "true", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("true", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("a")
-                    ]}})
-                ]}})
-            ]}}
-        ]})
+                    ])
+                ])
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_program("true = a -> b -> a\nfalse = a -> b -> b"),
-        Ok(Program { assignments: vec![
-            Assignment { target: "true", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("true", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("a")
-                    ]}})
-                ]}})
-            ]}},
-            Assignment { target: "false", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+                    ])
+                ])
+            ]),
+            assignment("false", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("b")
-                    ]}})
-                ]}})
-            ]}}
-        ]})
+                    ])
+                ])
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_program(r"
@@ -211,29 +237,29 @@ fn test_program() {
             false = a -> b -> b
             not = a -> a false true
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "true", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("true", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("a")
-                    ]}})
-                ]}})
-            ]}},
-            Assignment { target: "false", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+                    ])
+                ])
+            ]),
+            assignment("false", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("b")
-                    ]}})
-                ]}})
-            ]}},
-            Assignment { target: "not", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
+                    ])
+                ])
+            ]),
+            assignment("not", vec![
+                lambda("a", vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("false"),
                     Expression::Identifier("true")
-                ]}})
-            ]}}
-        ]})
+                ])
+            ])
+        ]))
     );
 }
 
@@ -251,29 +277,29 @@ fn test_program_newlines() {
                 b -> b
             not = a -> a false true
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "true", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("true", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("a")
-                    ]}})
-                ]}})
-            ]}},
-            Assignment { target: "false", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+                    ])
+                ])
+            ]),
+            assignment("false", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("b")
-                    ]}})
-                ]}})
-            ]}},
-            Assignment { target: "not", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
+                    ])
+                ])
+            ]),
+            assignment("not", vec![
+                lambda("a", vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("false"),
                     Expression::Identifier("true")
-                ]}})
-            ]}}
-        ]})
+                ])
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_program(r"
@@ -284,26 +310,26 @@ fn test_program_newlines() {
             z = (a b
                 )
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "x", value: Application { expressions: vec![
-                Expression::Parenthesis(Application { expressions: vec![
+        Ok(program(vec![
+            assignment("x", vec![
+                paren(vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("b")
-                ]})
-            ]}},
-            Assignment { target: "y", value: Application { expressions: vec![
-                Expression::Parenthesis(Application { expressions: vec![
+                ])
+            ]),
+            assignment("y", vec![
+                paren(vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("b")
-                ]})
-            ]}},
-            Assignment { target: "z", value: Application { expressions: vec![
-                Expression::Parenthesis(Application { expressions: vec![
+                ])
+            ]),
+            assignment("z", vec![
+                paren(vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("b")
-                ]})
-            ]}}
-        ]})
+                ])
+            ])
+        ]))
     );
 }
 
@@ -317,17 +343,17 @@ fn test_program_inner_newlines() {
                 )
             y = a
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "x", value: Application { expressions: vec![
-                Expression::Parenthesis(Application { expressions: vec![
+        Ok(program(vec![
+            assignment("x", vec![
+                paren(vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("b")
-                ]})
-            ]}},
-            Assignment { target: "y", value: Application { expressions: vec![
+                ])
+            ]),
+            assignment("y", vec![
                 Expression::Identifier("a"),
-            ]}}
-        ]})
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_program(r"
@@ -336,19 +362,19 @@ fn test_program_inner_newlines() {
             )
             y = a
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "x", value: Application { expressions: vec![
-                Expression::Parenthesis(Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("x", vec![
+                paren(vec![
+                    lambda("a", vec![
                         Expression::Identifier("b"),
                         Expression::Identifier("c")
-                    ]}}),
-                ]})
-            ]}},
-            Assignment { target: "y", value: Application { expressions: vec![
+                    ]),
+                ])
+            ]),
+            assignment("y", vec![
                 Expression::Identifier("a"),
-            ]}}
-        ]})
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_program(r"
@@ -357,21 +383,21 @@ fn test_program_inner_newlines() {
             )
             y = a
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "x", value: Application { expressions: vec![
-                Expression::Parenthesis(Application { expressions: vec![
-                    Expression::Parenthesis(Application { expressions: vec![
-                        Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("x", vec![
+                paren(vec![
+                    paren(vec![
+                        lambda("a", vec![
                             Expression::Identifier("b"),
-                        ]}}),
-                    ]}),
+                        ]),
+                    ]),
                     Expression::Identifier("c")
-                ]})
-            ]}},
-            Assignment { target: "y", value: Application { expressions: vec![
+                ])
+            ]),
+            assignment("y", vec![
                 Expression::Identifier("a"),
-            ]}}
-        ]})
+            ])
+        ]))
     );
 
     assert!(
@@ -409,29 +435,29 @@ fn test_program_comments() {
             # unary bool combinators
             not = a -> a false true
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "true", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("true", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("a")
-                    ]}})
-                ]}})
-            ]}},
-            Assignment { target: "false", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+                    ])
+                ])
+            ]),
+            assignment("false", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("b")
-                    ]}})
-                ]}})
-            ]}},
-            Assignment { target: "not", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
+                    ])
+                ])
+            ]),
+            assignment("not", vec![
+                lambda("a", vec![
                     Expression::Identifier("a"),
                     Expression::Identifier("false"),
                     Expression::Identifier("true")
-                ]}})
-            ]}}
-        ]})
+                ])
+            ])
+        ]))
     );
     assert_eq!(
         LambdaParser::parse_program(r"# foo
@@ -440,14 +466,48 @@ fn test_program_comments() {
             # the two possible bools
             true = a -> b -> a
         "),
-        Ok(Program { assignments: vec![
-            Assignment { target: "true", value: Application { expressions: vec![
-                Expression::Lambda(Lambda { argument: "a", body: Application { expressions: vec![
-                    Expression::Lambda(Lambda { argument: "b", body: Application { expressions: vec![
+        Ok(program(vec![
+            assignment("true", vec![
+                lambda("a", vec![
+                    lambda("b", vec![
                         Expression::Identifier("a")
-                    ]}})
-                ]}})
-            ]}}
-        ]})
+                    ])
+                ])
+            ])
+        ]))
     );
 }
+
+#[test]
+fn test_program_layouts() {
+    // the last assignment needs no newline and '=' and '->' may start a line
+    assert_eq!(LambdaParser::parse_program(""), Ok(program(vec![])));
+    assert_eq!(
+        LambdaParser::parse_program("x = a\ny = b"),
+        Ok(program(vec![
+            assignment("x", vec![Expression::Identifier("a")]),
+            assignment("y", vec![Expression::Identifier("b")])
+        ]))
+    );
+    assert_eq!(
+        LambdaParser::parse_assignment("x\n= a"),
+        Ok(assignment("x", vec![Expression::Identifier("a")]))
+    );
+    assert_eq!(
+        LambdaParser::parse_lambda("a\n-> b"),
+        Ok(lam("a", vec![Expression::Identifier("b")]))
+    );
+
+    assert!(LambdaParser::parse_program("x = a y = b").is_err());
+    assert!(LambdaParser::parse_program("x = a\n\n= b\n").is_err());
+    assert!(LambdaParser::parse_program("x = (a)\n-> b\n").is_err());
+}
+
+#[test]
+fn test_long_application() {
+    let code = format!("x = f{}\n", " a".repeat(100_000));
+    let program = LambdaParser::parse_program(&code).unwrap();
+
+    assert_eq!(program.assignments[0].value.expressions.len(), 100_001);
+    assert_eq!(program.to_string(), code);
+}
@@ -0,0 +1,364 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::error::Error;
+use crate::ast::nodata;
+use crate::analyze::compute_lambda_captures;
+use crate::analyze::compute_continuations;
+use crate::analyze::compute_continuation_captures;
+use crate::analyze::textual;
+use crate::analyze::anf;
+use crate::analyze::closure_conversion;
+use crate::analyze::defunctionalize;
+use crate::optimize::Inline;
+use crate::optimize::PartialEval;
+use crate::optimize::EtaReduce;
+use crate::optimize::EtaLint;
+use crate::optimize::OptimizationOptions;
+
+#[derive(Clone)]
+pub enum Ir<'i> {
+    Source(nodata::Program<'i>),
+    LambdaCaptures(compute_lambda_captures::Program<'i>),
+    Continuations(compute_continuations::Program<'i>),
+    ContinuationCaptures(compute_continuation_captures::Program<'i>),
+    Anf(anf::Program<'i>),
+    Flat(closure_conversion::Program<'i>),
+    Defunctionalized(defunctionalize::Program<'i>)
+}
+
+impl Ir<'_> {
+    pub fn stage(&self) -> &'static str {
+        match self {
+            Ir::Source(_) => "source",
+            Ir::LambdaCaptures(_) => "lambda-captures",
+            Ir::Continuations(_) => "continuations",
+            Ir::ContinuationCaptures(_) => "continuation-captures",
+            Ir::Anf(_) => "anf",
+            Ir::Flat(_) => "flat",
+            Ir::Defunctionalized(_) => "defunctionalized"
+        }
+    }
+}
+
+impl Display for Ir<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Ir::Source(program) => write!(f, "{}", program),
+            Ir::LambdaCaptures(program) => write!(f, "{:#?}", program),
+            Ir::Continuations(program) => write!(f, "{:#?}", program),
+            Ir::ContinuationCaptures(program) => write!(f, "{}", textual::print_program(program)),
+            Ir::Anf(program) => write!(f, "{}", program),
+            Ir::Flat(program) => write!(f, "{}", program),
+            Ir::Defunctionalized(program) => write!(f, "{}", program)
+        }
+    }
+}
+
+/// what the passes report besides the IR they produce
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassOutput {
+    pub diagnostics: Vec<String>,
+    pub dumps: Vec<(&'static str, String)>,
+    pub timings: Vec<(&'static str, Duration)>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassError {
+    UnknownPass(String),
+    UnexpectedIr {
+        pass: &'static str,
+        expected: &'static str,
+        found: &'static str
+    },
+    /// a pass returned an error, output holds what the passes before it
+    /// reported so it is not lost
+    Failed {
+        pass: &'static str,
+        error: Box<Error>,
+        output: PassOutput
+    }
+}
+
+impl Display for PassError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            PassError::UnknownPass(name) => write!(f, "unknown pass '{}'", name),
+            PassError::UnexpectedIr { pass, expected, found } => write!(f,
+                "pass '{}' expects {} IR, but got {} IR",
+                pass, expected, found
+            ),
+            PassError::Failed { pass, error, .. } => match error.as_ref() {
+                Error::PassError(e) => write!(f, "{}", e),
+                _ => write!(f, "pass '{}' failed", pass)
+            }
+        }
+    }
+}
+
+pub(crate) fn unexpected_ir(pass: &'static str, expected: &'static str, ir: &Ir<'_>) -> Error {
+    Error::PassError(PassError::UnexpectedIr {
+        pass,
+        expected,
+        found: ir.stage()
+    })
+}
+
+pub trait Pass {
+    fn name(&self) -> &'static str;
+    fn run<'i>(&self, ir: Ir<'i>, diagnostics: &mut Vec<String>) -> Result<Ir<'i>, Error>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CheckNames;
+
+impl Pass for CheckNames {
+    fn name(&self) -> &'static str {
+        "names"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, diagnostics: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Source(program) => {
+                compute_lambda_captures::check_program(&program, diagnostics);
+                Ok(Ir::Source(program))
+            }
+            ir => Err(unexpected_ir(self.name(), "source", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeLambdaCaptures;
+
+impl Pass for ComputeLambdaCaptures {
+    fn name(&self) -> &'static str {
+        "lambda-captures"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Source(program) => Ok(Ir::LambdaCaptures(
+                compute_lambda_captures::transform_program(&program)
+            )),
+            ir => Err(unexpected_ir(self.name(), "source", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeContinuations;
+
+impl Pass for ComputeContinuations {
+    fn name(&self) -> &'static str {
+        "continuations"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::LambdaCaptures(program) => Ok(Ir::Continuations(
+                compute_continuations::transform_program(&program)
+            )),
+            ir => Err(unexpected_ir(self.name(), "lambda-captures", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeContinuationCaptures;
+
+impl Pass for ComputeContinuationCaptures {
+    fn name(&self) -> &'static str {
+        "continuation-captures"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Continuations(program) => Ok(Ir::ContinuationCaptures(
+                compute_continuation_captures::transform_program(&program)
+            )),
+            ir => Err(unexpected_ir(self.name(), "continuations", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeAnf;
+
+impl Pass for ComputeAnf {
+    fn name(&self) -> &'static str {
+        "anf"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::ContinuationCaptures(program) => Ok(Ir::Anf(
+                anf::transform_program(&program)
+            )),
+            ir => Err(unexpected_ir(self.name(), "continuation-captures", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClosureConversion;
+
+impl Pass for ClosureConversion {
+    fn name(&self) -> &'static str {
+        "closure-conversion"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Anf(program) => Ok(Ir::Flat(
+                closure_conversion::transform_program(&program)
+            )),
+            ir => Err(unexpected_ir(self.name(), "anf", &ir))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Defunctionalize;
+
+impl Pass for Defunctionalize {
+    fn name(&self) -> &'static str {
+        "defunctionalize"
+    }
+
+    fn run<'i>(&self, ir: Ir<'i>, _: &mut Vec<String>) -> Result<Ir<'i>, Error> {
+        match ir {
+            Ir::Flat(program) => Ok(Ir::Defunctionalized(
+                defunctionalize::transform_program(&program)
+            )),
+            ir => Err(unexpected_ir(self.name(), "flat", &ir))
+        }
+    }
+}
+
+pub fn pass_from_name(name: &str, options: &OptimizationOptions) -> Result<Box<dyn Pass>, Error> {
+    Ok(match name {
+        "inline" => Box::new(Inline { threshold: options.inline_threshold }),
+        "eta" => Box::new(EtaReduce),
+        "lint-eta" => Box::new(EtaLint),
+        "partial-eval" => Box::new(PartialEval { budget: options.eval_budget, threshold: options.fold_threshold }),
+        "names" => Box::new(CheckNames),
+        "lambda-captures" => Box::new(ComputeLambdaCaptures),
+        "continuations" => Box::new(ComputeContinuations),
+        "continuation-captures" => Box::new(ComputeContinuationCaptures),
+        "anf" => Box::new(ComputeAnf),
+        "closure-conversion" => Box::new(ClosureConversion),
+        "defunctionalize" => Box::new(Defunctionalize),
+        _ => return Err(Error::PassError(PassError::UnknownPass(name.to_string())))
+    })
+}
+
+pub struct PassResult<'i> {
+    pub ir: Ir<'i>,
+    pub output: PassOutput
+}
+
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    dump_after: Vec<String>,
+    dump_all: bool,
+    time_passes: bool
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn analysis() -> Self {
+        let mut pm = PassManager::new();
+        pm.add_check_passes();
+        pm.add_analysis_passes();
+        pm
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    /// checks the source program, this comes before any pass that can
+    /// remove code
+    pub fn add_check_passes(&mut self) {
+        self.add_pass(Box::new(CheckNames));
+    }
+
+    pub fn add_analysis_passes(&mut self) {
+        self.add_pass(Box::new(ComputeLambdaCaptures));
+        self.add_pass(Box::new(ComputeContinuations));
+        self.add_pass(Box::new(ComputeContinuationCaptures));
+    }
+
+    /// lowers the continuation-captures IR to the flat IR
+    pub fn add_flat_passes(&mut self) {
+        self.add_pass(Box::new(ComputeAnf));
+        self.add_pass(Box::new(ClosureConversion));
+    }
+
+    pub fn add_lint_passes(&mut self) {
+        self.add_pass(Box::new(EtaLint));
+    }
+
+    pub fn add_optimization_passes(&mut self, options: &OptimizationOptions) {
+        if options.level >= 2 {
+            self.add_pass(Box::new(PartialEval { budget: options.eval_budget, threshold: options.fold_threshold }));
+        }
+
+        if options.level >= 1 {
+            self.add_pass(Box::new(EtaReduce));
+            self.add_pass(Box::new(Inline { threshold: options.inline_threshold }));
+        }
+    }
+
+    pub fn dump_after(&mut self, name: &str) {
+        self.dump_after.push(name.to_string());
+    }
+
+    pub fn dump_all(&mut self, enable: bool) {
+        self.dump_all = enable;
+    }
+
+    pub fn time_passes(&mut self, enable: bool) {
+        self.time_passes = enable;
+    }
+
+    pub fn run<'i>(&self, mut ir: Ir<'i>) -> Result<PassResult<'i>, Error> {
+        for name in self.dump_after.iter() {
+            if !self.passes.iter().any(|pass| pass.name() == name) {
+                return Err(Error::PassError(PassError::UnknownPass(name.clone())));
+            }
+        }
+
+        let mut output = PassOutput::default();
+
+        for pass in self.passes.iter() {
+            let start = Instant::now();
+            ir = pass.run(ir, &mut output.diagnostics).map_err(|error| Error::PassError(PassError::Failed {
+                pass: pass.name(),
+                error: Box::new(error),
+                output: std::mem::take(&mut output)
+            }))?;
+
+            if self.time_passes {
+                output.timings.push((pass.name(), start.elapsed()));
+            }
+
+            if self.dump_all || self.dump_after.iter().any(|name| name == pass.name()) {
+                output.dumps.push((pass.name(), ir.to_string()));
+            }
+        }
+
+        Ok(PassResult { ir, output })
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,117 @@
+use super::*;
+use crate::parser::LambdaParser;
+
+const CODE: &str = "ident = a -> a\nx = ident ident\n";
+
+#[test]
+fn test_analysis_pipeline() {
+    let program = LambdaParser::parse_program(CODE).unwrap();
+
+    let mut pm = PassManager::analysis();
+    pm.dump_after("lambda-captures");
+    pm.time_passes(true);
+
+    let result = pm.run(Ir::Source(program)).unwrap();
+
+    assert_eq!(result.ir.stage(), "continuation-captures");
+    assert!(result.output.diagnostics.is_empty());
+    assert_eq!(result.output.dumps.len(), 1);
+    assert_eq!(result.output.dumps[0].0, "lambda-captures");
+    assert_eq!(result.output.timings.len(), 4);
+}
+
+#[test]
+fn test_flat_pipeline() {
+    let program = LambdaParser::parse_program(CODE).unwrap();
+
+    let mut pm = PassManager::analysis();
+    pm.add_flat_passes();
+    pm.dump_after("closure-conversion");
+    pm.time_passes(true);
+
+    let result = pm.run(Ir::Source(program)).unwrap();
+
+    // the dump describes the program that flat backends generate code from
+    assert_eq!(result.ir.stage(), "flat");
+    assert_eq!(result.output.dumps, vec![("closure-conversion", result.ir.to_string())]);
+
+    let names: Vec<_> = result.output.timings.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, vec!["names", "lambda-captures", "continuations", "continuation-captures", "anf", "closure-conversion"]);
+}
+
+#[test]
+fn test_diagnostics_before_optimization() {
+    // beta reduction removes the only reference to 'undefined'
+    let program = LambdaParser::parse_program("x = (a -> b -> b) undefined\n").unwrap();
+
+    for level in 0..=2 {
+        let mut pm = PassManager::new();
+        pm.add_check_passes();
+        pm.add_optimization_passes(&OptimizationOptions { level, ..Default::default() });
+        pm.add_analysis_passes();
+
+        let result = pm.run(Ir::Source(program.clone())).unwrap();
+        assert_eq!(result.output.diagnostics, vec!["error: undefined name 'undefined' in definition of 'x'"], "at -O{}", level);
+    }
+}
+
+#[test]
+fn test_dump_all() {
+    let program = LambdaParser::parse_program(CODE).unwrap();
+
+    let mut pm = PassManager::analysis();
+    pm.dump_all(true);
+
+    let result = pm.run(Ir::Source(program)).unwrap();
+
+    let names: Vec<_> = result.output.dumps.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, vec!["names", "lambda-captures", "continuations", "continuation-captures"]);
+}
+
+#[test]
+fn test_pass_errors() {
+    let program = LambdaParser::parse_program(CODE).unwrap();
+
+    let mut pm = PassManager::analysis();
+    pm.dump_after("does-not-exist");
+    assert_eq!(
+        pm.run(Ir::Source(program.clone())).err(),
+        Some(Error::PassError(PassError::UnknownPass(String::from("does-not-exist"))))
+    );
+
+    let mut pm = PassManager::new();
+    pm.add_pass(pass_from_name("continuations", &Default::default()).unwrap());
+    assert_eq!(
+        pm.run(Ir::Source(program)).err(),
+        Some(Error::PassError(PassError::Failed {
+            pass: "continuations",
+            error: Box::new(Error::PassError(PassError::UnexpectedIr {
+                pass: "continuations",
+                expected: "lambda-captures",
+                found: "source"
+            })),
+            output: PassOutput::default()
+        }))
+    );
+}
+
+#[test]
+fn test_pass_error_output() {
+    let program = LambdaParser::parse_program("f = x -> ident x\n").unwrap();
+
+    let mut pm = PassManager::new();
+    pm.add_lint_passes();
+    pm.add_pass(pass_from_name("continuations", &Default::default()).unwrap());
+    pm.dump_after("lint-eta");
+    pm.time_passes(true);
+
+    // the lint and the dump before the failing pass are kept
+    match pm.run(Ir::Source(program)).err() {
+        Some(Error::PassError(PassError::Failed { pass: "continuations", output, .. })) => {
+            assert_eq!(output.diagnostics.len(), 1);
+            assert_eq!(output.dumps, vec![("lint-eta", String::from("f = x -> ident x\n"))]);
+            assert_eq!(output.timings.len(), 1);
+        }
+        _ => panic!("expected the continuations pass to fail")
+    }
+}